@@ -0,0 +1,105 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::vertex::Vertex;
+
+/// Returns the maximum number of fixed handicap stones that can be placed on
+/// a board of the given size.
+///
+/// # Arguments
+///
+/// * `size` - the width and height of the board
+///
+pub fn max_fixed_handicap(size: usize) -> usize {
+    if size < 7 {
+        0
+    } else if size == 7 || size % 2 == 0 {
+        4  // there is no center point, or it is too close to the corners
+    } else {
+        9
+    }
+}
+
+/// Returns the vertices of the given number of handicap stones, placed as
+/// described in section 4.1.1 of the GTP specification. Returns `None` if
+/// the number of stones is not supported for the given board size.
+///
+/// # Arguments
+///
+/// * `size` - the width and height of the board
+/// * `num_stones` - the number of handicap stones
+///
+pub fn get_fixed_handicap(size: usize, num_stones: usize) -> Option<Vec<Vertex>> {
+    if num_stones < 2 || num_stones > max_fixed_handicap(size) {
+        return None;
+    }
+
+    let edge = if size >= 13 { 3 } else { 2 };
+    let (low, mid, high) = (edge, size / 2, size - edge - 1);
+    let mut out = [(low, low), (high, high), (low, high), (high, low)].iter()
+        .take(num_stones)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if num_stones >= 6 {
+        out.extend_from_slice(&[(low, mid), (high, mid)]);
+    }
+
+    if num_stones >= 8 {
+        out.extend_from_slice(&[(mid, low), (mid, high)]);
+    }
+
+    if num_stones >= 5 && num_stones % 2 == 1 {
+        out.push((mid, mid));
+    }
+
+    Some(out.into_iter().map(|(x, y)| Vertex { x, y }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_string(vertices: Option<Vec<Vertex>>) -> Option<String> {
+        vertices.map(|vertices| {
+            vertices.iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+    }
+
+    #[test]
+    fn fixed_19() {
+        assert_eq!(to_string(get_fixed_handicap(19, 1)), None);
+        assert_eq!(to_string(get_fixed_handicap(19, 2)), Some("d4 q16".to_string()));
+        assert_eq!(to_string(get_fixed_handicap(19, 3)), Some("d4 q16 d16".to_string()));
+        assert_eq!(to_string(get_fixed_handicap(19, 4)), Some("d4 q16 d16 q4".to_string()));
+        assert_eq!(to_string(get_fixed_handicap(19, 5)), Some("d4 q16 d16 q4 k10".to_string()));
+        assert_eq!(to_string(get_fixed_handicap(19, 6)), Some("d4 q16 d16 q4 d10 q10".to_string()));
+        assert_eq!(to_string(get_fixed_handicap(19, 7)), Some("d4 q16 d16 q4 d10 q10 k10".to_string()));
+        assert_eq!(to_string(get_fixed_handicap(19, 8)), Some("d4 q16 d16 q4 d10 q10 k4 k16".to_string()));
+        assert_eq!(to_string(get_fixed_handicap(19, 9)), Some("d4 q16 d16 q4 d10 q10 k4 k16 k10".to_string()));
+        assert_eq!(to_string(get_fixed_handicap(19, 10)), None);
+    }
+
+    #[test]
+    fn fixed_small() {
+        assert_eq!(to_string(get_fixed_handicap(9, 2)), Some("c3 g7".to_string()));
+        assert_eq!(to_string(get_fixed_handicap(9, 5)), Some("c3 g7 c7 g3 e5".to_string()));
+        assert_eq!(to_string(get_fixed_handicap(13, 3)), Some("d4 k10 d10".to_string()));
+        assert_eq!(to_string(get_fixed_handicap(8, 5)), None);
+        assert_eq!(to_string(get_fixed_handicap(5, 2)), None);
+    }
+}
@@ -13,18 +13,26 @@
 // limitations under the License.
 
 use regex::Regex;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::env;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Read};
-use std::time::Instant;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use dg_go::utils::score::{Score, StoneStatus};
-use dg_go::utils::sgf::Sgf;
-use dg_go::{DEFAULT_KOMI, Board, Color};
+use dg_go::utils::sgf::{GameTree, SgfError};
+use dg_go::{DEFAULT_KOMI, DEFAULT_SIZE, MAX_SIZE, Board, Color, Rules, Superko};
 use dg_mcts::time_control;
 use dg_mcts as mcts;
-use dg_utils::config;
+use dg_nn::Network;
+use dg_utils::config::{Config, SearchParams};
 
+mod handicap;
 mod ponder_service;
 mod time_settings;
 mod vertex;
@@ -36,13 +44,16 @@ use dg_mcts::tree::GreedyPath;
 
 /// List containing all implemented commands, this is used to implement
 /// the `list_commands` and `known_command` commands.
-const KNOWN_COMMANDS: [&str; 24] = [
+const KNOWN_COMMANDS: [&str; 37] = [
     "protocol_version", "name", "version", "gomill-describe_engine", "gomill-cpu_time",
     "boardsize", "clear_board", "komi", "play",
     "list_commands", "known_command", "showboard", "genmove", "reg_genmove",
     "kgs-genmove_cleanup", "gomill-explain_last_move", "undo",
     "time_settings", "kgs-time_settings", "time_left", "quit",
-    "final_score", "final_status_list", "loadsgf"
+    "final_score", "final_status_list", "loadsgf", "lz-analyze",
+    "kata-analyze", "fixed_handicap", "place_free_handicap", "set_free_handicap",
+    "kgs-rules", "dg-set_param", "dg-get_param", "dg-list_params",
+    "dg-save_tree", "dg-load_tree", "dg-load_weights", "dg-network_info"
 ];
 
 #[derive(Clone, Debug, PartialEq)]
@@ -62,6 +73,12 @@ impl GenMoveMode {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AnalyzeMode {
+    Lz,
+    Kata(bool)  // whether to include the ownership
+}
+
 #[derive(Debug, PartialEq)]
 enum Command {
     Pass,  // do nothing
@@ -75,6 +92,9 @@ enum Command {
     ExplainLastMove,  // write a description of why the last move was played
     Komi(f32),  // set the komi
     Play(Color, Vertex),  // play a stone of the given color at the given vertex
+    FixedHandicap(usize),  // place N handicap stones at the standard vertices
+    PlaceFreeHandicap(usize),  // place N handicap stones at vertices chosen by the engine
+    SetFreeHandicap(Vec<Vertex>),  // place handicap stones at the given vertices
     ListCommands,  // list all available commands
     KnownCommand(String),  // tell whether a command is known
     ShowBoard,  // write the position to stdout
@@ -88,6 +108,16 @@ enum Command {
     TimeSettingsCanadian(f32, f32, usize),  // set the time settings
     TimeSettingsByoYomi(f32, f32, usize),  // set the time settings
     TimeLeft(Color, f32, usize),  // set the remaining time for the given color
+    LzAnalyze(Option<Color>, usize),  // write the analysis every N centiseconds until the next command
+    KataAnalyze(Option<Color>, usize, bool),  // write the analysis (and ownership) every N centiseconds until the next command
+    KgsRules(Rules),  // set the rules of the game
+    SetParam(String, String),  // set the value of a search parameter
+    GetParam(String),  // write the value of a search parameter
+    ListParams,  // write the value of all search parameters
+    SaveTree(String),  // save the search tree to a file
+    LoadTree(String),  // load the search tree from a file
+    LoadWeights(String),  // replace the network weights with the ones in a file
+    NetworkInfo,  // write a description of the network and its weights
     Quit  // quit
 }
 
@@ -114,6 +144,9 @@ lazy_static! {
     static ref BOARD_SIZE: Regex = Regex::new(r"^boardsize +([0-9]+)").unwrap();
     static ref KOMI: Regex = Regex::new(r"^komi +(-?[0-9\.]+)").unwrap();
     static ref PLAY: Regex = Regex::new(r"^play +([bBwW]) +([a-z][0-9]+|pass)").unwrap();
+    static ref FIXED_HANDICAP: Regex = Regex::new(r"^fixed_handicap +([0-9]+)").unwrap();
+    static ref PLACE_FREE_HANDICAP: Regex = Regex::new(r"^place_free_handicap +([0-9]+)").unwrap();
+    static ref SET_FREE_HANDICAP: Regex = Regex::new(r"^set_free_handicap((?: +[a-z][0-9]+)+)").unwrap();
     static ref KNOWN_COMMAND: Regex = Regex::new(r"^known_command +([^ ]+)").unwrap();
     static ref GENMOVE: Regex = Regex::new(r"^genmove +([bw])").unwrap();
     static ref REG_GENMOVE: Regex = Regex::new(r"^reg_genmove +([bw])").unwrap();
@@ -126,15 +159,46 @@ lazy_static! {
     static ref KGS_TIME_SETTINGS_BYOYOMI: Regex = Regex::new(r"^kgs-time_settings +byoyomi +([0-9]+\.?[0-9]*) +([0-9]+\.?[0-9]*) +([0-9]+)").unwrap();
     static ref KGS_TIME_SETTINGS_CANADIAN: Regex = Regex::new(r"^kgs-time_settings +canadian +([0-9]+\.?[0-9]*) +([0-9]+\.?[0-9]*) +([0-9]+)").unwrap();
     static ref TIME_LEFT: Regex = Regex::new(r"^time_left +([bBwW]) +([0-9]+\.?[0-9]*) +([0-9]+)").unwrap();
+    static ref LZ_ANALYZE: Regex = Regex::new(r"^lz-analyze(?: +(b|w|black|white))?(?: +(?:interval +)?([0-9]+))?\b").unwrap();
+    static ref SET_PARAM: Regex = Regex::new(r"^dg-set_param +([a-z_]+) +([^ ]+)").unwrap();
+    static ref GET_PARAM: Regex = Regex::new(r"^dg-get_param +([a-z_]+)").unwrap();
+    static ref SAVE_TREE: Regex = Regex::new(r"(?i)^dg-save_tree +(.*[^ ])").unwrap();
+    static ref LOAD_TREE: Regex = Regex::new(r"(?i)^dg-load_tree +(.*[^ ])").unwrap();
+    static ref LOAD_WEIGHTS: Regex = Regex::new(r"(?i)^dg-load_weights +(.*[^ ])").unwrap();
+    static ref KGS_RULES: Regex = Regex::new(r"^kgs-rules +([a-z_\-]+)").unwrap();
+    static ref KATA_ANALYZE: Regex = Regex::new(r"^kata-analyze(?: +(b|w|black|white))?(?: +(?:interval +)?([0-9]+))?(?: +ownership +(true|false))?\b").unwrap();
+}
+
+/// The interval (in centiseconds) between each analysis if nothing else has
+/// been specified.
+const DEFAULT_ANALYZE_INTERVAL: usize = 100;
+
+/// The network evaluations written by `kata-analyze`. These only depend on
+/// the position being analyzed, and not on the search tree, so they are
+/// computed once per analysis instead of once per report.
+#[derive(Default)]
+struct AnalysisCache {
+    /// The ownership of the analyzed position.
+    ownership: Option<Option<Vec<f32>>>,
+
+    /// The score lead after playing each candidate move.
+    score_lead: HashMap<usize, Option<f32>>
 }
 
 struct Gtp {
+    config: Arc<Config>,
     ponder: PonderService,
+    weights: Option<String>,
     history: Vec<Board>,
+    board_size: usize,
     komi: f32,
+    rules: Rules,
+    handicap: usize,
     time_settings: [Box<time_settings::TimeSettings>; 3],
     explain_last_move: String,
-    finished_board: Option<Result<Board, &'static str>>
+    finished_board: Option<Result<Board, &'static str>>,
+    analyze: Option<(AnalyzeMode, Color, Duration)>,
+    analysis_cache: AnalysisCache
 }
 
 impl Gtp {
@@ -147,6 +211,9 @@ impl Gtp {
     /// * `line` -
     /// 
     fn parse_command(id: Option<usize>, line: &str) -> Result<(Option<usize>, Command), &str> {
+        // file names are case sensitive, so they are matched against the
+        // original line
+        let original = line;
         let line = &line.to_lowercase();
 
         if line == "protocol_version" {
@@ -170,6 +237,20 @@ impl Gtp {
             let vertex = caps[2].parse::<Vertex>().map_err(|_| "syntax error")?;
 
             Ok((id, Command::Play(color, vertex)))
+        } else if let Some(caps) = FIXED_HANDICAP.captures(line) {
+            let num_stones = caps[1].parse::<usize>().map_err(|_| "syntax error")?;
+
+            Ok((id, Command::FixedHandicap(num_stones)))
+        } else if let Some(caps) = PLACE_FREE_HANDICAP.captures(line) {
+            let num_stones = caps[1].parse::<usize>().map_err(|_| "syntax error")?;
+
+            Ok((id, Command::PlaceFreeHandicap(num_stones)))
+        } else if let Some(caps) = SET_FREE_HANDICAP.captures(line) {
+            let vertices = caps[1].split_whitespace()
+                .map(|vertex| vertex.parse::<Vertex>().map_err(|_| "syntax error"))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok((id, Command::SetFreeHandicap(vertices)))
         } else if line == "list_commands" {
             Ok((id, Command::ListCommands))
         } else if let Some(caps) = KNOWN_COMMAND.captures(line) {
@@ -181,7 +262,7 @@ impl Gtp {
         } else if let Some(caps) = GENMOVE.captures(line) {
             let color = caps[1].parse::<Color>().map_err(|_| "syntax error")?;
 
-            Ok((id, Command::GenMove(color, if *config::TROMP_TAYLOR { GenMoveMode::CleanUp } else { GenMoveMode::Normal })))
+            Ok((id, Command::GenMove(color, GenMoveMode::Normal)))
         } else if line == "final_score" {
             Ok((id, Command::FinalScore))
         } else if let Some(caps) = FINAL_STATUS_LIST.captures(line) {
@@ -196,6 +277,10 @@ impl Gtp {
             let color = caps[1].parse::<Color>().map_err(|_| "syntax error")?;
 
             Ok((id, Command::GenMove(color, GenMoveMode::CleanUp)))
+        } else if let Some(caps) = KGS_RULES.captures(line) {
+            let rules = caps[1].parse::<Rules>().map_err(|_| "unknown rules")?;
+
+            Ok((id, Command::KgsRules(rules)))
         } else if line == "undo" {
             Ok((id, Command::Undo))
         } else if let Some(caps) = LOADSGF.captures(line) {
@@ -250,6 +335,43 @@ impl Gtp {
             let byo_yomi_stones = caps[3].parse::<usize>().map_err(|_| "syntax error")?;
 
             Ok((id, Command::TimeLeft(color, main_time, byo_yomi_stones)))
+        } else if let Some(caps) = LZ_ANALYZE.captures(line) {
+            let color = match caps.get(1) {
+                Some(color) => Some(color.as_str().parse::<Color>().map_err(|_| "syntax error")?),
+                None => None
+            };
+            let interval = match caps.get(2) {
+                Some(interval) => interval.as_str().parse::<usize>().map_err(|_| "syntax error")?,
+                None => DEFAULT_ANALYZE_INTERVAL
+            };
+
+            Ok((id, Command::LzAnalyze(color, if interval > 0 { interval } else { DEFAULT_ANALYZE_INTERVAL })))
+        } else if let Some(caps) = KATA_ANALYZE.captures(line) {
+            let color = match caps.get(1) {
+                Some(color) => Some(color.as_str().parse::<Color>().map_err(|_| "syntax error")?),
+                None => None
+            };
+            let interval = match caps.get(2) {
+                Some(interval) => interval.as_str().parse::<usize>().map_err(|_| "syntax error")?,
+                None => DEFAULT_ANALYZE_INTERVAL
+            };
+            let ownership = caps.get(3).map(|x| x.as_str() == "true").unwrap_or(false);
+
+            Ok((id, Command::KataAnalyze(color, if interval > 0 { interval } else { DEFAULT_ANALYZE_INTERVAL }, ownership)))
+        } else if let Some(caps) = SET_PARAM.captures(line) {
+            Ok((id, Command::SetParam(caps[1].to_string(), caps[2].to_string())))
+        } else if let Some(caps) = GET_PARAM.captures(line) {
+            Ok((id, Command::GetParam(caps[1].to_string())))
+        } else if line == "dg-list_params" {
+            Ok((id, Command::ListParams))
+        } else if let Some(caps) = SAVE_TREE.captures(original) {
+            Ok((id, Command::SaveTree(caps[1].to_string())))
+        } else if let Some(caps) = LOAD_TREE.captures(original) {
+            Ok((id, Command::LoadTree(caps[1].to_string())))
+        } else if let Some(caps) = LOAD_WEIGHTS.captures(original) {
+            Ok((id, Command::LoadWeights(caps[1].to_string())))
+        } else if line == "dg-network_info" {
+            Ok((id, Command::NetworkInfo))
         } else if line == "gomill-cpu_time" {
             Ok((id, Command::CpuTime))
         } else if line == "gomill-describe_engine" {
@@ -321,6 +443,7 @@ impl Gtp {
     fn generate_move(&mut self, id: Option<usize>, to_move: Color, mode: &GenMoveMode) -> Option<Vertex> {
         let (main_time, byo_yomi_time, byo_yomi_periods) = self.time_settings[to_move as usize].remaining();
         let board = self.history.last().unwrap();
+        let config = &self.config;
         let result = self.ponder.service(|service, search_tree, p_state| {
             let search_tree = if search_tree.to_move != to_move {
                 // passing moves are not recorded in the GTP protocol, so we
@@ -331,15 +454,26 @@ impl Gtp {
                 Some(search_tree)
             };
 
-            let result = if main_time.is_finite() && byo_yomi_time.is_finite() {
+            // how far a search gets within a time limit depends on the speed of
+            // the machine, so ignore the time settings if the user asked for
+            // reproducible results
+            let result = if config.seed.is_none() && main_time.is_finite() && byo_yomi_time.is_finite() {
                 let total_visits = search_tree.as_ref()
                     .map(|tree| tree.total_count)
                     .unwrap_or(0);
 
                 mcts::predict::<_, _, StandardSearch>(
                     &service.lock().clone_to_static(),
+                    config,
                     None,
-                    time_control::ByoYomi::new(board.count(), total_visits, main_time, byo_yomi_time, byo_yomi_periods),
+                    time_control::ByoYomi::new(
+                        board.count(),
+                        total_visits,
+                        main_time,
+                        byo_yomi_time,
+                        byo_yomi_periods,
+                        config.safe_time_ms
+                    ),
                     search_tree,
                     &board,
                     to_move
@@ -347,8 +481,9 @@ impl Gtp {
             } else {
                 mcts::predict::<_, _, StandardSearch>(
                     &service.lock().clone_to_static(),
+                    config,
                     None,
-                    time_control::RolloutLimit::new((*config::NUM_ROLLOUT).into()),
+                    time_control::KlDivergence::new(config.num_rollout.into(), config.kl_threshold),
                     search_tree,
                     &board,
                     to_move
@@ -369,15 +504,15 @@ impl Gtp {
                     tree.disqualify(index);
                 }
 
-                tree.best(0.0)
+                tree.best(0.0, &config.search)
             } else {
                 (value, index)
             };
 
-            let explain_last_move = mcts::tree::to_pretty(&tree).to_string();
+            let explain_last_move = mcts::tree::to_pretty(&tree, &config.search, config.verbose).to_string();
             eprintln!("{}", explain_last_move);
 
-            let should_resign = !*config::NO_RESIGN && value.is_finite() && value < 0.1;  // 10% chance of winning
+            let should_resign = !config.no_resign && value.is_finite() && value < 0.1;  // 10% chance of winning
             let index = if should_resign { 361 } else { index };
             let (vertex, tree, other) = if index >= 361 {  // passing move
                 (None, mcts::tree::Node::forward(tree, 361), board.clone())
@@ -417,6 +552,199 @@ impl Gtp {
         }
     }
 
+    /// Write the candidate moves of the search tree that is currently being
+    /// pondered in the format used by `lz-analyze` or `kata-analyze`, if the
+    /// pondering is for the other color then the tree is fast-forwarded with
+    /// a passing move.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - the format to write the analysis in
+    /// * `to_move` - the color to write the analysis for
+    ///
+    fn write_analysis(&mut self, mode: AnalyzeMode, to_move: Color) {
+        let cache = &mut self.analysis_cache;
+        let params = &self.config.search;
+        let result = self.ponder.service(|service, search_tree, (board, color)| {
+            let search_tree = if color != to_move {
+                mcts::tree::Node::forward(search_tree, 361)
+            } else {
+                Some(search_tree)
+            };
+            let analysis = match (mode, search_tree.as_ref()) {
+                (_, None) => String::new(),
+                (AnalyzeMode::Lz, Some(tree)) => mcts::tree::to_lz_analyze(tree, params).to_string(),
+                (AnalyzeMode::Kata(ownership), Some(tree)) => {
+                    let server = service.lock();
+                    let score_lead_cache = RefCell::new(&mut cache.score_lead);
+                    let score_lead = |index: usize| {
+                        *score_lead_cache.borrow_mut().entry(index).or_insert_with(|| {
+                            let mut other = board.clone();
+
+                            if index != 361 {
+                                other.place(to_move, index % 19, index / 19);
+                            }
+
+                            mcts::get_ownership(&server, &other, to_move.opposite())
+                                .map(|other_ownership| -mcts::get_score_lead(&other, to_move.opposite(), &other_ownership))
+                        })
+                    };
+                    let mut analysis = mcts::tree::to_kata_analyze(tree, params, score_lead).to_string();
+
+                    if ownership {
+                        let ownership = cache.ownership.get_or_insert_with(|| {
+                            mcts::get_ownership(&server, &board, to_move)
+                        });
+
+                        if let Some(ownership) = ownership {
+                            let size = board.size();
+
+                            // the ownership is written one row at a time,
+                            // starting from the top of the board.
+                            analysis += " ownership";
+
+                            for y in (0..size).rev() {
+                                for x in 0..size {
+                                    analysis += &format!(" {:.4}", ownership[19 * y + x]);
+                                }
+                            }
+                        }
+                    }
+
+                    analysis
+                }
+            };
+
+            (analysis, search_tree, (board, to_move))
+        });
+
+        if let Ok(analysis) = result {
+            if !analysis.is_empty() {
+                println!("{}", analysis);
+            }
+        }
+    }
+
+    /// Start writing the analysis of the current position every `interval`
+    /// centiseconds, until the next command arrives.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - the id of the command
+    /// * `mode` - the format to write the analysis in
+    /// * `color` - the color to write the analysis for, or `None` for the
+    ///   player whose turn it is
+    /// * `interval` - the number of centiseconds between each analysis
+    ///
+    fn start_analysis(&mut self, id: Option<usize>, mode: AnalyzeMode, color: Option<Color>, interval: usize) {
+        let color = color.unwrap_or_else(|| self.history.last().unwrap().to_move());
+
+        // the response is streamed as `info` lines until the next command
+        // arrives, at which point it is terminated by an empty line (see
+        // `stop_analysis`).
+        match id {
+            None => println!("="),
+            Some(id) => println!("={}", id)
+        }
+
+        self.ponder.set_forced(true);
+        self.analyze = Some((mode, color, Duration::from_millis(10 * interval as u64)));
+        self.analysis_cache = AnalysisCache::default();
+    }
+
+    /// Returns true if no stones has been placed on the board, and no moves
+    /// has been played.
+    fn is_empty(&self) -> bool {
+        self.history.len() == 1 && self.history[0] == self.empty_board(self.board_size)
+    }
+
+    /// Returns an empty board of the given size, with the current komi and
+    /// rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - the width and height of the board
+    ///
+    fn empty_board(&self, size: usize) -> Board {
+        let mut board = Board::new(size, self.komi);
+        board.set_rules(self.rules);
+        board
+    }
+
+    /// Replace the current game with an empty board, and restart the
+    /// pondering service.
+    fn reset(&mut self) {
+        let board = self.empty_board(self.board_size);
+
+        self.handicap = 0;
+        self.history = vec! [board.clone()];
+        self.explain_last_move = String::new();
+        self.finished_board = None;
+        self.ponder = PonderService::new(board, self.weights.clone(), self.config.clone());
+    }
+
+    /// Replace the current game with an empty board that has black stones
+    /// at the given vertices, and where white plays the first move. The
+    /// vertices are assumed to be valid and unique.
+    ///
+    /// # Arguments
+    ///
+    /// * `vertices` - the vertices of the handicap stones
+    ///
+    fn set_handicap(&mut self, vertices: &[Vertex]) {
+        let mut board = self.empty_board(self.board_size);
+
+        // some rules give white compensation for each handicap stone, which
+        // we treat as additional komi.
+        self.handicap = vertices.len();
+        board.set_komi(self.komi + self.rules.handicap_komi(self.handicap));
+
+        for vertex in vertices {
+            board.setup(Color::Black, vertex.x, vertex.y);
+        }
+
+        board.set_to_move(Color::White);
+
+        // restart the pondering service, since the tree it was searching
+        // belongs to the empty board.
+        self.history = vec! [board.clone()];
+        self.explain_last_move = String::new();
+        self.finished_board = None;
+        self.ponder = PonderService::new(board, self.weights.clone(), self.config.clone());
+    }
+
+    /// Place the given number of handicap stones at the standard vertices, and
+    /// write them to standard output.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - the id of the command
+    /// * `num_stones` - the number of handicap stones
+    ///
+    fn fixed_handicap(&mut self, id: Option<usize>, num_stones: usize) {
+        if !self.is_empty() {
+            error!(id, "board not empty");
+        } else if let Some(vertices) = handicap::get_fixed_handicap(self.board_size, num_stones) {
+            self.set_handicap(&vertices);
+
+            success!(id, vertices.iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(" "));
+        } else {
+            error!(id, "invalid number of stones");
+        }
+    }
+
+    /// Stop any ongoing analysis, and terminate its response.
+    fn stop_analysis(&mut self) {
+        if self.analyze.take().is_some() {
+            self.ponder.set_forced(false);
+
+            println!();
+        }
+    }
+
     fn greedy_playout(&mut self, board: &Board) -> Result<Board, &'static str> {
         let mut finished_board = self.finished_board.clone();
 
@@ -424,6 +752,7 @@ impl Gtp {
             finished_board = None;
         }
 
+        let config = self.config.clone();
         let result = finished_board.get_or_insert_with(|| {
             self.ponder.service(|service, original_search_tree, p_state| {
                 // if the search tree is too small, the expand it before continuing
@@ -431,8 +760,9 @@ impl Gtp {
                 let mut to_move = board.to_move();
                 let search_tree = match mcts::predict::<_, _, ScoringSearch>(
                     &service.lock().clone_to_static(),
+                    &config,
                     None,
-                    time_control::RolloutLimit::new((*config::NUM_ROLLOUT).into()),
+                    time_control::RolloutLimit::new(config.num_rollout.into()),
                     None,
                     &board,
                     to_move
@@ -482,38 +812,159 @@ impl Gtp {
                     "{} {}\n{}",
                     get_name(),
                     get_version(),
-                    config::get_description()
+                    self.config.description()
                 ));
             },
+            Command::SetParam(name, value) => {
+                match Arc::make_mut(&mut self.config).search.set(&name, &value) {
+                    Ok(()) => {
+                        // restart the background search, so that it uses the
+                        // new value.
+                        let _result = self.ponder.set_config(self.config.clone());
+
+                        success!(id, "");
+                    },
+                    Err(reason) => error!(id, reason)
+                }
+            },
+            Command::GetParam(name) => {
+                match self.config.search.get(&name) {
+                    Some(value) => success!(id, value),
+                    None => error!(id, format!("unknown parameter -- {}", name))
+                }
+            },
+            Command::ListParams => {
+                let params = &self.config.search;
+                let values = SearchParams::NAMES.iter()
+                    .map(|name| format!("{} {}", name, params.get(name).unwrap()))
+                    .collect::<Vec<_>>();
+
+                success!(id, values.join("\n"));
+            },
+            Command::SaveTree(filename) => {
+                let result = self.ponder.service(|_service, search_tree, p_state| {
+                    let result = File::create(&filename).and_then(|file| {
+                        let mut writer = BufWriter::new(file);
+
+                        mcts::tree::save(&search_tree, &p_state.0, &mut writer)?;
+                        writer.flush()
+                    });
+
+                    (result, Some(search_tree), p_state)
+                });
+
+                match result {
+                    Ok(Ok(())) => success!(id, ""),
+                    Ok(Err(reason)) => error!(id, format!("cannot save search tree -- {}", reason)),
+                    Err(reason) => error!(id, reason)
+                }
+            },
+            Command::LoadTree(filename) => {
+                let result = self.ponder.service(|_service, search_tree, p_state| {
+                    let result = File::open(&filename).and_then(|file| {
+                        let mut reader = BufReader::new(file);
+
+                        mcts::tree::load(&mut reader, &p_state.0, p_state.1)
+                    });
+
+                    match result {
+                        Ok(other) => (Ok(()), Some(other), p_state),
+                        Err(reason) => (Err(reason), Some(search_tree), p_state)
+                    }
+                });
+
+                match result {
+                    Ok(Ok(())) => success!(id, ""),
+                    Ok(Err(reason)) => error!(id, format!("cannot load search tree -- {}", reason)),
+                    Err(reason) => error!(id, reason)
+                }
+            },
+            Command::LoadWeights(filename) => {
+                match Network::from_path(Path::new(&filename), &self.config) {
+                    Ok(network) => {
+                        if self.ponder.set_network(network).is_err() {
+                            // the previous network never loaded, so there is
+                            // nothing to replace. Start over with the new one
+                            // instead.
+                            let board = self.history.last().unwrap().clone();
+
+                            self.ponder = PonderService::new(board, Some(filename.clone()), self.config.clone());
+                        }
+
+                        self.weights = Some(filename);
+                        self.finished_board = None;
+
+                        success!(id, "");
+                    },
+                    Err(reason) => error!(id, format!("cannot load network weights -- {:?}", reason))
+                }
+            },
+            Command::NetworkInfo => {
+                let result = self.ponder.service(|service, search_tree, p_state| {
+                    let info = service.lock().get_state().get_network().info();
+
+                    (info, Some(search_tree), p_state)
+                });
+
+                match result {
+                    Ok(Ok(info)) => success!(id, info),
+                    Ok(Err(reason)) => error!(id, format!("cannot describe network -- {:?}", reason)),
+                    Err(reason) => error!(id, reason)
+                }
+            },
             Command::BoardSize(size) => {
-                if size != 19 {
+                if size < 2 || size > MAX_SIZE {
                     error!(id, "unacceptable size");
                 } else {
+                    if self.board_size != size {
+                        self.board_size = size;
+                        self.reset();
+                    }
+
                     success!(id, "");
                 }
             },
             Command::ClearBoard => {
-                if self.history.len() > 1 {
-                    self.history = vec![Board::new(self.komi)];
-                    self.explain_last_move = String::new();
+                if !self.is_empty() {
+                    self.reset();
+                }
+
+                success!(id, "");
+            },
+            Command::KgsRules(rules) => {
+                if self.rules != rules {
+                    let handicap_komi = rules.handicap_komi(self.handicap);
+
+                    self.rules = rules;
+                    for board in self.history.iter_mut() {
+                        (*board).set_rules(rules);
+                        (*board).set_komi(self.komi + handicap_komi);
+                    }
+
+                    // restart the pondering service, since we have been thinking
+                    // with the wrong rules.
+                    let board = self.history.last().unwrap().clone();
+
                     self.finished_board = None;
-                    self.ponder = PonderService::new(Board::new(self.komi));
+                    self.ponder = PonderService::new(board, self.weights.clone(), self.config.clone());
                 }
 
                 success!(id, "");
             },
             Command::Komi(komi) => {
                 if self.komi != komi {
+                    let handicap_komi = self.rules.handicap_komi(self.handicap);
+
                     self.komi = komi;
                     for board in self.history.iter_mut() {
-                        (*board).set_komi(komi);
+                        (*board).set_komi(komi + handicap_komi);
                     }
 
                     // restart the pondering service, since we have been thinking
                     // with the wrong komi.
                     let board = self.history.last().unwrap().clone();
 
-                    self.ponder = PonderService::new(board);
+                    self.ponder = PonderService::new(board, self.weights.clone(), self.config.clone());
                 }
 
                 success!(id, "");
@@ -545,6 +996,36 @@ impl Gtp {
                     error!(id, "illegal move");
                 }
             },
+            Command::FixedHandicap(num_stones) => {
+                self.fixed_handicap(id, num_stones);
+            },
+            Command::PlaceFreeHandicap(num_stones) => {
+                // the engine is allowed to place fewer stones than requested
+                // when it gets to choose the vertices, see section 4.1.2 of
+                // the GTP specification.
+                let max_stones = handicap::max_fixed_handicap(self.board_size);
+
+                if num_stones >= 2 && num_stones > max_stones && max_stones >= 2 {
+                    self.fixed_handicap(id, max_stones);
+                } else {
+                    self.fixed_handicap(id, num_stones);
+                }
+            },
+            Command::SetFreeHandicap(vertices) => {
+                let is_valid = vertices.iter().enumerate().all(|(i, v)| {
+                    v.x < self.board_size && v.y < self.board_size && !vertices[..i].contains(v)
+                });
+
+                if !self.is_empty() {
+                    error!(id, "board not empty");
+                } else if vertices.len() < 2 || !is_valid {
+                    error!(id, "bad vertex list");
+                } else {
+                    self.set_handicap(&vertices);
+
+                    success!(id, "");
+                }
+            },
             Command::ListCommands => {
                 success!(id, KNOWN_COMMANDS.join("\n"));
             },
@@ -564,6 +1045,11 @@ impl Gtp {
             },
             Command::GenMove(color, mode) => {
                 let start_time = Instant::now();
+                let mode = if mode == GenMoveMode::Normal && self.rules.capture_dead {
+                    GenMoveMode::CleanUp  // there is no dead stone removal phase
+                } else {
+                    mode
+                };
                 let vertex = self.generate_move(id, color, &mode);
 
                 if !mode.is_regression() {
@@ -594,10 +1080,10 @@ impl Gtp {
                     let (black, white) = board.get_guess_score(&finished);
 
                     eprintln!("Black: {}", black);
-                    eprintln!("White: {} + {}", white, self.komi);
+                    eprintln!("White: {} + {}", white, board.komi());
 
                     let black = black as f32;
-                    let white = white as f32 + self.komi;
+                    let white = white as f32 + board.komi();
 
                     if black == white {
                         success!(id, "0");
@@ -643,27 +1129,29 @@ impl Gtp {
 
                     if let Err(_reason) = buf_reader.read_to_end(&mut content) {
                         error!(id, "cannot read file content");
+                        return;
                     }
 
-                    self.history = vec! [];
+                    let (history, info) = match load_main_line(&content, self.komi, self.rules, move_number) {
+                        Ok(result) => result,
+                        Err(reason) => {
+                            error!(id, format!("failed to parse file: {}", reason));
+                            return;
+                        }
+                    };
+
+                    self.history = history;
+                    self.komi = info.komi;
+                    self.rules = info.rules;
+                    self.handicap = info.handicap;
+
                     self.explain_last_move = String::new();
                     self.finished_board = None;
 
-                    for entry in Sgf::new(&content, self.komi).take(move_number) {
-                        match entry {
-                            Ok(entry) => {
-                                self.history.push(entry.board);
-                            },
-                            Err(_reason) => {
-                                error!(id, "failed to parse file");
-                                return;
-                            }
-                        }
-                    }
-
                     // start the pondering agent
                     let board = self.history.last().unwrap().clone();
-                    self.ponder = PonderService::new(board);
+                    self.board_size = board.size();
+                    self.ponder = PonderService::new(board, self.weights.clone(), self.config.clone());
 
                     success!(id, "");
                 } else {
@@ -679,7 +1167,7 @@ impl Gtp {
 
                     self.explain_last_move = String::new();
                     self.finished_board = None;
-                    self.ponder = PonderService::new(board);
+                    self.ponder = PonderService::new(board, self.weights.clone(), self.config.clone());
 
                     success!(id, "");
                 } else {
@@ -728,6 +1216,12 @@ impl Gtp {
                 self.time_settings[c].time_left(main_time, byo_yomi_stones);
                 success!(id, "");
             },
+            Command::LzAnalyze(color, interval) => {
+                self.start_analysis(id, AnalyzeMode::Lz, color, interval);
+            },
+            Command::KataAnalyze(color, interval, ownership) => {
+                self.start_analysis(id, AnalyzeMode::Kata(ownership), color, interval);
+            },
             Command::CpuTime => {
                 let cpu_time = self.ponder.cpu_time();
                 let secs = cpu_time.as_secs() as f64 + cpu_time.subsec_nanos() as f64 / 1e6;
@@ -738,6 +1232,62 @@ impl Gtp {
     }
 }
 
+/// The komi, handicap, and rules of a game as given by the root node of an
+/// SGF file, or the current ones for any property that is missing.
+struct SgfGameInfo {
+    komi: f32,
+    handicap: usize,
+    rules: Rules
+}
+
+/// Returns the board positions along the main line of the first game tree
+/// in the given SGF file, starting with the position after the root node
+/// and stopping before `move_number` is played. The komi, handicap, and
+/// rules are read from the root node, and the boards get the handicap komi
+/// of those rules.
+///
+/// # Arguments
+///
+/// * `content` - the content of the SGF file
+/// * `komi` - the komi to use if the file does not specify one
+/// * `rules` - the rules to use if the file does not specify any
+/// * `move_number` - the (one based) move number to stop before
+///
+fn load_main_line(content: &[u8], komi: f32, rules: Rules, move_number: usize) -> Result<(Vec<Board>, SgfGameInfo), SgfError> {
+    let trees = GameTree::parse(content)?;
+    let tree = &trees[0];
+    let info = SgfGameInfo {
+        komi: tree.komi().unwrap_or(komi),
+        handicap: tree.handicap().unwrap_or(0),
+        rules: tree.rules().and_then(|rules| rules.parse::<Rules>().ok()).unwrap_or(rules)
+    };
+    let main_line = tree.main_line();
+    let boards = tree.replay(&main_line, info.komi)?;
+    let mut history: Vec<Board> = Vec::with_capacity(boards.len());
+
+    for (&index, mut board) in main_line.iter().zip(boards.into_iter()) {
+        let node = tree.node(index);
+        let is_move = node.get("B").is_some() || node.get("W").is_some();
+
+        board.set_rules(info.rules);
+        board.set_komi(info.komi + info.rules.handicap_komi(info.handicap));
+
+        if history.is_empty() {
+            history.push(board);
+        } else if is_move {
+            if history.len() >= move_number {
+                break
+            }
+
+            history.push(board);
+        } else {
+            *history.last_mut().unwrap() = board;  // only setup, or comments
+        }
+    }
+
+    Ok((history, info))
+}
+
 /// Returns the name of this engine.
 pub fn get_name() -> String {
     env::var("DG_NAME").unwrap_or_else(|_| env!("CARGO_PKG_NAME").to_string())
@@ -751,15 +1301,50 @@ pub fn get_version() -> String {
 /// Run the GTP (Go Text Protocol) client that reads from standard input
 /// and writes to standard output. This client implements the minimum
 /// necessary feature-set of a GTP client.
-pub fn run() {
-    let stdin = ::std::io::stdin();
-    let stdin_lock = stdin.lock();
+///
+/// # Arguments
+///
+/// * `config` - the configuration of the engine
+///
+pub fn run(config: Config) {
+    // read the commands on a separate thread, so that we can keep writing the
+    // analysis while waiting for the next command to arrive.
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let stdin = ::std::io::stdin();
+        let stdin_lock = stdin.lock();
+
+        for line in stdin_lock.lines() {
+            if sender.send(line).is_err() {
+                break
+            }
+        }
+    });
+
+    let mut rules = Rules::from(config.rules);
+
+    if let Some(superko) = config.superko {
+        rules.superko = Superko::from(superko);
+    }
+
+    let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
+    board.set_rules(rules);
+
+    let config = Arc::new(config);
     let mut gtp = Gtp {
-        ponder: PonderService::new(Board::new(DEFAULT_KOMI)),
-        history: vec! [Board::new(DEFAULT_KOMI)],
+        ponder: PonderService::new(board.clone(), None, config.clone()),
+        config: config,
+        weights: None,
+        history: vec! [board],
+        board_size: DEFAULT_SIZE,
         komi: DEFAULT_KOMI,
+        rules: rules,
+        handicap: 0,
         explain_last_move: String::new(),
         finished_board: None,
+        analyze: None,
+        analysis_cache: AnalysisCache::default(),
         time_settings: [
             Box::new(time_settings::None::new()),
             Box::new(time_settings::None::new()),
@@ -767,7 +1352,25 @@ pub fn run() {
         ],
     };
 
-    for line in stdin_lock.lines() {
+    loop {
+        let line = match gtp.analyze {
+            Some((mode, to_move, interval)) => match receiver.recv_timeout(interval) {
+                Ok(line) => {
+                    gtp.stop_analysis();
+                    line
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    gtp.write_analysis(mode, to_move);
+                    continue
+                },
+                Err(RecvTimeoutError::Disconnected) => break
+            },
+            None => match receiver.recv() {
+                Ok(line) => line,
+                Err(_) => break
+            }
+        };
+
         if let Ok(line) = line {
             match Gtp::parse_line(&line) {
                 Some((id, Command::Quit)) => {
@@ -890,6 +1493,38 @@ mod tests {
         assert_eq!(Gtp::parse_line("loadsgf x/y/z.sgf 120"), Some((None, Command::LoadSgf("x/y/z.sgf".into(), 120))));
     }
 
+    #[test]
+    fn loadsgf_main_line() {
+        let content = b"(;SZ[9]AB[cc];W[gg]C[comment];B[cg](;W[gc])(;W[ee]))";
+        let (history, _info) = load_main_line(content, 7.5, Rules::chinese(), ::std::usize::MAX).unwrap();
+
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[0].size(), 9);
+        assert_eq!(history[0].to_move(), Color::White);
+        assert_eq!(history[3].at(6, 2), Some(Color::White));
+        assert_eq!(history[3].at(4, 4), None);
+
+        let (history, _info) = load_main_line(content, 7.5, Rules::chinese(), 2).unwrap();
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].to_move(), Color::Black);
+        assert!(load_main_line(b"(;B[dd];W[dd])", 7.5, Rules::chinese(), 10).is_err());
+    }
+
+    #[test]
+    fn loadsgf_game_info() {
+        let content = b"(;SZ[9]KM[0.5]HA[2]RU[AGA]AB[cc][gg];W[ee])";
+        let (history, info) = load_main_line(content, 7.5, Rules::chinese(), ::std::usize::MAX).unwrap();
+
+        assert_eq!((info.komi, info.handicap, info.rules), (0.5, 2, Rules::aga()));
+        assert!(history.iter().all(|board| board.komi() == 1.5 && board.rules() == Rules::aga()));
+
+        let (history, info) = load_main_line(b"(;SZ[9]RU[unknown])", 7.5, Rules::japanese(), ::std::usize::MAX).unwrap();
+
+        assert_eq!((info.komi, info.handicap, info.rules), (7.5, 0, Rules::japanese()));
+        assert_eq!(history[0].komi(), 7.5);
+    }
+
     #[test]
     fn undo() {
         assert_eq!(Gtp::parse_line("1 undo"), Some((Some(1), Command::Undo)));
@@ -942,6 +1577,68 @@ mod tests {
         assert_eq!(Gtp::parse_line("gomill-cpu_time"), Some((None, Command::CpuTime)));
     }
 
+    #[test]
+    fn handicap() {
+        assert_eq!(Gtp::parse_line("fixed_handicap 4"), Some((None, Command::FixedHandicap(4))));
+        assert_eq!(Gtp::parse_line("2 place_free_handicap 9"), Some((Some(2), Command::PlaceFreeHandicap(9))));
+        assert_eq!(Gtp::parse_line("set_free_handicap D4 q16"), Some((None, Command::SetFreeHandicap(vec! [
+            Vertex { x: 3, y: 3 },
+            Vertex { x: 15, y: 15 }
+        ]))));
+    }
+
+    #[test]
+    fn lz_analyze() {
+        assert_eq!(Gtp::parse_line("1 lz-analyze"), Some((Some(1), Command::LzAnalyze(None, 100))));
+        assert_eq!(Gtp::parse_line("lz-analyze 50"), Some((None, Command::LzAnalyze(None, 50))));
+        assert_eq!(Gtp::parse_line("lz-analyze b 25"), Some((None, Command::LzAnalyze(Some(Color::Black), 25))));
+        assert_eq!(Gtp::parse_line("lz-analyze white interval 10"), Some((None, Command::LzAnalyze(Some(Color::White), 10))));
+        assert_eq!(Gtp::parse_line("lz-analyze W 0"), Some((None, Command::LzAnalyze(Some(Color::White), 100))));
+    }
+
+    #[test]
+    fn search_params() {
+        assert_eq!(Gtp::parse_line("1 dg-set_param uct_exp 0,0.88:3200,1.44"), Some((Some(1), Command::SetParam("uct_exp".to_string(), "0,0.88:3200,1.44".to_string()))));
+        assert_eq!(Gtp::parse_line("dg-get_param vloss_cnt"), Some((None, Command::GetParam("vloss_cnt".to_string()))));
+        assert_eq!(Gtp::parse_line("dg-list_params"), Some((None, Command::ListParams)));
+    }
+
+    #[test]
+    fn save_load_tree() {
+        assert_eq!(Gtp::parse_line("1 dg-save_tree analysis.tree"), Some((Some(1), Command::SaveTree("analysis.tree".to_string()))));
+        assert_eq!(Gtp::parse_line("dg-load_tree x/y/z.tree"), Some((None, Command::LoadTree("x/y/z.tree".to_string()))));
+        assert_eq!(Gtp::parse_line("dg-load_tree"), Some((None, Command::Pass)));
+        assert_eq!(Gtp::parse_line("dg-save_tree Analysis/My Game.tree"), Some((None, Command::SaveTree("Analysis/My Game.tree".to_string()))));
+        assert_eq!(Gtp::parse_line("dg-load_tree Analysis/My Game.tree"), Some((None, Command::LoadTree("Analysis/My Game.tree".to_string()))));
+    }
+
+    #[test]
+    fn load_weights() {
+        assert_eq!(Gtp::parse_line("1 dg-load_weights models/dream_go.json"), Some((Some(1), Command::LoadWeights("models/dream_go.json".to_string()))));
+        assert_eq!(Gtp::parse_line("dg-load_weights"), Some((None, Command::Pass)));
+        assert_eq!(Gtp::parse_line("DG-LOAD_WEIGHTS /models/Net B.json "), Some((None, Command::LoadWeights("/models/Net B.json".to_string()))));
+    }
+
+    #[test]
+    fn network_info() {
+        assert_eq!(Gtp::parse_line("1 dg-network_info"), Some((Some(1), Command::NetworkInfo)));
+    }
+
+    #[test]
+    fn kgs_rules() {
+        assert_eq!(Gtp::parse_line("1 kgs-rules japanese"), Some((Some(1), Command::KgsRules(Rules::japanese()))));
+        assert_eq!(Gtp::parse_line("kgs-rules new_zealand"), Some((None, Command::KgsRules(Rules::new_zealand()))));
+        assert_eq!(Gtp::parse_line("kgs-rules ing"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn kata_analyze() {
+        assert_eq!(Gtp::parse_line("1 kata-analyze"), Some((Some(1), Command::KataAnalyze(None, 100, false))));
+        assert_eq!(Gtp::parse_line("kata-analyze 50"), Some((None, Command::KataAnalyze(None, 50, false))));
+        assert_eq!(Gtp::parse_line("kata-analyze b interval 25 ownership true"), Some((None, Command::KataAnalyze(Some(Color::Black), 25, true))));
+        assert_eq!(Gtp::parse_line("kata-analyze white 10 ownership false"), Some((None, Command::KataAnalyze(Some(Color::White), 10, false))));
+    }
+
     #[test]
     fn quit() {
         assert_eq!(Gtp::parse_line("1 quit"), Some((Some(1), Command::Quit)));
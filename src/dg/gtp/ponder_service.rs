@@ -13,18 +13,19 @@
 // limitations under the License.
 
 use cpu_time::ProcessTime;
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
 use dg_go::{Board, Color};
-use dg_utils::config;
-use dg_mcts::predict_service::PredictService;
+use dg_utils::config::Config;
+use dg_mcts::predict_service::{self, PredictService};
 use dg_mcts::time_control::{TimeStrategy, TimeStrategyResult};
 use dg_mcts::tree;
 use dg_mcts as mcts;
-use dg_nn::Network;
+use dg_nn::{Error, Network};
 use dg_mcts::options::{StandardSearch, SearchOptions};
 
 type SearchTree = tree::Node<StandardSearch>;
@@ -67,6 +68,7 @@ impl TimeStrategy for PonderTimeControl {
 /// # Arguments
 /// 
 /// * `service` - the neural network service used for inference
+/// * `config` - the configuration to search with
 /// * `search_tree` - the search tree to probe into
 /// * `board` - the board state at the root of the search tree
 /// * `to_move` - the color of the player whose turn it is to play
@@ -74,6 +76,7 @@ impl TimeStrategy for PonderTimeControl {
 /// 
 fn ponder_worker(
     service: PredictService,
+    config: Arc<Config>,
     search_tree: Option<SearchTree>,
     board: Board,
     to_move: Color,
@@ -81,9 +84,10 @@ fn ponder_worker(
 ) -> (PonderResult, Duration)
 {
     let start_time = ProcessTime::now();
-    let max_tree_size = (*config::NUM_ROLLOUT).user_defined_or(500_000);
+    let max_tree_size = config.num_rollout.user_defined_or(500_000);
     let result = mcts::predict::<_, _, StandardSearch>(
         &service.lock().clone_to_static(),
+        &config,
         None,
         PonderTimeControl { is_running, max_tree_size },
         search_tree,
@@ -104,7 +108,9 @@ fn ponder_worker(
 /// and allows the user to intercept and replace said pondering state at any
 /// point.
 pub struct PonderService {
+    config: Arc<Config>,
     is_running: Arc<AtomicBool>,
+    is_forced: bool,
     worker: Option<thread::JoinHandle<(PonderResult, Duration)>>,
     last_error: &'static str,
     cpu_time: Duration
@@ -127,21 +133,34 @@ impl PonderService {
     /// # Arguments
     /// 
     /// * `board` - the initial board.
+    /// * `weights` - the path to the network weights, or `None` to use the
+    ///   default network.
+    /// * `config` - the configuration to search with
     ///
-    pub fn new(board: Board) -> PonderService {
-        let is_running = Arc::new(AtomicBool::new(!*config::NO_PONDER));
+    pub fn new(board: Board, weights: Option<String>, config: Arc<Config>) -> PonderService {
+        let is_running = Arc::new(AtomicBool::new(config.is_pondering()));
         let is_running_worker = is_running.clone();
+        let config_worker = config.clone();
         let to_move = board.to_move();
 
         PonderService {
+            config: config,
             is_running: is_running,
+            is_forced: false,
             worker: Some(thread::spawn(move || {
-                if let Some(network) = Network::new() {
-                    let service = mcts::predict_service::service(network);
+                let network = match weights {
+                    Some(path) => Network::from_path(Path::new(&path), &config_worker),
+                    None => Network::new(&config_worker)
+                };
 
-                    ponder_worker(service, None, board, to_move, is_running_worker)
-                } else {
-                    (Err("unable to load network weights"), Duration::new(0, 0))
+                match network {
+                    Ok(network) => {
+                        let service = mcts::predict_service::service(network, &config_worker);
+
+                        ponder_worker(service, config_worker, None, board, to_move, is_running_worker)
+                    },
+                    Err(Error::UnavailableBackend(reason)) => (Err(reason), Duration::new(0, 0)),
+                    Err(_) => (Err("unable to load network weights"), Duration::new(0, 0))
                 }
             })),
             last_error: "",
@@ -149,6 +168,31 @@ impl PonderService {
         }
     }
 
+    /// Returns whether the service should be pondering in the background.
+    fn is_pondering(&self) -> bool {
+        self.is_forced || self.config.is_pondering()
+    }
+
+    /// Sets whether the service should ponder in the background even if
+    /// pondering has been disabled using `--no-ponder`, this is used during
+    /// analysis since it should keep searching until told otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `is_forced` -
+    ///
+    pub fn set_forced(&mut self, is_forced: bool) {
+        if self.is_forced != is_forced {
+            self.is_forced = is_forced;
+
+            // re-spawn the pondering thread, since it might already have
+            // terminated if we were not allowed to ponder before.
+            let _result = self.service(|_service, search_tree, state| {
+                ((), Some(search_tree), state)
+            });
+        }
+    }
+
     /// Returns the total amount of time the service has spent pondering in the background, or in
     /// the `service` handler.
     pub fn cpu_time(&self) -> Duration {
@@ -195,11 +239,12 @@ impl PonderService {
                 // re-spawn the pondering thread now that the callback has been
                 // executed.
                 let is_running_worker = self.is_running.clone();
+                let config_worker = self.config.clone();
 
                 self.cpu_time += start_time.elapsed() + duration;
-                self.is_running.store(!*config::NO_PONDER, Ordering::SeqCst);
+                self.is_running.store(self.is_pondering(), Ordering::SeqCst);
                 self.worker = Some(thread::spawn(move || {
-                    ponder_worker(service, search_tree, board, to_move, is_running_worker)
+                    ponder_worker(service, config_worker, search_tree, board, to_move, is_running_worker)
                 }));
 
                 Ok(result)
@@ -207,6 +252,36 @@ impl PonderService {
         }
     }
 
+    /// Replaces the configuration used by this service. The current search
+    /// tree is kept, and the pondering continues with the new configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - the new configuration
+    ///
+    pub fn set_config(&mut self, config: Arc<Config>) -> Result<(), &'static str> {
+        self.config = config;
+        self.service(|_service, search_tree, p_state| {
+            ((), Some(search_tree), p_state)
+        })
+    }
+
+    /// Replaces the network used by this service. The current search tree is
+    /// discarded since it was computed using the old network, but the
+    /// pondering continues from the same board position.
+    ///
+    /// # Arguments
+    ///
+    /// * `network` - the new network
+    ///
+    pub fn set_network(&mut self, network: Network) -> Result<(), &'static str> {
+        self.service(move |service, _search_tree, p_state| {
+            predict_service::set_network(service, network);
+
+            ((), None, p_state)
+        })
+    }
+
     /// Plays the given move into the current _search tree_. Moving the search
     /// tree forward one turn.
     /// 
@@ -24,14 +24,42 @@ extern crate regex;
 
 mod gtp;
 
-use dg_utils::config::{self, Procedure};
-
-/// Returns the network weights, panics if it failed to load the weights.
-fn load_network() -> dg_nn::Network {
-    match dg_nn::Network::new() {
-        Some(network) => network,
-        None => {
-            println!("Could not load network weights!");
+use std::path::Path;
+
+use dg_go::Color;
+use dg_go::utils::sgf::{CGoban, write_game};
+use dg_utils::config::{Config, Procedure};
+
+/// Returns the network weights, exits if it failed to load the weights.
+///
+/// # Arguments
+///
+/// * `config` - the configuration to load the network with
+///
+fn load_network(config: &Config) -> dg_nn::Network {
+    match dg_nn::Network::new(config) {
+        Ok(network) => network,
+        Err(reason) => {
+            eprintln!("Could not load network weights -- {:?}", reason);
+            ::std::process::exit(1);
+        }
+    }
+}
+
+/// Returns the network weights of the opponent in a match, exits if it failed
+/// to load the weights.
+///
+/// # Arguments
+///
+/// * `config` - the configuration to load the network with
+///
+fn load_opponent_network(config: &Config) -> dg_nn::Network {
+    let path = config.opponent.clone().unwrap_or_default();
+
+    match dg_nn::Network::from_path(Path::new(&path), config) {
+        Ok(network) => network,
+        Err(reason) => {
+            eprintln!("Could not load network weights from {} -- {:?}", path, reason);
             ::std::process::exit(1);
         }
     }
@@ -39,7 +67,15 @@ fn load_network() -> dg_nn::Network {
 
 /// Main function.
 fn main() {
-    match *config::PROCEDURE {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            ::std::process::exit(1);
+        }
+    };
+
+    match config.procedure {
         Procedure::Help => {
             println!("Usage: ./dream-go [options]");
             println!();
@@ -47,22 +83,52 @@ fn main() {
             println!("  --policy-play <n>     Extract a dataset from self-play using only the policy network");
             println!("  --ex-it               When combined with --policy-play perform search on some partial");
             println!("                        policies");
+            println!("  --match <n>           Play n games against the network given by --opponent, and output");
+            println!("                        the games and a summary of the results");
+            println!("  --convert-weights <path>");
+            println!("                        Convert the network given by --weights into the binary format, and");
+            println!("                        write it to the given path");
+            println!("  --network-info        Output the architecture of the network, and statistics about its");
+            println!("                        weights");
             println!("  --gtp                 Run GTP client (default)");
             println!();
             println!("Advanced options:");
+            println!("  --weights <path>      The network weights to use, instead of looking for them in the");
+            println!("                        default locations");
+            println!("  --opponent <path>     The network weights to play against during --match");
+            println!("  --provenance <text>   A description of where the weights came from, to store in the");
+            println!("                        output of --convert-weights");
+            println!("  --config <path>       Read options from the given TOML file, command-line options and");
+            println!("                        environment variables take precedence over the file");
             println!("  --safe-time <n>       The minimum number of milliseconds to leave on the game clock");
             println!("  --num-rollout <n>     The number of rollouts to add to the search tree for every move");
+            println!("  --kl-threshold <x>    Stop searching a position once the visit distribution changes less");
+            println!("                        than this between snapshots, with --num-rollout as the maximum");
+            println!("  --full-search-prob <p>");
+            println!("                        The probability that a self-play move gets a full search, other");
+            println!("                        moves are searched with --num-cheap-rollout and get no policy");
+            println!("  --num-cheap-rollout <n>");
+            println!("                        The number of rollouts to add to the search tree for a cheap search");
             println!("  --num-games <n>       The number of games to play or extract in parallel");
             println!("  --num-threads <n>     The number of search threads to use in total");
             println!("  --num-samples <n>     The number of games to extract from each game record");
             println!("  --batch-size <n>      The number parallel rollouts to perform on the GPU");
+            println!("  --max-tree-memory <n> The maximum number of MiB the search tree may use, or 0 for");
+            println!("                        no limit (default)");
+            println!("  --seed <n>            Seed the random number generators, and search using a single");
+            println!("                        thread, to get reproducible results");
+            println!("  --backend <name>      The backend to evaluate the neural network on, one of `auto`");
+            println!("                        (default), `cuda`, or `cpu`");
+            println!("  --rules <name>        The rules to play with, one of `chinese` (default), `japanese`,");
+            println!("                        `aga`, `new_zealand`, or `tromp-taylor`");
+            println!("  --superko <rule>      Override the super-ko rule, either `positional` or `situational`");
             println!("  --tt                  Play using Tromp-Taylor rules");
             println!("  --no-ponder           Do not think in the background during idle time");
             println!("  --no-resign           Do not allow the engine to resign in games");
         },
 
         Procedure::SelfPlay(n) => {
-            let (receiver, _server) = dg_mcts::self_play(load_network(), n);
+            let (receiver, _server) = dg_mcts::self_play(load_network(&config), n, &config);
 
             for result in receiver.iter() {
                 println!("{}", result);
@@ -70,15 +136,76 @@ fn main() {
         },
 
         Procedure::PolicyPlay(n, ex_it) => {
-            let (receiver, _server) = dg_mcts::policy_play(load_network(), n, ex_it);
+            let (receiver, _server) = dg_mcts::policy_play(load_network(&config), n, ex_it, &config);
 
             for result in receiver.iter() {
                 println!("{}", result);
             }
         },
 
+        Procedure::Match(n) => {
+            let (receiver, _first, _second) = dg_mcts::match_play(
+                load_network(&config),
+                load_opponent_network(&config),
+                n,
+                &config
+            );
+            let mut summary = dg_mcts::MatchSummary::default();
+
+            for (first_color, result) in receiver.iter() {
+                let mut info = result.game_info();
+
+                let first_name = config.weights.clone().unwrap_or_else(|| "dream_go".to_string());
+
+                if first_color == Color::Black {
+                    info.black = Some(first_name);
+                    info.white = config.opponent.clone();
+                } else {
+                    info.black = config.opponent.clone();
+                    info.white = Some(first_name);
+                }
+
+                println!("{}", write_game::<CGoban>(&info, result.moves()));
+                summary.add(first_color, result.winner());
+            }
+
+            eprintln!("{} {}", config.weights.clone().unwrap_or_else(|| "dream_go".to_string()), summary);
+        },
+
+        Procedure::ConvertWeights => {
+            let input = config.weights.clone().unwrap_or_default();
+            let output = config.convert_weights.clone().unwrap_or_default();
+            let provenance = config.provenance.clone().unwrap_or_else(|| format!("converted from {}", input));
+
+            match dg_nn::binary::convert(Path::new(&input), Path::new(&output), &provenance) {
+                Ok(header) => {
+                    eprintln!("Wrote {} ({} channels, {} residual blocks, {} features, checksum {:016x})",
+                        output,
+                        header.architecture.num_channels,
+                        header.architecture.num_residual,
+                        header.architecture.num_features,
+                        header.checksum
+                    );
+                },
+                Err(reason) => {
+                    eprintln!("Could not convert network weights from {} -- {:?}", input, reason);
+                    ::std::process::exit(1);
+                }
+            }
+        },
+
+        Procedure::NetworkInfo => {
+            match load_network(&config).info() {
+                Ok(info) => println!("{}", info),
+                Err(reason) => {
+                    eprintln!("Could not describe network -- {:?}", reason);
+                    ::std::process::exit(1);
+                }
+            }
+        },
+
         Procedure::Gtp => {
-            gtp::run()
+            gtp::run(config)
         }
     }
 }
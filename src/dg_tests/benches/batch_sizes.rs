@@ -24,10 +24,11 @@ use rand::{Rng, thread_rng};
 
 use dg_go::utils::features::FEATURE_SIZE;
 use dg_nn::*;
+use dg_utils::config::{Config, Procedure};
 use dg_utils::types::f16;
 
 thread_local! {
-    static NETWORK: Network = Network::new().unwrap();
+    static NETWORK: Network = Network::new(&Config::new(Procedure::Gtp)).unwrap();
 }
 
 /// Benchmark the forward pass through the neural network for the given batch
@@ -15,13 +15,15 @@
 
 extern crate dg_go;
 extern crate dg_mcts;
+extern crate dg_utils;
 extern crate test;
 
-use dg_go::{Board, Color};
+use dg_go::{DEFAULT_SIZE, Board, Color};
 use dg_mcts::time_control::RolloutLimit;
 use dg_mcts as mcts;
 use test::Bencher;
 use dg_mcts::options::StandardSearch;
+use dg_utils::config::{Config, Procedure};
 
 #[bench]
 fn lee_sedol_alphago_4_78(b: &mut Bencher) {
@@ -48,7 +50,7 @@ fn lee_sedol_alphago_4_78(b: &mut Bencher) {
         (Color::Black,  5,  2), (Color::White, 10,  8)
     ];
 
-    let mut original_board = Board::new(7.5);
+    let mut original_board = Board::new(DEFAULT_SIZE, 7.5);
 
     for &(color, x, y) in lee_sedol_alphago_4_78.iter() {
         assert!(original_board.is_valid(color, x, y));
@@ -56,11 +58,14 @@ fn lee_sedol_alphago_4_78(b: &mut Bencher) {
         original_board.place(color, x, y);
     }
 
+    let config = Config::new(Procedure::Gtp);
+
     b.iter(move || {
         let server = mcts::predict::RandomPredictor::default();
 
         mcts::predict::<_, _, StandardSearch>(
             &server,
+            &config,
             Some(4),
             RolloutLimit::new(40),
             None,
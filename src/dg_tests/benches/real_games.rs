@@ -19,7 +19,7 @@ extern crate test;
 
 use test::Bencher;
 
-use dg_go::{DEFAULT_KOMI, Board, Color};
+use dg_go::{DEFAULT_KOMI, DEFAULT_SIZE, Board, Color};
 use dg_go::utils::features::{HWC, Features};
 use dg_go::utils::symmetry::Transform;
 
@@ -88,7 +88,7 @@ fn playout(b: &mut Bencher) {
     ];
 
     b.iter(|| {
-        let mut board = Board::new(DEFAULT_KOMI);
+        let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
 
         for &(color, x, y) in rina_fujisawa_jeong_chio.iter() {
             debug_assert!(board.is_valid(color, x, y));
@@ -128,7 +128,7 @@ fn get_features_16(b: &mut Bencher) {
         (Color::Black,  5,  2), (Color::White, 10,  8)
     ];
 
-    let mut board = Board::new(DEFAULT_KOMI);
+    let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
 
     for &(color, x, y) in lee_sedol_alphago_4_78.iter() {
         assert!(board.is_valid(color, x, y));
@@ -183,7 +183,7 @@ fn get_features_32(b: &mut Bencher) {
         (Color::Black,  8,  9), (Color::White,  4,  1), (Color::Black,  5,  2)
     ];
 
-    let mut board = Board::new(DEFAULT_KOMI);
+    let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
 
     for &(color, x, y) in rina_fujisawa_zhiying_yu.iter() {
         assert!(board.is_valid(color, x, y));
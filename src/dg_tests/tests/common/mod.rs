@@ -13,8 +13,9 @@
 // limitations under the License.
 //
 
-use dg_go::{DEFAULT_KOMI, Board, Color};
+use dg_go::{DEFAULT_KOMI, DEFAULT_SIZE, Board, Color};
 use dg_nn;
+use dg_utils::config::{Config, Procedure};
 
 use regex::Regex;
 use std::fs::File;
@@ -22,7 +23,7 @@ use std::io::prelude::*;
 
 thread_local! {
     #[allow(dead_code)]
-    static NETWORK: dg_nn::Network = dg_nn::Network::new().unwrap();
+    static NETWORK: dg_nn::Network = dg_nn::Network::new(&Config::new(Procedure::Gtp)).unwrap();
 }
 
 /// Play each move in the given SGF string and return the final board state,
@@ -44,7 +45,7 @@ pub fn playout_game(src: &str, max_moves: Option<usize>) -> Board {
         static ref MOVE: Regex = Regex::new(r";([BW])\[([a-z]*)\]").unwrap();
     }
 
-    let mut board = Board::new(DEFAULT_KOMI);
+    let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
     let mut count = 1;
 
     for cap in MOVE.captures_iter(src) {
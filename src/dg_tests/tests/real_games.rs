@@ -15,6 +15,7 @@
 
 extern crate dg_go;
 extern crate dg_nn;
+extern crate dg_utils;
 #[macro_use] extern crate lazy_static;
 extern crate regex;
 
@@ -15,10 +15,31 @@
 use std::fmt;
 use std::hash::{Hash, Hasher};
 
-use board_fast::{BoardFast, Vertex};
+use board_fast::{BoardFast, Vertex, One};
 use color::Color;
 use circular_buf::CircularBuf;
+use position_history::PositionHistory;
+use rules::{Rules, Superko};
 use small_set::SmallSet64;
+use zobrist;
+use ::MAX_SIZE;
+
+/// Random value that is mixed into the zobrist hashes stored in the history
+/// if white is to move, so that situational super-ko can be distinguished
+/// from positional super-ko.
+const WHITE_TO_MOVE: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Returns the value to mix into a zobrist hash of a position where the
+/// given color is to move.
+///
+/// # Arguments
+///
+/// * `to_move` - the color whose turn it is to play
+///
+#[inline]
+fn to_move_key(to_move: Color) -> u64 {
+    if to_move == Color::White { WHITE_TO_MOVE } else { 0 }
+}
 
 ///
 #[derive(Clone)]
@@ -33,36 +54,70 @@ pub struct Board {
     /// The zobrist hash of the current board state.
     pub(super) zobrist_hash: u64,
 
-    /// The zobrist hash of the most recent board positions.
+    /// The zobrist hash of the most recent board positions, mixed with
+    /// whose turn it was to play in each position.
     pub(super) zobrist_history: SmallSet64,
 
+    /// The zobrist hash of every board position since the start of the
+    /// game, mixed with whose turn it was to play in each position. This is
+    /// only necessary to detect long cycles, such as triple ko, since the
+    /// `zobrist_history` catches all short ones.
+    pub(super) full_history: Option<PositionHistory>,
+
+    /// The rules used for this game.
+    pub(super) rules: Rules,
+
+    /// The number of stones of each color that has been placed on this
+    /// board, including setup stones.
+    pub(super) num_placed: [u16; 2],
+
     /// The komi used for this game.
     pub(super) komi: f32,
 
+    /// The width and height of this board.
+    pub(super) size: u8,
+
     /// The total number of moves that has been played on this board.
     pub(super) count: u16,
 
     /// The color of the player who played the most recent move.
     pub(super) last_played: Option<Color>,
+
+    /// The color of the player whose turn it is to play.
+    pub(super) to_move: Color,
 }
 
 impl Board {
-    pub fn new(komi: f32) -> Board {
+    /// Returns an empty board with the given width, height, and komi.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - the width and height of the board, at most `MAX_SIZE`
+    /// * `komi` - the komi used for this game
+    ///
+    pub fn new(size: usize, komi: f32) -> Board {
+        assert!(size > 0 && size <= MAX_SIZE, "unsupported board size {}", size);
+
         Board {
-            inner: BoardFast::new(),
+            inner: BoardFast::new(size),
             history: CircularBuf::new(),
             komi: komi,
+            size: size as u8,
             count: 0,
             last_played: None,
+            to_move: Color::Black,
             zobrist_hash: 0,
             zobrist_history: SmallSet64::new(),
+            full_history: Some(PositionHistory::new()),
+            rules: Rules::default(),
+            num_placed: [0, 0],
         }
     }
 
     /// Returns the width and height of this board.
     #[inline]
     pub fn size(&self) -> usize {
-        19
+        self.size as usize
     }
 
     /// Returns the komi of this board.
@@ -77,6 +132,60 @@ impl Board {
         self.komi = komi;
     }
 
+    /// Returns the rules used for this board.
+    #[inline]
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    /// Sets the rules used for this board, this does not re-validate any
+    /// moves that has already been played.
+    ///
+    /// # Arguments
+    ///
+    /// * `rules` - the rules to use for this board
+    ///
+    #[inline]
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+    }
+
+    /// Sets whether to keep track of every board position since the start of
+    /// the game, or only the most recent ones. Without the full history some
+    /// long cycles (such as triple ko) are not detected as super-ko
+    /// violations.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - whether to keep track of every board position
+    ///
+    pub fn set_full_history(&mut self, enabled: bool) {
+        if !enabled {
+            self.full_history = None;
+        } else if self.full_history.is_none() {
+            let mut full_history = PositionHistory::new();
+
+            for z in self.zobrist_history.iter() {
+                full_history.push(z);
+            }
+
+            self.full_history = Some(full_history);
+        }
+    }
+
+    /// Returns the total number of stones of the given color that has been
+    /// placed on this board, including setup stones and stones that has
+    /// since been captured.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the stones
+    ///
+    #[inline]
+    pub fn num_placed(&self, color: Color) -> usize {
+        self.num_placed[color as usize - 1] as usize
+    }
+
     /// Returns the number of moves that has been played on this board.
     #[inline]
     pub fn count(&self) -> usize {
@@ -89,6 +198,28 @@ impl Board {
         self.zobrist_hash
     }
 
+    /// Returns a hash of every vertex that the given color can not play at
+    /// because of the super-ko rule. Two boards with the same zobrist hash
+    /// and `ko_hash` have the same legal moves for that color, even if
+    /// they were reached through different move orders.
+    ///
+    /// # Arguments
+    ///
+    /// * `to_move` - the color whose turn it is to play
+    ///
+    pub fn ko_hash(&self, to_move: Color) -> u64 {
+        let size = self.size();
+
+        (0..361)
+            .filter(|&index| index % 19 < size && index / 19 < size)
+            .filter(|&index| {
+                self.inner.vertices[index].visited() &&
+                    self.inner.is_valid(to_move, index) &&
+                    self._is_ko(to_move, index)
+            })
+            .fold(0, |hash, index| hash ^ zobrist::TABLE[0][index])
+    }
+
     /// Returns the color of the last player that played a move.
     #[inline]
     pub fn last_played(&self) -> Option<Color> {
@@ -98,10 +229,19 @@ impl Board {
     /// Returns the color whose turn it is to play a move.
     #[inline]
     pub fn to_move(&self) -> Color {
-        match self.last_played() {
-            Some(color) => color.opposite(),
-            _ => Color::Black,
-        }
+        self.to_move
+    }
+
+    /// Sets the color whose turn it is to play a move, this is only necessary
+    /// if the first move is not played by black (e.g. in handicap games).
+    ///
+    /// # Arguments
+    ///
+    /// * `to_move` - the color whose turn it is to play
+    ///
+    #[inline]
+    pub fn set_to_move(&mut self, to_move: Color) {
+        self.to_move = to_move;
     }
 
     /// Returns the color (if the vertex is not empty) of the stone at
@@ -139,9 +279,8 @@ impl Board {
 
         self.inner.vertices[index].visited() && {
             let adjust = self.inner.place_if_mut(color, index, workspace);
-            let next_zobrist_hash = self.zobrist_hash ^ adjust;
 
-            self.zobrist_history.contains(next_zobrist_hash)
+            self.is_repeated(color, self.zobrist_hash ^ adjust)
         }
     }
 
@@ -158,9 +297,84 @@ impl Board {
 
         self.inner.vertices[index].visited() && {
             let adjust = self.inner.place_if(color, index);
-            let next_zobrist_hash = self.zobrist_hash ^ adjust;
 
-            self.zobrist_history.contains(next_zobrist_hash)
+            self.is_repeated(color, self.zobrist_hash ^ adjust)
+        }
+    }
+
+    /// Returns true if the position with the given zobrist hash, after the
+    /// given color has played, violates the super-ko rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the move
+    /// * `next_zobrist_hash` - the zobrist hash after the move
+    ///
+    #[inline]
+    fn is_repeated(&self, color: Color, next_zobrist_hash: u64) -> bool {
+        match self.rules.superko {
+            Superko::Positional => {
+                self.is_in_history(next_zobrist_hash) ||
+                    self.is_in_history(next_zobrist_hash ^ WHITE_TO_MOVE)
+            },
+            Superko::Situational => {
+                self.is_in_history(next_zobrist_hash ^ to_move_key(color.opposite()))
+            }
+        }
+    }
+
+    /// Returns true if the given (mixed) zobrist hash is part of the history
+    /// of this board.
+    ///
+    /// # Arguments
+    ///
+    /// * `zobrist_hash` - the zobrist hash to look for
+    ///
+    #[inline]
+    fn is_in_history(&self, zobrist_hash: u64) -> bool {
+        self.zobrist_history.contains(zobrist_hash) || match self.full_history {
+            Some(ref full_history) => full_history.contains(zobrist_hash),
+            None => false
+        }
+    }
+
+    /// Adds the current position to the history of this board.
+    #[inline]
+    fn push_history(&mut self) {
+        let zobrist_hash = self.zobrist_hash ^ to_move_key(self.to_move);
+
+        self.zobrist_history.push(zobrist_hash);
+        if let Some(ref mut full_history) = self.full_history {
+            full_history.push(zobrist_hash);
+        }
+    }
+
+    /// Returns true if playing at the given index is a legal suicide, which
+    /// is a move that does not capture any stones, has no liberties, and
+    /// is connected to at least one other stone of the same color.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the move
+    /// * `index` - the index of the move
+    ///
+    fn _is_valid_suicide(&self, color: Color, index: usize) -> bool {
+        self.rules.suicide && self.inner.vertices[index].color() == 0 && {
+            let player = color as u8;
+            let mut groups: Vec<usize> = Vec::with_capacity(4);
+
+            for (other_index, other_vertex) in self.inner.adjacent_to(index) {
+                if other_vertex.color() == player && !groups.iter().any(|&g| self.inner.block_at(g).into_iter().any(|i| i == other_index)) {
+                    groups.push(other_index);
+                }
+            }
+
+            !groups.is_empty() && {
+                let adjust = groups.iter()
+                    .fold(0, |acc, &g| acc ^ self.inner.capture_if(player as usize, g));
+
+                !self.is_repeated(color, self.zobrist_hash ^ adjust)
+            }
         }
     }
 
@@ -174,7 +388,11 @@ impl Board {
     /// * `workspace` - the memoization of the board liberties
     ///
     pub fn is_valid_mut(&self, color: Color, index: usize, workspace: &mut [u8]) -> bool {
-        self.inner.is_valid_mut(color, index, workspace) && !self._is_ko_mut(color, index, workspace)
+        if self.inner.is_valid_mut(color, index, workspace) {
+            !self._is_ko_mut(color, index, workspace)
+        } else {
+            self._is_valid_suicide(color, index)
+        }
     }
 
     /// Returns whether the given move is valid according to the
@@ -187,7 +405,11 @@ impl Board {
     /// * `workspace` - the memoization of the board liberties
     ///
     pub(super) fn _is_valid(&self, color: Color, index: usize) -> bool {
-        self.inner.is_valid(color, index) && !self._is_ko(color, index)
+        if self.inner.is_valid(color, index) {
+            !self._is_ko(color, index)
+        } else {
+            self._is_valid_suicide(color, index)
+        }
     }
 
     /// Returns whether the given move is valid according to the
@@ -200,7 +422,7 @@ impl Board {
     /// * `y` - the row of the move
     ///
     pub fn is_valid(&self, color: Color, x: usize, y: usize) -> bool {
-        self._is_valid(color, 19 * y + x)
+        x < self.size() && y < self.size() && self._is_valid(color, 19 * y + x)
     }
 
     /// Place the given stone on the board without checking if it is legal, the
@@ -217,12 +439,18 @@ impl Board {
         // or not.
         self.zobrist_hash ^= self.inner.place(color, index);
         self.last_played = Some(color);
+        self.to_move = color.opposite();
         self.count += 1;
+        self.num_placed[color as usize - 1] += 1;
+
+        if self.rules.suicide && !self.inner.has_n_liberty::<One>(index, 1) {
+            self.zobrist_hash ^= self.inner.capture(color as usize, index);
+        }
 
         // store the actually played move since it is necessary for the feature
         // vector.
         self.history.push(index as u16);
-        self.zobrist_history.push(self.zobrist_hash);
+        self.push_history();
     }
 
     /// Place the given stone on the board without checking if it is legal, the
@@ -236,8 +464,29 @@ impl Board {
     /// * `y` - The row of the move
     ///
     pub fn place(&mut self, color: Color, x: usize, y: usize) {
+        debug_assert!(x < self.size() && y < self.size());
+
         self._place(color, 19 * y + x)
     }
+
+    /// Place the given setup stone (such as a handicap stone, or an `AB[]`
+    /// or `AW[]` property in an SGF file) on the board. Unlike `place` this
+    /// does not count as a move, so it does not change whose turn it is and
+    /// is not part of the move history.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the stone
+    /// * `x` - The column of the stone
+    /// * `y` - The row of the stone
+    ///
+    pub fn setup(&mut self, color: Color, x: usize, y: usize) {
+        debug_assert!(x < self.size() && y < self.size());
+
+        self.zobrist_hash ^= self.inner.place(color, 19 * y + x);
+        self.push_history();
+        self.num_placed[color as usize - 1] += 1;
+    }
 }
 
 impl fmt::Display for Board {
@@ -254,19 +503,21 @@ impl fmt::Display for Board {
             'v', 'w', 'x', 'y', 'z'
         ];
 
+        let size = self.size();
+
         write!(f, "    ")?;
-        for i in 0..19 { write!(f, " {}", LETTERS[i])?; }
+        for i in 0..size { write!(f, " {}", LETTERS[i])?; }
         writeln!(f)?;
         write!(f, "   \u{256d}")?;
-        for _ in 0..19 { write!(f, "\u{2500}\u{2500}")?; }
+        for _ in 0..size { write!(f, "\u{2500}\u{2500}")?; }
         writeln!(f, "\u{2500}\u{256e}")?;
 
-        for y in 0..19 {
-            let y = 18 - y;
+        for y in 0..size {
+            let y = size - y - 1;
 
             write!(f, "{:2} \u{2502}", 1 + y)?;
 
-            for x in 0..19 {
+            for x in 0..size {
                 let index = 19 * y + x;
 
                 if self.inner.vertices[index].color() == 0 {
@@ -282,10 +533,10 @@ impl fmt::Display for Board {
         }
 
         write!(f, "   \u{2570}")?;
-        for _ in 0..19 { write!(f, "\u{2500}\u{2500}")?; }
+        for _ in 0..size { write!(f, "\u{2500}\u{2500}")?; }
         writeln!(f, "\u{2500}\u{256f}")?;
         write!(f, "    ")?;
-        for i in 0..19 { write!(f, " {}", LETTERS[i])?; }
+        for i in 0..size { write!(f, " {}", LETTERS[i])?; }
         writeln!(f)?;
         writeln!(f, "    \u{25cf} Black    \u{25cb} White")?;
 
@@ -303,6 +554,8 @@ impl Hash for Board {
         }
 
         state.write_u32(self.komi.to_bits());
+        state.write_u8(self.size);
+        self.rules.hash(state);
     }
 }
 
@@ -312,7 +565,7 @@ impl PartialEq for Board {
             .zip(other.zobrist_history.iter())
             .all(|(a, b)| a == b);
 
-        history && self.inner.vertices.iter()
+        history && self.size == other.size && self.inner.vertices.iter()
             .zip(other.inner.vertices.iter())
             .all(|(a, b)| a.color() == b.color())
     }
@@ -324,12 +577,14 @@ impl Eq for Board { }
 mod tests {
     use board::*;
     use color::*;
+    use rules::*;
+    use small_set::SmallSet64;
 
     /// Test that it is possible to capture a stone in the middle of the
     /// board.
     #[test]
     fn capture() {
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
 
         board.place(Color::Black,  9,  9);
         board.place(Color::White,  8,  9);
@@ -343,7 +598,7 @@ mod tests {
     /// Test that it is possible to capture a group of stones in the corner.
     #[test]
     fn capture_group() {
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
 
         board.place(Color::Black, 0, 1);
         board.place(Color::Black, 1, 0);
@@ -365,7 +620,7 @@ mod tests {
     /// with two adjacent neighbours of the opposite color.
     #[test]
     fn suicide_corner() {
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
 
         board.place(Color::White, 0, 0);
         board.place(Color::Black, 1, 0);
@@ -380,7 +635,7 @@ mod tests {
     /// of a ponnuki.
     #[test]
     fn suicide_middle() {
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
 
         board.place(Color::Black,  9,  9);
         board.place(Color::White,  8,  9);
@@ -397,7 +652,57 @@ mod tests {
     /// corner ko.
     #[test]
     fn ko() {
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
+
+        board.place(Color::Black, 0, 0);
+        board.place(Color::Black, 0, 2);
+        board.place(Color::Black, 1, 1);
+        board.place(Color::White, 1, 0);
+        board.place(Color::White, 0, 1);
+
+        assert!(!board.is_valid(Color::Black, 0, 0));
+    }
+
+    /// Test that the ko hash only changes when the set of vertices that are
+    /// forbidden by the super-ko rule changes.
+    #[test]
+    fn ko_hash() {
+        let mut board = Board::new(19, 7.5);
+
+        board.place(Color::Black, 0, 0);
+        board.place(Color::Black, 0, 2);
+        board.place(Color::Black, 1, 1);
+        board.place(Color::White, 1, 0);
+
+        assert_eq!(board.ko_hash(Color::White), 0);
+        board.place(Color::White, 0, 1);
+
+        assert_ne!(board.ko_hash(Color::Black), 0);
+        assert_eq!(board.ko_hash(Color::White), 0);
+    }
+
+    /// Test that re-capturing a ko is legal if it only re-creates a position
+    /// with a different player to move, under situational super-ko.
+    #[test]
+    fn ko_situational() {
+        let mut board = Board::new(19, 7.5);
+        board.set_rules(Rules::japanese());
+
+        board.place(Color::Black, 0, 0);
+        board.place(Color::Black, 0, 2);
+        board.place(Color::Black, 1, 1);
+        board.place(Color::White, 1, 0);
+        board.place(Color::White, 0, 1);
+
+        assert!(board.is_valid(Color::Black, 0, 0));
+    }
+
+    /// Test that re-capturing a ko is illegal even if it only re-creates a
+    /// position with a different player to move, under positional super-ko.
+    #[test]
+    fn ko_positional() {
+        let mut board = Board::new(19, 7.5);
+        board.set_rules(Rules { superko: Superko::Positional, ..Rules::japanese() });
 
         board.place(Color::Black, 0, 0);
         board.place(Color::Black, 0, 2);
@@ -408,11 +713,61 @@ mod tests {
         assert!(!board.is_valid(Color::Black, 0, 0));
     }
 
+    /// Test that a ko is detected even after it has been pushed out of the
+    /// recent history, as long as the full history is enabled.
+    #[test]
+    fn ko_full_history() {
+        let mut board = Board::new(19, 7.5);
+
+        board.place(Color::Black, 0, 0);
+        board.place(Color::Black, 0, 2);
+        board.place(Color::Black, 1, 1);
+        board.place(Color::White, 1, 0);
+        board.place(Color::White, 0, 1);
+        board.zobrist_history = SmallSet64::new();
+
+        let mut other = board.clone();
+        assert!(!board.is_valid(Color::Black, 0, 0));
+        assert!(!other.is_valid(Color::Black, 0, 0));
+
+        other.set_full_history(false);
+        assert!(other.is_valid(Color::Black, 0, 0));
+        assert!(!board.is_valid(Color::Black, 0, 0));
+    }
+
+    /// Test that a multi-stone suicide is only legal if the rules allow it,
+    /// and that it removes the stones.
+    #[test]
+    fn suicide_allowed() {
+        let mut board = Board::new(19, 7.5);
+
+        board.place(Color::Black, 1, 0);
+        board.place(Color::White, 2, 0);
+        board.place(Color::White, 1, 1);
+        board.place(Color::White, 0, 1);
+        board.place(Color::White, 4, 5);
+        board.place(Color::White, 6, 5);
+        board.place(Color::White, 5, 4);
+        board.place(Color::White, 5, 6);
+
+        assert!(!board.is_valid(Color::Black, 0, 0));
+
+        board.set_rules(Rules::tromp_taylor());
+        assert!(board.is_valid(Color::Black, 0, 0));
+        assert!(!board.is_valid(Color::Black, 5, 5));
+
+        board.place(Color::Black, 0, 0);
+        assert_eq!(board.at(0, 0), None);
+        assert_eq!(board.at(1, 0), None);
+        assert_eq!(board.num_placed(Color::Black), 2);
+        assert_eq!(board.to_move(), Color::White);
+    }
+
     /// Test that when the same group is a neighbour multiple times we do
     /// not reduce its liberty count twice.
     #[test]
     fn double_liberty_subtraction() {
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
 
         board.place(Color::Black, 1, 1);
         board.place(Color::Black, 1, 2);
@@ -440,14 +795,14 @@ mod tests {
 
     #[test]
     fn black_starts() {
-        let board = Board::new(0.5);
+        let board = Board::new(19, 0.5);
 
         assert_eq!(board.to_move(), Color::Black);
     }
 
     #[test]
     fn alternate_turns() {
-        let mut board = Board::new(0.5);
+        let mut board = Board::new(19, 0.5);
 
         board.place(Color::Black, 0, 0);
         assert_eq!(board.to_move(), Color::White);
@@ -459,4 +814,55 @@ mod tests {
         board.place(Color::White, 2, 2);
         assert_eq!(board.to_move(), Color::Black);
     }
+
+    #[test]
+    fn setup_stones() {
+        let mut board = Board::new(19, 0.5);
+
+        board.setup(Color::Black, 3, 3);
+        board.setup(Color::Black, 15, 15);
+        board.set_to_move(Color::White);
+
+        assert_eq!(board.at(3, 3), Some(Color::Black));
+        assert_eq!(board.at(15, 15), Some(Color::Black));
+        assert_eq!(board.count(), 0);
+        assert_eq!(board.last_played(), None);
+        assert_eq!(board.to_move(), Color::White);
+        assert!(board != Board::new(19, 0.5));
+
+        board.place(Color::White, 15, 3);
+        assert_eq!(board.to_move(), Color::Black);
+    }
+
+    /// Test that the edge of a smaller board behaves as the edge of the
+    /// board, and not as empty vertices.
+    #[test]
+    fn suicide_corner_small() {
+        let mut board = Board::new(9, 7.5);
+
+        board.place(Color::White, 8, 8);
+        board.place(Color::Black, 7, 8);
+        board.place(Color::Black, 8, 7);
+
+        assert_eq!(board.at(8, 8), None);
+        assert!(!board.is_valid(Color::White, 8, 8));
+        assert!(board.is_valid(Color::Black, 8, 8));
+    }
+
+    /// Test that it is not possible to play outside of a smaller board.
+    #[test]
+    fn outside_small() {
+        let board = Board::new(13, 7.5);
+
+        assert_eq!(board.size(), 13);
+        assert!(board.is_valid(Color::Black, 12, 12));
+        assert!(!board.is_valid(Color::Black, 13, 0));
+        assert!(!board.is_valid(Color::Black, 0, 13));
+    }
+
+    /// Test that two boards of different size are not considered equal.
+    #[test]
+    fn different_sizes() {
+        assert!(Board::new(9, 7.5) != Board::new(13, 7.5));
+    }
 }
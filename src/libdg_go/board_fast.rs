@@ -402,21 +402,44 @@ pub struct BoardFast {
 }
 
 impl BoardFast {
-    /// Returns an empty board.
-    pub fn new() -> BoardFast {
+    /// Returns an empty board with the given width and height. Boards that
+    /// are smaller than `19x19` are stored in the upper left corner of the
+    /// full sized board, with the remaining vertices marked as _invalid_.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - the width and height of the board
+    ///
+    pub fn new(size: usize) -> BoardFast {
+        debug_assert!(size > 0 && size <= 19);
+
         let mut board = BoardFast {
             vertices: [0; 368],
         };
 
         // fill the padding with _invalid_ elements that does not match either
         // of the three possible vertices (`Black`, `White`, and `Empty`).
-        for i in 361..368 {
-            board.vertices[i].set_color(0x3);
+        for i in 0..368 {
+            if i >= 361 || i % 19 >= size || i / 19 >= size {
+                board.vertices[i].set_color(0x3);
+            }
         }
 
         board
     }
 
+    /// Returns whether the given vertex is outside of the playable area of
+    /// the board.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` -
+    ///
+    #[inline]
+    pub fn is_off_board(&self, index: usize) -> bool {
+        self.vertices[index].color() == 0x3
+    }
+
     /// Returns an iterator over all vertices that are adjacent to the given
     /// vertex.
     ///
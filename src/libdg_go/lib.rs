@@ -29,11 +29,20 @@ mod board;
 mod circular_buf;
 mod codegen;
 mod color;
+mod position_history;
+mod rules;
 pub mod utils;
 mod small_set;
 mod zobrist;
 
 pub use self::color::*;
 pub use self::board::*;
+pub use self::rules::*;
 
 pub const DEFAULT_KOMI: f32 = 7.5;
+
+/// The width and height of the board if nothing else has been specified.
+pub const DEFAULT_SIZE: usize = 19;
+
+/// The largest supported width and height of the board.
+pub const MAX_SIZE: usize = 19;
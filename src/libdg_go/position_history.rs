@@ -0,0 +1,234 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::Arc;
+
+/// The maximum number of hashes to keep in the linked list of recent
+/// positions before they are frozen into a hash set.
+const MAX_RECENT: usize = 32;
+
+/// Hasher that uses the (already random) zobrist hash as-is.
+#[derive(Default)]
+struct ZobristHasher {
+    value: u64
+}
+
+impl Hasher for ZobristHasher {
+    fn finish(&self) -> u64 {
+        self.value
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.value = (self.value << 8) ^ (self.value >> 56) ^ (b as u64);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.value = value;
+    }
+}
+
+type ZobristSet = HashSet<u64, BuildHasherDefault<ZobristHasher>>;
+
+/// A single link in the list of recently added positions.
+struct Link {
+    hash: u64,
+    next: Option<Arc<Link>>
+}
+
+/// A hash set that is never modified once it has been created, and the
+/// (older) layer that was frozen before it.
+struct Layer {
+    hashes: ZobristSet,
+    next: Option<Arc<Layer>>
+}
+
+/// A persistent set of every zobrist hash that has been added to it. Old
+/// hashes are stored in a chain of immutable hash sets, while recent hashes
+/// are stored in a linked list. Both are shared between all clones, so
+/// cloning a history is just two reference count increments.
+#[derive(Clone)]
+pub struct PositionHistory {
+    frozen: Option<Arc<Layer>>,
+    recent: Option<Arc<Link>>,
+    num_recent: usize
+}
+
+impl PositionHistory {
+    /// Returns an empty history.
+    pub fn new() -> PositionHistory {
+        PositionHistory {
+            frozen: None,
+            recent: None,
+            num_recent: 0
+        }
+    }
+
+    /// Adds the given hash to this history. This does not affect any other
+    /// history that this one was cloned from, or has been cloned into.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - the hash to add
+    ///
+    pub fn push(&mut self, hash: u64) {
+        self.recent = Some(Arc::new(Link {
+            hash: hash,
+            next: self.recent.take()
+        }));
+        self.num_recent += 1;
+
+        if self.num_recent >= MAX_RECENT {
+            let mut hashes = ZobristSet::default();
+            let mut link = self.recent.take();
+
+            while let Some(current) = link {
+                hashes.insert(current.hash);
+                link = current.next.clone();
+            }
+
+            // merge any layers that are not larger than the new one into
+            // it, so that the number of layers stays logarithmic in the
+            // number of hashes. The merged layers are copied, since they
+            // may still be in use by other clones.
+            let mut next = self.frozen.take();
+
+            while let Some(layer) = next.take() {
+                if layer.hashes.len() <= hashes.len() {
+                    hashes.extend(layer.hashes.iter().cloned());
+                    next = layer.next.clone();
+                } else {
+                    next = Some(layer);
+                    break;
+                }
+            }
+
+            self.frozen = Some(Arc::new(Layer { hashes: hashes, next: next }));
+            self.num_recent = 0;
+        }
+    }
+
+    /// Returns true if the given hash has been added to this history.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - the hash to look for
+    ///
+    pub fn contains(&self, hash: u64) -> bool {
+        let mut link = self.recent.as_ref();
+
+        while let Some(current) = link {
+            if current.hash == hash {
+                return true;
+            }
+
+            link = current.next.as_ref();
+        }
+
+        let mut layer = self.frozen.as_ref();
+
+        while let Some(current) = layer {
+            if current.hashes.contains(&hash) {
+                return true;
+            }
+
+            layer = current.next.as_ref();
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use position_history::*;
+
+    #[test]
+    fn contains() {
+        let mut history = PositionHistory::new();
+
+        for i in 0..100 {
+            history.push(i * 7919);
+        }
+
+        assert!((0..100).all(|i| history.contains(i * 7919)));
+        assert!(!history.contains(1));
+    }
+
+    #[test]
+    fn clones_are_independent() {
+        let mut a = PositionHistory::new();
+
+        for i in 0..40 {
+            a.push(i);
+        }
+
+        let mut b = a.clone();
+        b.push(1000);
+        for i in 0..40 {
+            a.push(2000 + i);
+        }
+
+        assert!(b.contains(1000));
+        assert!(!b.contains(2000));
+        assert!(!a.contains(1000));
+        assert!(a.contains(2039));
+        assert!((0..40).all(|i| a.contains(i) && b.contains(i)));
+    }
+
+    #[test]
+    fn logarithmic_number_of_layers() {
+        let mut history = PositionHistory::new();
+
+        for i in 0..(64 * MAX_RECENT as u64) {
+            history.push(i);
+        }
+
+        let mut num_layers = 0;
+        let mut layer = history.frozen.as_ref();
+
+        while let Some(current) = layer {
+            num_layers += 1;
+            layer = current.next.as_ref();
+        }
+
+        assert!(num_layers <= 7, "{}", num_layers);
+        assert!((0..(64 * MAX_RECENT as u64)).all(|i| history.contains(i)));
+    }
+
+    #[test]
+    fn frozen_layers_are_shared() {
+        let mut a = PositionHistory::new();
+
+        for i in 0..(2 * MAX_RECENT as u64) {
+            a.push(i);
+        }
+
+        let b = a.clone();
+
+        for i in 0..(MAX_RECENT as u64) {
+            a.push(1000 + i);
+        }
+
+        let a_next = a.frozen.as_ref().unwrap().next.as_ref().unwrap();
+        let b_frozen = b.frozen.as_ref().unwrap();
+
+        assert!(Arc::ptr_eq(a_next, b_frozen));
+        assert_eq!(Arc::strong_count(b_frozen), 2);
+        assert!((0..(MAX_RECENT as u64)).all(|i| a.contains(1000 + i) && !b.contains(1000 + i)));
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::str::FromStr;
+
+use dg_utils::config::{RuleSet, SuperkoRule};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scoring {
+    /// Stones on the board, and the empty vertices they surround.
+    Area,
+
+    /// The empty vertices surrounded by stones, and any prisoners.
+    Territory
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Superko {
+    /// A move may not re-create any previous board position.
+    Positional,
+
+    /// A move may not re-create any previous board position with the same
+    /// player to move.
+    Situational
+}
+
+impl From<SuperkoRule> for Superko {
+    fn from(superko: SuperkoRule) -> Superko {
+        match superko {
+            SuperkoRule::Positional => Superko::Positional,
+            SuperkoRule::Situational => Superko::Situational
+        }
+    }
+}
+
+impl FromStr for Superko {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Superko, Self::Err> {
+        s.parse::<SuperkoRule>().map(Superko::from)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HandicapKomi {
+    /// White receives no compensation for handicap stones.
+    None,
+
+    /// White receives one point per handicap stone.
+    Full,
+
+    /// White receives one point per handicap stone, except the first one.
+    MinusOne
+}
+
+/// The rules of a game, such as how to score it and which moves are legal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rules {
+    pub scoring: Scoring,
+    pub superko: Superko,
+    pub handicap_komi: HandicapKomi,
+
+    /// Whether a move is allowed to capture its own group (of more than one
+    /// stone).
+    pub suicide: bool,
+
+    /// Whether all dead stones needs to be captured before the game ends,
+    /// since there is no agreement phase.
+    pub capture_dead: bool
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules::chinese()
+    }
+}
+
+impl Rules {
+    pub fn chinese() -> Rules {
+        Rules {
+            scoring: Scoring::Area,
+            superko: Superko::Positional,
+            handicap_komi: HandicapKomi::Full,
+            suicide: false,
+            capture_dead: false
+        }
+    }
+
+    pub fn japanese() -> Rules {
+        Rules {
+            scoring: Scoring::Territory,
+            superko: Superko::Situational,
+            handicap_komi: HandicapKomi::None,
+            suicide: false,
+            capture_dead: false
+        }
+    }
+
+    pub fn aga() -> Rules {
+        Rules {
+            scoring: Scoring::Area,
+            superko: Superko::Situational,
+            handicap_komi: HandicapKomi::MinusOne,
+            suicide: false,
+            capture_dead: false
+        }
+    }
+
+    pub fn new_zealand() -> Rules {
+        Rules {
+            scoring: Scoring::Area,
+            superko: Superko::Situational,
+            handicap_komi: HandicapKomi::Full,
+            suicide: true,
+            capture_dead: false
+        }
+    }
+
+    pub fn tromp_taylor() -> Rules {
+        Rules {
+            scoring: Scoring::Area,
+            superko: Superko::Positional,
+            handicap_komi: HandicapKomi::None,
+            suicide: true,
+            capture_dead: true
+        }
+    }
+
+    /// Returns the komi that white receive as compensation for the given
+    /// number of handicap stones.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_stones` - the number of handicap stones
+    ///
+    pub fn handicap_komi(&self, num_stones: usize) -> f32 {
+        match self.handicap_komi {
+            HandicapKomi::None => 0.0,
+            HandicapKomi::Full => num_stones as f32,
+            HandicapKomi::MinusOne => num_stones.saturating_sub(1) as f32
+        }
+    }
+
+    /// Returns the name of these rules as used by the `RU[...]` property of
+    /// an SGF file.
+    pub fn sgf_name(&self) -> String {
+        match self.to_string().as_str() {
+            "chinese" => "Chinese".to_string(),
+            "japanese" => "Japanese".to_string(),
+            "aga" => "AGA".to_string(),
+            "new_zealand" => "NZ".to_string(),
+            "tromp-taylor" => "Tromp-Taylor".to_string(),
+            other => other.to_string()
+        }
+    }
+}
+
+impl From<RuleSet> for Rules {
+    fn from(rules: RuleSet) -> Rules {
+        match rules {
+            RuleSet::Chinese => Rules::chinese(),
+            RuleSet::Japanese => Rules::japanese(),
+            RuleSet::Aga => Rules::aga(),
+            RuleSet::NewZealand => Rules::new_zealand(),
+            RuleSet::TrompTaylor => Rules::tromp_taylor()
+        }
+    }
+}
+
+impl FromStr for Rules {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Rules, Self::Err> {
+        s.parse::<RuleSet>().map(Rules::from)
+    }
+}
+
+impl fmt::Display for Rules {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let known = [
+            ("chinese", Rules::chinese()),
+            ("japanese", Rules::japanese()),
+            ("aga", Rules::aga()),
+            ("new_zealand", Rules::new_zealand()),
+            ("tromp-taylor", Rules::tromp_taylor())
+        ];
+
+        match known.iter().find(|(_, rules)| rules == self) {
+            Some((name, _)) => write!(fmt, "{}", name),
+            None => write!(fmt, "custom")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!("chinese".parse::<Rules>(), Ok(Rules::chinese()));
+        assert_eq!("Japanese".parse::<Rules>(), Ok(Rules::japanese()));
+        assert_eq!("new_zealand".parse::<Rules>(), Ok(Rules::new_zealand()));
+        assert_eq!("tt".parse::<Rules>(), Ok(Rules::tromp_taylor()));
+        assert_eq!("ing".parse::<Rules>(), Err(()));
+        assert_eq!("situational".parse::<Superko>(), Ok(Superko::Situational));
+        assert_eq!("psk".parse::<Superko>(), Ok(Superko::Positional));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(Rules::aga().to_string(), "aga");
+        assert_eq!(Rules::new_zealand().sgf_name(), "NZ");
+        assert_eq!(Rules::default().sgf_name(), "Chinese");
+    }
+
+    #[test]
+    fn handicap_komi() {
+        assert_eq!(Rules::chinese().handicap_komi(4), 4.0);
+        assert_eq!(Rules::aga().handicap_komi(4), 3.0);
+        assert_eq!(Rules::aga().handicap_komi(0), 0.0);
+        assert_eq!(Rules::japanese().handicap_komi(4), 0.0);
+    }
+}
@@ -14,10 +14,10 @@
 
 use color::Color;
 use board::Board;
-use ::DEFAULT_KOMI;
+use ::{DEFAULT_KOMI, DEFAULT_SIZE};
 
 use super::features::{HWC, FEATURE_SIZE, NUM_FEATURES, Features};
-use super::sgf::{Sgf, SgfError};
+use super::sgf::{Sgf, SgfError, SgfErrorKind};
 use super::symmetry;
 
 use dg_utils::types::f16;
@@ -177,8 +177,8 @@ pub unsafe extern fn extract_single_example(
 
         for m in Sgf::new(content.as_bytes(), komi) {
             match m {
-                Err(SgfError::IllegalMove) => { return -30 },
-                Err(SgfError::ParseError) => { return -23 },
+                Err(SgfError { kind: SgfErrorKind::IllegalMove, .. }) => { return -30 },
+                Err(_) => { return -23 },
                 Ok(m) => {
                     let is_pass = m.x >= 19 || m.y >= 19;
 
@@ -203,7 +203,7 @@ pub unsafe extern fn extract_single_example(
             let last_color = examples.last().map(|cand| cand.color).unwrap_or(Color::White);
 
             examples.push(Candidate {
-                board: last_board.unwrap_or_else(|| Board::new(komi)),
+                board: last_board.unwrap_or_else(|| Board::new(DEFAULT_SIZE, komi)),
                 index: 361,
                 color: last_color.opposite(),
                 policy: None,
@@ -219,8 +219,10 @@ pub unsafe extern fn extract_single_example(
         }
 
         // if any of the candidate examples has full policies, then only consider
-        // those policies. Also remove any candidates whose `value` is too extreme
-        // since the MCTS does not tend to play too well in those situations.
+        // those policies, this skips the moves that only got a cheap search
+        // during self-play (see the `full_search_prob` option). Also remove any
+        // candidates whose `value` is too extreme since the MCTS does not tend
+        // to play too well in those situations.
         let candidate_examples: Vec<usize> = (0..examples.len())
             .filter(|&i| {
                 (!has_policy || examples[i].has_policy()) && examples[i].has_reasonable_value()
@@ -407,4 +409,30 @@ TW[da][ea][fa][ma][na][oa][eb][ib][lb][mb][nb][cc][dc][ec][fc][nc]
             -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0
         ]);
     }
+
+    #[test]
+    fn skip_cheap_searches() {
+        let policy = b85::encode(&vec! [1.0 / 362.0; 362]);
+        let mut content = "(;GM[1]FF[4]SZ[19]KM[7.5]RE[B+R]".to_string();
+
+        for i in 0..40 {
+            let color = if i % 2 == 0 { "B" } else { "W" };
+            let (x, y) = (2 * (i % 10), 2 * (i / 10));
+
+            content += &format!(";{}[{}]", color, CGoban::to_sgf(x, y));
+            if i % 4 == 0 {
+                content += &format!("P[{}]", policy);
+            }
+        }
+        content += ")";
+
+        let c_string = ::std::ffi::CString::new(content).unwrap();
+        let mut example = Example::default();
+
+        for _ in 0..20 {
+            assert_eq!(unsafe { extract_single_example(c_string.as_ptr(), &mut example) }, 0);
+            assert_eq!(example.number % 4, 0);
+            assert!(example.policy.iter().all(|&p| p > 0.0));
+        }
+    }
 }
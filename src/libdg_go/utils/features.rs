@@ -73,6 +73,9 @@ impl Features for Board {
     /// 
     /// ## Global properties
     /// 
+    /// These are only filled in for the vertices that are on the board, so
+    /// for boards smaller than `19x19` they also mark the edge of the board.
+    /// 
     ///  1. A constant plane filled with ones if we are black
     ///  2. A constant plane filled with ones if we are white
     ///  3. A constant plane filled with ones if any move is super-ko
@@ -138,7 +141,7 @@ impl Features for Board {
         let c_1 = T::from(1.0);
 
         let mut features = vec! [c_0; FEATURE_SIZE];
-        let symmetry_table = symmetry.get_table();
+        let symmetry_table = symmetry.get_table(self.size());
         let current = to_move as u8;
         let opponent = to_move.opposite();
 
@@ -159,7 +162,9 @@ impl Features for Board {
         for index in 0..361 {
             let other = symmetry_table[index] as usize;
 
-            if self.inner.vertices[index].color() != 0 {
+            if self.inner.is_off_board(index) {
+                // pass
+            } else if self.inner.vertices[index].color() != 0 {
                 let start = if self.inner.vertices[index].color() == current { 5 } else { 21 };
                 let num_liberties = ::std::cmp::min(
                     get_num_liberties(&self.inner, index, &mut liberties),
@@ -229,6 +234,10 @@ impl Features for Board {
         let is_white = if to_move == Color::White { c_komi } else { c_0 };
 
         for index in 0..361 {
+            if self.inner.is_off_board(index) {
+                continue;
+            }
+
             let other = symmetry_table[index] as usize;
 
             features[O::index(0, other)] = is_black;
@@ -372,7 +381,7 @@ mod tests {
 
     #[test]
     fn check_features_chw() {
-        let features = Board::new(0.5)
+        let features = Board::new(19, 0.5)
             .get_features::<CHW, f32>(Color::Black, symmetry::Transform::Identity);
 
         assert_eq!(features.len(), FEATURE_SIZE);
@@ -380,9 +389,21 @@ mod tests {
 
     #[test]
     fn check_features_hwc() {
-        let features = Board::new(0.5)
+        let features = Board::new(19, 0.5)
             .get_features::<HWC, f32>(Color::Black, symmetry::Transform::Identity);
 
         assert_eq!(features.len(), FEATURE_SIZE);
     }
+
+    #[test]
+    fn check_features_small() {
+        let features = Board::new(9, 0.5)
+            .get_features::<CHW, f32>(Color::Black, symmetry::Transform::Rot90);
+        let on_board = (0..361)
+            .filter(|&i| features[CHW::index(0, i)] > 0.0)
+            .collect::<Vec<_>>();
+
+        assert_eq!(on_board.len(), 81);
+        assert!(on_board.iter().all(|&i| i % 19 < 9 && i / 19 < 9));
+    }
 }
@@ -193,7 +193,7 @@ mod tests {
         // . . . . .
         // X . . . X
         //
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
         board.place(Color::Black,  0,  0);
         board.place(Color::Black,  0, 18);
         board.place(Color::Black, 18,  0);
@@ -231,7 +231,7 @@ mod tests {
         // . . . . .
         // . . . . .
         //
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
         board.place(Color::White, 3, 3);
         board.place(Color::Black, 2, 3);
         board.place(Color::Black, 3, 2);
@@ -255,7 +255,7 @@ mod tests {
     #[test]
     fn ladder_escape() {
         // test a standard ladder pattern with a stone on the diagonal
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
         board.place(Color::White,  3,  3);
         board.place(Color::White, 15, 15);  // ladder breaking
         board.place(Color::Black,  2,  3);
@@ -313,7 +313,7 @@ mod tests {
             (Color::Black,  4, 12), (Color::White,  3, 12), (Color::Black,  3, 11)
         ];
 
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
 
         for &(color, x, y) in moves.into_iter() {
             board.place(color, x, y);
@@ -330,7 +330,7 @@ mod tests {
             (Color::Black,  1,  4)
         ];
 
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
 
         for &(color, x, y) in moves.into_iter() {
             board.place(color, x, y);
@@ -347,7 +347,7 @@ mod tests {
             (Color::Black,  1,  4)
         ];
 
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
 
         for &(color, x, y) in moves.into_iter() {
             board.place(color, x, y);
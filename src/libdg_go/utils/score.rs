@@ -15,6 +15,7 @@
 use board_fast::{BoardFast, Vertex, Two};
 use board::Board;
 use color::Color;
+use rules::Scoring;
 
 use std::collections::VecDeque;
 
@@ -64,7 +65,8 @@ pub trait Score {
     fn get_scorable_territory(&self) -> Vec<usize>;
 
     /// Returns the score for each player `(black, white)` of the
-    /// current board state according to the Tromp-Taylor rules.
+    /// current board state according to the rules of this board, which
+    /// for area scoring are the Tromp-Taylor rules.
     ///
     /// This method does not take any komi into account, you will
     /// need to add it yourself.
@@ -72,7 +74,7 @@ pub trait Score {
 
     /// Returns the score for each player `(black, white)` of the
     /// current board state after any stones that are not part of
-    /// the given _finished_ board state. The Tromp-Taylor rules are
+    /// the given _finished_ board state. The rules of this board are
     /// used to determine the score after clean-up.
     ///
     /// This method does not take any komi into account, you will
@@ -112,7 +114,9 @@ impl Score for Board {
             let mut workspace = [0; 368];
 
             (0..361).all(|i| {
-                self.inner.vertices[i].color() == 0 || self.inner.has_n_liberty_mut::<Two>(i, 2, &mut workspace)
+                let color = self.inner.vertices[i].color();
+
+                color == 0 || color == 0x3 || self.inner.has_n_liberty_mut::<Two>(i, 2, &mut workspace)
             })
         }
     }
@@ -122,13 +126,13 @@ impl Score for Board {
         let white_distance = get_territory_distance(&self.inner, Color::White);
 
         (0..361).filter(|&i| {
-            black_distance[i] == 0xff || white_distance[i] == 0xff
+            !self.inner.is_off_board(i) && (black_distance[i] == 0xff || white_distance[i] == 0xff)
         }).collect()
     }
 
     fn get_score(&self) -> (usize, usize) {
         if self.zobrist_hash != 0 {  // at least one stone has been played
-            get_tt_score(&self.inner)
+            get_rules_score(self, &self.inner)
         } else {
             (0, 0)
         }
@@ -158,7 +162,7 @@ impl Score for Board {
             }
         }
 
-        get_tt_score(&other)
+        get_rules_score(self, &other)
     }
 
     fn get_stone_status(&self, finished: &Board) -> Vec<(usize, Vec<StoneStatus>)> {
@@ -167,7 +171,9 @@ impl Score for Board {
         let mut status_list = vec! [];
 
         for i in 0..361 {
-            if self.inner.vertices[i].color() == finished.inner.vertices[i].color() {
+            if self.inner.is_off_board(i) {
+                // pass
+            } else if self.inner.vertices[i].color() == finished.inner.vertices[i].color() {
                 if self.inner.vertices[i].color() != 0 {
                     let territory_status = match Color::from(self.inner.vertices[i].color()) {
                         Color::Black => StoneStatus::BlackTerritory,
@@ -214,6 +220,32 @@ impl Score for Board {
     }
 }
 
+/// Returns the score of the given cleaned-up board according to the rules
+/// of `board`. For territory scoring the score is derived from the area score
+/// by removing all stones on the board, and adding all prisoners (any stone
+/// that was placed on `board` but is not present on `inner`).
+///
+/// # Arguments
+///
+/// * `board` - the board whose rules, and prisoners, to use
+/// * `inner` - the board to score
+///
+fn get_rules_score(board: &Board, inner: &BoardFast) -> (usize, usize) {
+    let (black, white) = get_tt_score(inner);
+
+    match board.rules().scoring {
+        Scoring::Area => (black, white),
+        Scoring::Territory => {
+            let black_stones = (0..361).filter(|&i| inner.vertices[i].color() == Color::Black as u8).count();
+            let white_stones = (0..361).filter(|&i| inner.vertices[i].color() == Color::White as u8).count();
+            let black_prisoners = board.num_placed(Color::White).saturating_sub(white_stones);
+            let white_prisoners = board.num_placed(Color::Black).saturating_sub(black_stones);
+
+            (black - black_stones + black_prisoners, white - white_stones + white_prisoners)
+        }
+    }
+}
+
 /// Returns the score of the given board according to the Tromp-Taylor
 /// rules.
 ///
@@ -228,7 +260,9 @@ fn get_tt_score(board: &BoardFast) -> (usize, usize) {
     let white_distance = get_territory_distance(&board, Color::White);
 
     for i in 0..361 {
-        if black_distance[i] == 0 as u8 {
+        if board.is_off_board(i) {
+            // pass
+        } else if black_distance[i] == 0 as u8 {
             black += 1; // black has stone at vertex
         } else if white_distance[i] == 0 as u8 {
             white += 1; // white has stone at vertex
@@ -288,11 +322,12 @@ fn get_territory_distance(board: &BoardFast, color: Color) -> [u8; 368] {
 mod tests {
     use board::*;
     use color::*;
+    use rules::*;
     use super::*;
 
     #[test]
     fn score_black() {
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
         board.place(Color::Black, 0, 0);
 
         assert!(!board.is_scorable());
@@ -301,7 +336,7 @@ mod tests {
 
     #[test]
     fn score_white() {
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
         board.place(Color::White, 0, 0);
 
         assert!(!board.is_scorable());
@@ -310,7 +345,7 @@ mod tests {
 
     #[test]
     fn score_black_white() {
-        let mut board = Board::new(7.5);
+        let mut board = Board::new(19, 7.5);
         board.place(Color::White, 1, 0);
         board.place(Color::White, 0, 1);
         board.place(Color::White, 1, 1);
@@ -328,4 +363,44 @@ mod tests {
         assert!(board.is_scorable());
         assert_eq!(board.get_score(), (353, 8));
     }
+
+    #[test]
+    fn score_black_small() {
+        let mut board = Board::new(9, 7.5);
+        board.place(Color::Black, 0, 0);
+
+        assert_eq!(board.get_score(), (81, 0));
+    }
+
+    #[test]
+    fn score_black_white_small() {
+        let mut board = Board::new(9, 7.5);
+        for y in 0..9 {
+            board.place(Color::Black, 3, y);
+            board.place(Color::White, 4, y);
+        }
+
+        assert!(board.is_scorable());
+        assert_eq!(board.get_score(), (36, 45));
+        assert_eq!(board.get_scorable_territory().len(), 81);
+    }
+
+    #[test]
+    fn score_territory_small() {
+        let mut board = Board::new(9, 7.5);
+        board.set_rules(Rules::japanese());
+        board.place(Color::White, 0, 0);
+        board.place(Color::Black, 1, 0);
+        board.place(Color::Black, 0, 1);
+        for y in 0..9 {
+            board.place(Color::Black, 3, y);
+            board.place(Color::White, 4, y);
+        }
+
+        assert_eq!(board.at(0, 0), None);
+        assert_eq!(board.get_score(), (26, 36));
+
+        board.set_rules(Rules::chinese());
+        assert_eq!(board.get_score(), (36, 45));
+    }
 }
@@ -0,0 +1,75 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SgfErrorKind {
+    /// The file ended before the game tree was closed.
+    UnexpectedEndOfFile,
+
+    /// A character that is not allowed at this point of the grammar.
+    UnexpectedCharacter(char),
+
+    /// A property value that could not be interpreted, such as a move that
+    /// is outside of the board.
+    InvalidValue,
+
+    /// The `SZ[...]` property is not a supported board size.
+    UnsupportedBoardSize,
+
+    /// A move that is not legal in the current board position.
+    IllegalMove
+}
+
+/// An error in an SGF file, and the byte offset (from the start of the file)
+/// where it was detected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SgfError {
+    pub kind: SgfErrorKind,
+    pub offset: usize
+}
+
+impl SgfError {
+    pub fn new(kind: SgfErrorKind, offset: usize) -> SgfError {
+        SgfError { kind, offset }
+    }
+}
+
+impl fmt::Display for SgfError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            SgfErrorKind::UnexpectedEndOfFile => write!(fmt, "unexpected end of file")?,
+            SgfErrorKind::UnexpectedCharacter(ch) => write!(fmt, "unexpected character {:?}", ch)?,
+            SgfErrorKind::InvalidValue => write!(fmt, "invalid property value")?,
+            SgfErrorKind::UnsupportedBoardSize => write!(fmt, "unsupported board size")?,
+            SgfErrorKind::IllegalMove => write!(fmt, "illegal move")?
+        }
+
+        write!(fmt, " at byte {}", self.offset)
+    }
+}
+
+impl error::Error for SgfError {
+    fn description(&self) -> &str {
+        match self.kind {
+            SgfErrorKind::UnexpectedEndOfFile => "unexpected end of file",
+            SgfErrorKind::UnexpectedCharacter(_) => "unexpected character",
+            SgfErrorKind::InvalidValue => "invalid property value",
+            SgfErrorKind::UnsupportedBoardSize => "unsupported board size",
+            SgfErrorKind::IllegalMove => "illegal move"
+        }
+    }
+}
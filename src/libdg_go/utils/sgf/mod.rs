@@ -12,9 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use ::{Board, Color};
+use ::{Board, Color, DEFAULT_SIZE, MAX_SIZE};
 use memchr::memchr;
 
+mod error;
+mod tree;
+mod writer;
+
+pub use self::error::*;
+pub use self::tree::*;
+pub use self::writer::*;
+
 static SGF_LETTERS: [char; 26] = [
     'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm',
     'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z'
@@ -80,12 +88,6 @@ impl SgfCoordinate for Sabaki {
 
 // -------- Parsing --------
 
-#[derive(Debug)]
-pub enum SgfError {
-    IllegalMove,
-    ParseError
-}
-
 pub struct SgfEntry<'a> {
     pub board: Board,
     pub policy: Option<&'a [u8]>,
@@ -96,9 +98,13 @@ pub struct SgfEntry<'a> {
     pub y: usize
 }
 
+/// A fast iterator over all moves (in all variations) of an SGF file, and
+/// the board position before each move. This is used by the training
+/// pipeline, see `GameTree` for a complete parser.
 pub struct Sgf<'a> {
     content: &'a [u8],
     board: Vec<Board>,
+    error: Option<SgfError>,
     index: usize,
 }
 
@@ -223,14 +229,146 @@ fn find_next_move<'a>(bytes: &'a [u8], start_at: &mut usize) -> Option<SgfMatch<
     None
 }
 
+/// Returns the width and height of the board as given by the `SZ[...]`
+/// property of the given SGF file, or `DEFAULT_SIZE` if the file does not
+/// specify a size (Fox sometimes output `SZ[0]`). If the size is not
+/// supported then `None` is returned.
+///
+/// # Arguments
+///
+/// * `bytes` - the content of the SGF file
+///
+pub fn get_board_size(bytes: &[u8]) -> Option<usize> {
+    try_get_board_size(bytes).ok()
+}
+
+fn try_get_board_size(bytes: &[u8]) -> Result<usize, SgfError> {
+    match bytes.windows(3).position(|w| w == b"SZ[") {
+        None => Ok(DEFAULT_SIZE),
+        Some(i) => {
+            let mut start_at = i + 3;
+            let value = skip_until_next(bytes, &mut start_at, b']');
+
+            ::std::str::from_utf8(value).ok()
+                .and_then(|x| x.trim().parse::<usize>().ok())
+                .map(|size| if size == 0 { DEFAULT_SIZE } else { size })
+                .filter(|&size| size <= MAX_SIZE)
+                .ok_or_else(|| SgfError::new(SgfErrorKind::UnsupportedBoardSize, i))
+        }
+    }
+}
+
+/// Returns the setup stones (`AB[...]` and `AW[...]`) and the player to
+/// move (`PL[...]`) of the root node of the given SGF file. Each stone also
+/// contains the byte offset of its value.
+///
+/// # Arguments
+///
+/// * `bytes` - the content of the SGF file
+///
+fn get_setup_stones(bytes: &[u8]) -> (Vec<(Color, usize, usize, usize)>, Option<Color>) {
+    let mut stones = vec! [];
+    let mut to_move = None;
+    let mut start_at = match memchr(b';', bytes) {
+        Some(i) => i + 1,
+        None => return (stones, to_move)
+    };
+
+    loop {
+        skip_ws(bytes, &mut start_at);
+        if start_at >= bytes.len() || bytes[start_at] == b';' || bytes[start_at] == b'(' || bytes[start_at] == b')' {
+            break
+        }
+
+        let key = skip_until_next(bytes, &mut start_at, b'[');
+        if key.is_empty() {
+            break
+        }
+
+        // a property can have several values, e.g. `AB[dd][pp]`, and any
+        // escaped `]` inside of a value is not the end of the value.
+        loop {
+            let value_begin = start_at;
+
+            while start_at < bytes.len() && bytes[start_at] != b']' {
+                start_at += if bytes[start_at] == b'\\' { 2 } else { 1 };
+            }
+
+            let value = &bytes[value_begin..::std::cmp::min(start_at, bytes.len())];
+            let value = ::std::str::from_utf8(value).unwrap_or("");
+
+            match key {
+                b"AB" | b"AW" => {
+                    let color = if key == b"AB" { Color::Black } else { Color::White };
+
+                    if let Ok((x, y)) = CGoban::parse(value) {
+                        stones.push((color, x, y, value_begin));
+                    }
+                },
+                b"PL" => {
+                    to_move = value.trim().parse::<Color>().ok();
+                },
+                _ => {}
+            }
+
+            start_at += 1;
+            skip_ws(bytes, &mut start_at);
+
+            if start_at < bytes.len() && bytes[start_at] == b'[' {
+                start_at += 1;
+            } else {
+                break
+            }
+        }
+    }
+
+    (stones, to_move)
+}
+
 impl<'a> Sgf<'a> {
     pub fn new(content: &'a [u8], komi: f32) -> Sgf {
-        Sgf {
-            content: content,
-            board: vec! [Board::new(komi)],
-            index: 0
+        let board = try_get_board_size(content).and_then(|size| {
+            let mut board = Board::new(size, komi);
+            let (stones, to_move) = get_setup_stones(content);
+            let is_handicap = !stones.is_empty() && stones.iter().all(|&(color, _, _, _)| color == Color::Black);
+
+            if let Some(&(_, _, _, offset)) = stones.iter().find(|&&(_, x, y, _)| x >= size || y >= size) {
+                return Err(SgfError::new(SgfErrorKind::InvalidValue, offset));
+            }
+
+            for &(color, x, y, _) in &stones {
+                board.setup(color, x, y);
+            }
+
+            // if only black stones has been setup then this is a handicap
+            // game, and white plays first
+            match to_move {
+                Some(to_move) => board.set_to_move(to_move),
+                None if is_handicap => board.set_to_move(Color::White),
+                None => {}
+            }
+
+            Ok(board)
+        });
+
+        match board {
+            Ok(board) => Sgf { content: content, board: vec! [board], error: None, index: 0 },
+            Err(err) => Sgf { content: content, board: vec! [], error: Some(err), index: 0 }
         }
     }
+
+    /// Returns the error that prevented the initial board from being
+    /// created, or an unbalanced parenthesis at the given offset.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - the offset of the unexpected character
+    ///
+    fn error_at(&self, offset: usize) -> SgfError {
+        self.error.unwrap_or_else(|| {
+            SgfError::new(SgfErrorKind::UnexpectedCharacter(self.content[offset] as char), offset)
+        })
+    }
 }
 
 impl<'a> Iterator for Sgf<'a> {
@@ -251,7 +389,7 @@ impl<'a> Iterator for Sgf<'a> {
                         in_property = false;
                     } else if !in_property && self.content[i] == b'(' {
                         if self.board.is_empty() {
-                            return Some(Err(SgfError::ParseError));
+                            return Some(Err(self.error_at(i)));
                         }
 
                         let prev_board = self.board.last().unwrap().clone();
@@ -259,7 +397,7 @@ impl<'a> Iterator for Sgf<'a> {
                         self.board.push(prev_board);
                     } else if !in_property && self.content[i] == b')' {
                         if self.board.is_empty() {
-                            return Some(Err(SgfError::ParseError));
+                            return Some(Err(self.error_at(i)));
                         }
 
                         self.board.pop();
@@ -268,14 +406,18 @@ impl<'a> Iterator for Sgf<'a> {
             }
 
             // if we have a valid, or pass, move then advance the board state
-            let mut board = self.board.last_mut().unwrap();
+            if self.board.is_empty() {
+                return Some(Err(self.error_at(m.begin)));
+            }
+
+            let board = self.board.last_mut().unwrap();
             let prev_board = board.clone();
 
             if m.x < 19 && m.y < 19 {
                 if board.is_valid(m.color, m.x, m.y) {
                     board.place(m.color, m.x, m.y);
                 } else {
-                    return Some(Err(SgfError::IllegalMove));
+                    return Some(Err(SgfError::new(SgfErrorKind::IllegalMove, m.begin)));
                 }
             }
 
@@ -381,6 +523,67 @@ mod tests {
         assert_eq!(moves[9].color, Color::White);
     }
 
+    #[test]
+    fn small_sgf() {
+        let moves = Sgf::new(b"(;GM[1]SZ[9];B[ee];W[ii];B[tt])", 7.5)
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(moves.len(), 3);
+        assert_eq!(moves[0].board.size(), 9);
+        assert_eq!(moves[2].board.at(8, 8), Some(Color::White));
+        assert_eq!(moves[2].x, 19);
+    }
+
+    #[test]
+    fn small_sgf_outside_board() {
+        let mut moves = Sgf::new(b"(;GM[1]SZ[9];B[ee];W[jj])", 7.5);
+
+        assert!(moves.next().unwrap().is_ok());
+        assert_eq!(moves.next().unwrap().err(), Some(SgfError::new(SgfErrorKind::IllegalMove, 18)));
+    }
+
+    #[test]
+    fn handicap_sgf() {
+        let moves = Sgf::new(b"(;GM[1]HA[2]AB[dp] [pd]C[a \\] comment];W[dd];B[pp])", 0.5)
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0].board.count(), 0);
+        assert_eq!(moves[0].board.at(3, 15), Some(Color::Black));
+        assert_eq!(moves[0].board.at(15, 3), Some(Color::Black));
+        assert_eq!(moves[0].board.to_move(), Color::White);
+        assert_eq!(moves[0].color, Color::White);
+        assert_eq!(moves[1].board.to_move(), Color::Black);
+        assert_eq!(moves[1].board.at(3, 3), Some(Color::White));
+    }
+
+    #[test]
+    fn setup_sgf() {
+        let moves = Sgf::new(b"(;GM[1]AB[aa]AW[ca][ab]PL[B];B[pp])", 0.5)
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].board.at(0, 0), Some(Color::Black));
+        assert_eq!(moves[0].board.at(2, 0), Some(Color::White));
+        assert_eq!(moves[0].board.at(0, 1), Some(Color::White));
+        assert_eq!(moves[0].board.to_move(), Color::Black);
+    }
+
+    #[test]
+    fn unsupported_size_sgf() {
+        assert_eq!(get_board_size(b"(;GM[1]SZ[13];B[dd])"), Some(13));
+        assert_eq!(get_board_size(b"(;GM[1];B[dd])"), Some(19));
+        assert_eq!(get_board_size(b"(;GM[1]SZ[0];B[dd])"), Some(19));
+        assert_eq!(get_board_size(b"(;GM[1]SZ[25];B[dd])"), None);
+        assert_eq!(
+            Sgf::new(b"(;GM[1]SZ[25];B[dd])", 7.5).next().unwrap().err(),
+            Some(SgfError::new(SgfErrorKind::UnsupportedBoardSize, 7))
+        );
+    }
+
     #[bench]
     fn bench_sgf(b: &mut Bencher) {
         let sgf = black_box(r#"
@@ -0,0 +1,582 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ::{Board, Color, DEFAULT_SIZE, MAX_SIZE};
+use super::{CGoban, SgfCoordinate};
+use super::error::{SgfError, SgfErrorKind};
+
+/// A single property of a node, such as `AB[dd][pp]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Property {
+    /// The identifier of this property, with any lower-case letters (as
+    /// allowed by FF[3]) removed.
+    pub ident: String,
+
+    /// The (unescaped) values of this property.
+    pub values: Vec<String>,
+
+    /// The byte offset of the identifier of this property.
+    pub offset: usize
+}
+
+/// A node in a game tree, and the indices of its parent and children.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub properties: Vec<Property>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+
+    /// The byte offset of the `;` that starts this node.
+    pub offset: usize
+}
+
+impl Node {
+    /// Returns the property with the given identifier, if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `ident` - the identifier of the property
+    ///
+    pub fn get(&self, ident: &str) -> Option<&Property> {
+        self.properties.iter().find(|p| p.ident == ident)
+    }
+
+    /// Returns the first value of the property with the given identifier, if
+    /// it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `ident` - the identifier of the property
+    ///
+    pub fn value(&self, ident: &str) -> Option<&str> {
+        self.get(ident)
+            .and_then(|p| p.values.first())
+            .map(|v| v.as_str())
+    }
+}
+
+/// A game tree as described by the SGF FF[4] specification. The nodes are
+/// stored in a flat list, with the root node at index `0`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameTree {
+    nodes: Vec<Node>
+}
+
+fn skip_ws(bytes: &[u8], pos: &mut usize) {
+    while *pos < bytes.len() && (bytes[*pos] as char).is_ascii_whitespace() {
+        *pos += 1;
+    }
+}
+
+/// Returns the value that starts at the given `[`, and advance `pos` to the
+/// first byte after the closing `]`. Any escaped characters are unescaped,
+/// and soft line breaks are removed.
+///
+/// # Arguments
+///
+/// * `bytes` - the content of the SGF file
+/// * `pos` - the offset of the `[`
+///
+fn parse_value(bytes: &[u8], pos: &mut usize) -> Result<String, SgfError> {
+    let mut value = vec! [];
+
+    debug_assert_eq!(bytes[*pos], b'[');
+    *pos += 1;
+
+    loop {
+        match bytes.get(*pos) {
+            None => return Err(SgfError::new(SgfErrorKind::UnexpectedEndOfFile, bytes.len())),
+            Some(&b']') => break,
+            Some(&b'\\') => {
+                *pos += 1;
+
+                match bytes.get(*pos) {
+                    None => return Err(SgfError::new(SgfErrorKind::UnexpectedEndOfFile, bytes.len())),
+                    Some(&b'\n') => {
+                        if bytes.get(*pos + 1) == Some(&b'\r') { *pos += 1; }
+                    },
+                    Some(&b'\r') => {
+                        if bytes.get(*pos + 1) == Some(&b'\n') { *pos += 1; }
+                    },
+                    Some(&ch) => value.push(ch)
+                }
+            },
+            Some(&ch) => value.push(ch)
+        }
+
+        *pos += 1;
+    }
+
+    *pos += 1;
+
+    Ok(String::from_utf8_lossy(&value).into_owned())
+}
+
+/// Returns all properties of the node that starts at `pos`, and advance
+/// `pos` to the first byte that is not part of a property.
+///
+/// # Arguments
+///
+/// * `bytes` - the content of the SGF file
+/// * `pos` - the offset of the first byte after the `;`
+///
+fn parse_properties(bytes: &[u8], pos: &mut usize) -> Result<Vec<Property>, SgfError> {
+    let mut properties = vec! [];
+
+    loop {
+        skip_ws(bytes, pos);
+
+        let offset = *pos;
+        let mut ident = String::new();
+
+        while *pos < bytes.len() && (bytes[*pos] as char).is_ascii_alphabetic() {
+            if (bytes[*pos] as char).is_ascii_uppercase() {
+                ident.push(bytes[*pos] as char);
+            }
+
+            *pos += 1;
+        }
+
+        if *pos == offset {
+            break
+        } else if ident.is_empty() {
+            return Err(SgfError::new(SgfErrorKind::UnexpectedCharacter(bytes[offset] as char), offset));
+        }
+
+        let mut values = vec! [];
+
+        loop {
+            skip_ws(bytes, pos);
+
+            match bytes.get(*pos) {
+                None => return Err(SgfError::new(SgfErrorKind::UnexpectedEndOfFile, bytes.len())),
+                Some(&b'[') => values.push(parse_value(bytes, pos)?),
+                Some(&ch) if values.is_empty() => {
+                    return Err(SgfError::new(SgfErrorKind::UnexpectedCharacter(ch as char), *pos));
+                },
+                _ => break
+            }
+        }
+
+        properties.push(Property { ident, values, offset });
+    }
+
+    Ok(properties)
+}
+
+/// Returns the list of points in the given value, which is either a single
+/// point or a compressed rectangle of points such as `aa:cc`. Passes are
+/// not allowed.
+///
+/// # Arguments
+///
+/// * `value` - the value to parse
+/// * `size` - the width and height of the board
+///
+fn parse_point_list(value: &str, size: usize) -> Option<Vec<(usize, usize)>> {
+    let parse_point = |s: &str| {
+        match CGoban::parse(s) {
+            Ok((x, y)) if x < size && y < size => Some((x, y)),
+            _ => None
+        }
+    };
+
+    let mut parts = value.splitn(2, ':');
+    let (x1, y1) = parse_point(parts.next()?)?;
+    let (x2, y2) = match parts.next() {
+        Some(other) => parse_point(other)?,
+        None => (x1, y1)
+    };
+
+    if x1 > x2 || y1 > y2 {
+        return None;
+    }
+
+    Some((y1..=y2).flat_map(|y| (x1..=x2).map(move |x| (x, y))).collect())
+}
+
+impl GameTree {
+    /// Returns all game trees in the given SGF file. Anything before the
+    /// first `(` of each game tree is ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - the content of the SGF file
+    ///
+    pub fn parse(bytes: &[u8]) -> Result<Vec<GameTree>, SgfError> {
+        let mut trees = vec! [];
+        let mut pos = 0;
+
+        while pos < bytes.len() {
+            if bytes[pos] == b'(' {
+                trees.push(GameTree::parse_one(bytes, &mut pos)?);
+            } else {
+                pos += 1;
+            }
+        }
+
+        if trees.is_empty() {
+            Err(SgfError::new(SgfErrorKind::UnexpectedEndOfFile, bytes.len()))
+        } else {
+            Ok(trees)
+        }
+    }
+
+    /// Returns the game tree that starts at the `(` at the given offset, and
+    /// advance `pos` to the first byte after its closing `)`. Variations are
+    /// tracked using an explicit stack to avoid recursing on deeply nested
+    /// files.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - the content of the SGF file
+    /// * `pos` - the offset of the `(`
+    ///
+    fn parse_one(bytes: &[u8], pos: &mut usize) -> Result<GameTree, SgfError> {
+        let mut nodes: Vec<Node> = vec! [];
+        let mut stack = vec! [];
+        let mut current = None;
+        let mut expect_node = false;
+        let mut after_variation = false;
+
+        loop {
+            skip_ws(bytes, pos);
+
+            let ch = match bytes.get(*pos) {
+                None => return Err(SgfError::new(SgfErrorKind::UnexpectedEndOfFile, bytes.len())),
+                Some(&ch) => ch
+            };
+
+            match ch {
+                b'(' if !expect_node => {
+                    stack.push(current);
+                    expect_node = true;
+                    after_variation = false;
+                    *pos += 1;
+                },
+                b')' if !expect_node => {
+                    current = stack.pop().unwrap();
+                    after_variation = true;
+                    *pos += 1;
+
+                    if stack.is_empty() {
+                        return Ok(GameTree { nodes });
+                    }
+                },
+                b';' if !after_variation => {
+                    let offset = *pos;
+                    let index = nodes.len();
+
+                    *pos += 1;
+                    nodes.push(Node {
+                        properties: parse_properties(bytes, pos)?,
+                        parent: current,
+                        children: vec! [],
+                        offset: offset
+                    });
+
+                    if let Some(parent) = current {
+                        nodes[parent].children.push(index);
+                    }
+
+                    current = Some(index);
+                    expect_node = false;
+                },
+                _ => {
+                    return Err(SgfError::new(SgfErrorKind::UnexpectedCharacter(ch as char), *pos));
+                }
+            }
+        }
+    }
+
+    /// Returns the root node of this game tree.
+    pub fn root(&self) -> &Node {
+        &self.nodes[0]
+    }
+
+    /// Returns the node with the given index.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the index of the node
+    ///
+    pub fn node(&self, index: usize) -> &Node {
+        &self.nodes[index]
+    }
+
+    /// Returns all nodes in this game tree.
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Returns the indices of the nodes in the main line of this game tree,
+    /// which is the line that always follows the first variation.
+    pub fn main_line(&self) -> Vec<usize> {
+        let mut out = vec! [0];
+
+        while let Some(&next) = self.nodes[*out.last().unwrap()].children.first() {
+            out.push(next);
+        }
+
+        out
+    }
+
+    /// Returns the width and height of the board as given by the `SZ[...]`
+    /// property of the root node, or `DEFAULT_SIZE` if it is not given.
+    pub fn board_size(&self) -> Result<usize, SgfError> {
+        match self.root().get("SZ") {
+            None => Ok(DEFAULT_SIZE),
+            Some(prop) => {
+                prop.values.first()
+                    .and_then(|v| v.trim().parse::<usize>().ok())
+                    .map(|size| if size == 0 { DEFAULT_SIZE } else { size })
+                    .filter(|&size| size <= MAX_SIZE)
+                    .ok_or_else(|| SgfError::new(SgfErrorKind::UnsupportedBoardSize, prop.offset))
+            }
+        }
+    }
+
+    /// Returns the komi as given by the `KM[...]` property of the root node.
+    pub fn komi(&self) -> Option<f32> {
+        self.root().value("KM").and_then(|v| v.trim().parse::<f32>().ok())
+    }
+
+    /// Returns the number of handicap stones as given by the `HA[...]`
+    /// property of the root node.
+    pub fn handicap(&self) -> Option<usize> {
+        self.root().value("HA").and_then(|v| v.trim().parse::<usize>().ok())
+    }
+
+    /// Returns the rules as given by the `RU[...]` property of the root node.
+    pub fn rules(&self) -> Option<&str> {
+        self.root().value("RU")
+    }
+
+    /// Returns the board position after each of the given nodes, which must
+    /// form a path starting at the root (such as the one returned by
+    /// `main_line`).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the indices of the nodes to replay
+    /// * `komi` - the komi to use for the boards
+    ///
+    pub fn replay(&self, path: &[usize], komi: f32) -> Result<Vec<Board>, SgfError> {
+        let size = self.board_size()?;
+        let mut board = Board::new(size, komi);
+        let mut out = Vec::with_capacity(path.len());
+
+        for &index in path {
+            let node = &self.nodes[index];
+
+            board = setup_node(board, node, size, komi)?;
+
+            for prop in &node.properties {
+                let color = match prop.ident.as_str() {
+                    "B" => Color::Black,
+                    "W" => Color::White,
+                    _ => continue
+                };
+                let value = prop.values.first().map(|v| v.as_str()).unwrap_or("");
+
+                match CGoban::parse(value) {
+                    Ok((x, y)) if x >= 19 || y >= 19 => {
+                        board.set_to_move(color.opposite());  // pass
+                    },
+                    Ok((x, y)) if x < size && y < size => {
+                        if !board.is_valid(color, x, y) {
+                            return Err(SgfError::new(SgfErrorKind::IllegalMove, prop.offset));
+                        }
+
+                        board.place(color, x, y);
+                    },
+                    _ => {
+                        return Err(SgfError::new(SgfErrorKind::InvalidValue, prop.offset));
+                    }
+                }
+            }
+
+            out.push(board.clone());
+        }
+
+        Ok(out)
+    }
+}
+
+/// Returns the given board after the setup properties (`AB`, `AW`, `AE`,
+/// and `PL`) of the given node has been applied. If any stones are removed,
+/// or replaced, then the board is re-created from scratch.
+///
+/// # Arguments
+///
+/// * `board` - the board to apply the setup properties to
+/// * `node` - the node to apply
+/// * `size` - the width and height of the board
+/// * `komi` - the komi to use for the board
+///
+fn setup_node(mut board: Board, node: &Node, size: usize, komi: f32) -> Result<Board, SgfError> {
+    let mut stones = vec! [];
+    let mut empty = vec! [];
+    let mut to_move = None;
+
+    for prop in &node.properties {
+        let color = match prop.ident.as_str() {
+            "AB" => Some(Color::Black),
+            "AW" => Some(Color::White),
+            "AE" => None,
+            "PL" => {
+                let value = prop.values.first().map(|v| v.trim()).unwrap_or("");
+
+                to_move = Some(value.parse::<Color>()
+                    .map_err(|_| SgfError::new(SgfErrorKind::InvalidValue, prop.offset))?);
+                continue
+            },
+            _ => continue
+        };
+
+        for value in &prop.values {
+            let points = parse_point_list(value, size)
+                .ok_or_else(|| SgfError::new(SgfErrorKind::InvalidValue, prop.offset))?;
+
+            match color {
+                Some(color) => stones.extend(points.into_iter().map(|(x, y)| (color, x, y))),
+                None => empty.extend(points)
+            }
+        }
+    }
+
+    if !empty.is_empty() || stones.iter().any(|&(_, x, y)| board.at(x, y).is_some()) {
+        let mut other = Board::new(size, komi);
+
+        other.set_to_move(board.to_move());
+        for y in 0..size {
+            for x in 0..size {
+                match board.at(x, y) {
+                    Some(color) if !empty.contains(&(x, y)) && !stones.iter().any(|&(_, sx, sy)| (sx, sy) == (x, y)) => {
+                        other.setup(color, x, y);
+                    },
+                    _ => {}
+                }
+            }
+        }
+
+        board = other;
+    }
+
+    for &(color, x, y) in &stones {
+        board.setup(color, x, y);
+    }
+
+    // if only black stones has been setup then this is (probably) a handicap
+    // game, and white plays first
+    match to_move {
+        Some(to_move) => board.set_to_move(to_move),
+        None if !stones.is_empty() && stones.iter().all(|&(c, _, _)| c == Color::Black) => {
+            board.set_to_move(Color::White);
+        },
+        None => {}
+    }
+
+    Ok(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variations() {
+        let trees = GameTree::parse(b"(;GM[1](;B[dd];W[pp])(;B[pd]))").unwrap();
+
+        assert_eq!(trees.len(), 1);
+        assert_eq!(trees[0].nodes().len(), 4);
+        assert_eq!(trees[0].root().children, vec! [1, 3]);
+        assert_eq!(trees[0].node(2).parent, Some(1));
+        assert_eq!(trees[0].node(3).value("B"), Some("pd"));
+        assert_eq!(trees[0].main_line(), vec! [0, 1, 2]);
+    }
+
+    #[test]
+    fn multiple_trees() {
+        let trees = GameTree::parse(b"header (;B[dd]) \n (;W[pp])").unwrap();
+
+        assert_eq!(trees.len(), 2);
+        assert_eq!(trees[1].root().value("W"), Some("pp"));
+    }
+
+    #[test]
+    fn escapes() {
+        let trees = GameTree::parse(b"(;C[a \\] b\\\\ c\\\nd]AddBlack[dd][pp])").unwrap();
+        let root = trees[0].root();
+
+        assert_eq!(root.value("C"), Some("a ] b\\ cd"));
+        assert_eq!(root.get("AB").map(|p| p.values.clone()), Some(vec! ["dd".to_string(), "pp".to_string()]));
+    }
+
+    #[test]
+    fn error_offsets() {
+        assert_eq!(GameTree::parse(b"(;B[dd]"), Err(SgfError::new(SgfErrorKind::UnexpectedEndOfFile, 7)));
+        assert_eq!(GameTree::parse(b"(;B[dd"), Err(SgfError::new(SgfErrorKind::UnexpectedEndOfFile, 6)));
+        assert_eq!(GameTree::parse(b"(;B[dd]1)"), Err(SgfError::new(SgfErrorKind::UnexpectedCharacter('1'), 7)));
+        assert_eq!(GameTree::parse(b"(;B[dd](;W[pp]);B[pd])"), Err(SgfError::new(SgfErrorKind::UnexpectedCharacter(';'), 15)));
+        assert_eq!(GameTree::parse(b"(B[dd])"), Err(SgfError::new(SgfErrorKind::UnexpectedCharacter('B'), 1)));
+
+        let trees = GameTree::parse(b"(;SZ[9];B[ee];W[ee])").unwrap();
+        let main_line = trees[0].main_line();
+
+        assert_eq!(trees[0].replay(&main_line, 7.5).err(), Some(SgfError::new(SgfErrorKind::IllegalMove, 14)));
+
+        let trees = GameTree::parse(b"(;SZ[9];B[jj])").unwrap();
+        let main_line = trees[0].main_line();
+
+        assert_eq!(trees[0].replay(&main_line, 7.5).err(), Some(SgfError::new(SgfErrorKind::InvalidValue, 8)));
+
+        let trees = GameTree::parse(b"(;SZ[25];B[jj])").unwrap();
+
+        assert_eq!(trees[0].board_size(), Err(SgfError::new(SgfErrorKind::UnsupportedBoardSize, 2)));
+    }
+
+    #[test]
+    fn replay() {
+        let trees = GameTree::parse(b"(;SZ[9]AB[aa:bb]AW[ee];W[cc];B[];W[dd]AE[aa])").unwrap();
+        let main_line = trees[0].main_line();
+        let boards = trees[0].replay(&main_line, 0.5).unwrap();
+
+        assert_eq!(boards.len(), 4);
+        assert_eq!(boards[0].at(0, 0), Some(Color::Black));
+        assert_eq!(boards[0].at(1, 1), Some(Color::Black));
+        assert_eq!(boards[0].at(4, 4), Some(Color::White));
+        assert_eq!(boards[0].to_move(), Color::Black);
+        assert_eq!(boards[1].at(2, 2), Some(Color::White));
+        assert_eq!(boards[2].to_move(), Color::White);
+        assert_eq!(boards[3].at(0, 0), None);
+        assert_eq!(boards[3].at(1, 0), Some(Color::Black));
+        assert_eq!(boards[3].at(3, 3), Some(Color::White));
+        assert_eq!(boards[3].to_move(), Color::Black);
+    }
+
+    #[test]
+    fn game_info() {
+        let trees = GameTree::parse(b"(;GM[1]FF[4]SZ[13]KM[6.5]HA[2]RU[Japanese]AB[dd][jj];W[dj])").unwrap();
+        let main_line = trees[0].main_line();
+        let boards = trees[0].replay(&main_line, 0.5).unwrap();
+
+        assert_eq!(trees[0].board_size(), Ok(13));
+        assert_eq!(trees[0].komi(), Some(6.5));
+        assert_eq!(trees[0].handicap(), Some(2));
+        assert_eq!(trees[0].rules(), Some("Japanese"));
+        assert_eq!(boards[0].to_move(), Color::White);
+        assert_eq!(boards[1].at(3, 9), Some(Color::White));
+    }
+}
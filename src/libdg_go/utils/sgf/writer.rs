@@ -0,0 +1,360 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ::{Board, Color};
+use super::SgfCoordinate;
+
+use std::marker::PhantomData;
+
+/// The game information that is written to the root node of an SGF file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameInfo {
+    pub size: usize,
+    pub komi: f32,
+    pub handicap: Option<usize>,
+    pub rules: Option<String>,
+    pub black: Option<String>,
+    pub white: Option<String>,
+    pub result: Option<String>,
+    pub date: Option<String>
+}
+
+impl GameInfo {
+    /// Returns the game information of a game on a board with the given size
+    /// and komi, without any optional properties.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - the width and height of the board
+    /// * `komi` - the komi of the game
+    ///
+    pub fn new(size: usize, komi: f32) -> GameInfo {
+        GameInfo {
+            size: size,
+            komi: komi,
+            handicap: None,
+            rules: None,
+            black: None,
+            white: None,
+            result: None,
+            date: None
+        }
+    }
+}
+
+/// A single move in a game record, and any additional properties (such as
+/// the `P[...]`, `V[...]` and `TR[...]` used by the training pipeline) that
+/// should be written to the same node.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SgfMove {
+    pub color: Color,
+    pub vertex: Option<(usize, usize)>,
+    pub properties: Vec<(&'static str, String)>
+}
+
+impl SgfMove {
+    /// Returns a move without any additional properties.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the player who played the move
+    /// * `vertex` - the coordinates of the move, or `None` for a pass
+    ///
+    pub fn new(color: Color, vertex: Option<(usize, usize)>) -> SgfMove {
+        SgfMove { color, vertex, properties: vec! [] }
+    }
+
+    /// Adds the given property to this move.
+    ///
+    /// # Arguments
+    ///
+    /// * `ident` - the identifier of the property
+    /// * `value` - the (unescaped) value of the property
+    ///
+    pub fn with(mut self, ident: &'static str, value: String) -> SgfMove {
+        self.properties.push((ident, value));
+        self
+    }
+}
+
+/// Returns the given value with all characters that has a special meaning
+/// inside of a property value escaped.
+///
+/// # Arguments
+///
+/// * `value` - the value to escape
+///
+pub fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+
+    for ch in value.chars() {
+        if ch == ']' || ch == '\\' {
+            out.push('\\');
+        }
+
+        out.push(ch);
+    }
+
+    out
+}
+
+/// An incremental writer of SGF files, that takes care of the escaping of
+/// property values and the format of the coordinates.
+pub struct SgfWriter<S: SgfCoordinate> {
+    _coordinate_format: PhantomData<S>,
+    out: String,
+    depth: usize
+}
+
+impl<S: SgfCoordinate> SgfWriter<S> {
+    pub fn new() -> SgfWriter<S> {
+        SgfWriter {
+            _coordinate_format: PhantomData::default(),
+            out: String::new(),
+            depth: 0
+        }
+    }
+
+    /// Starts a new game tree, or variation.
+    pub fn open(&mut self) -> &mut Self {
+        self.out.push('(');
+        self.depth += 1;
+        self
+    }
+
+    /// Ends the current game tree, or variation.
+    pub fn close(&mut self) -> &mut Self {
+        debug_assert!(self.depth > 0);
+
+        self.out.push(')');
+        self.depth -= 1;
+        self
+    }
+
+    /// Starts a new node, all properties that are written afterwards will
+    /// belong to this node.
+    pub fn node(&mut self) -> &mut Self {
+        self.out.push(';');
+        self
+    }
+
+    /// Writes the given property with a single value.
+    ///
+    /// # Arguments
+    ///
+    /// * `ident` - the identifier of the property
+    /// * `value` - the (unescaped) value of the property
+    ///
+    pub fn property(&mut self, ident: &str, value: &str) -> &mut Self {
+        self.out.push_str(ident);
+        self.out.push('[');
+        self.out.push_str(&escape(value));
+        self.out.push(']');
+        self
+    }
+
+    /// Writes the given property with a list of values, nothing is written
+    /// if the list is empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `ident` - the identifier of the property
+    /// * `values` - the (unescaped) values of the property
+    ///
+    pub fn property_list<V: AsRef<str>>(&mut self, ident: &str, values: &[V]) -> &mut Self {
+        if !values.is_empty() {
+            self.out.push_str(ident);
+
+            for value in values {
+                self.out.push('[');
+                self.out.push_str(&escape(value.as_ref()));
+                self.out.push(']');
+            }
+        }
+
+        self
+    }
+
+    /// Writes the game information properties of the root node.
+    ///
+    /// # Arguments
+    ///
+    /// * `info` - the game information
+    ///
+    pub fn game_info(&mut self, info: &GameInfo) -> &mut Self {
+        self.property("GM", "1");
+        self.property("FF", "4");
+        if let Some(ref date) = info.date { self.property("DT", date); }
+        self.property("SZ", &format!("{}", info.size));
+        if let Some(ref rules) = info.rules { self.property("RU", rules); }
+        self.property("KM", &format!("{:.1}", info.komi));
+        if let Some(handicap) = info.handicap { self.property("HA", &format!("{}", handicap)); }
+        if let Some(ref black) = info.black { self.property("PB", black); }
+        if let Some(ref white) = info.white { self.property("PW", white); }
+        if let Some(ref result) = info.result { self.property("RE", result); }
+        self
+    }
+
+    /// Writes the stones of the given board as setup stones (`AB[...]` and
+    /// `AW[...]`), and whose turn it is to play (`PL[...]`).
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board whose stones to write
+    ///
+    pub fn setup(&mut self, board: &Board) -> &mut Self {
+        let size = board.size();
+        let mut black = vec! [];
+        let mut white = vec! [];
+
+        for y in 0..size {
+            for x in 0..size {
+                match board.at(x, y) {
+                    Some(Color::Black) => black.push(S::to_sgf(x, y)),
+                    Some(Color::White) => white.push(S::to_sgf(x, y)),
+                    None => {}
+                }
+            }
+        }
+
+        self.property_list("AB", &black);
+        self.property_list("AW", &white);
+        self.property("PL", &format!("{}", board.to_move()))
+    }
+
+    /// Writes a move (`B[...]` or `W[...]`) to the current node.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the player who played the move
+    /// * `vertex` - the coordinates of the move, or `None` for a pass
+    ///
+    pub fn play(&mut self, color: Color, vertex: Option<(usize, usize)>) -> &mut Self {
+        let ident = if color == Color::Black { "B" } else { "W" };
+
+        match vertex {
+            Some((x, y)) => self.property(ident, &S::to_sgf(x, y)),
+            None => self.property(ident, "")
+        }
+    }
+
+    /// Writes a comment (`C[...]`) to the current node.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - the (unescaped) comment
+    ///
+    pub fn comment(&mut self, text: &str) -> &mut Self {
+        self.property("C", text)
+    }
+
+    /// Writes a new node that contains the given move and its additional
+    /// properties.
+    ///
+    /// # Arguments
+    ///
+    /// * `mv` - the move to write
+    ///
+    pub fn write_move(&mut self, mv: &SgfMove) -> &mut Self {
+        self.node();
+        self.play(mv.color, mv.vertex);
+
+        for &(ident, ref value) in &mv.properties {
+            self.property(ident, value);
+        }
+
+        self
+    }
+
+    /// Returns the written SGF content, after closing any game trees or
+    /// variations that are still open.
+    pub fn finish(mut self) -> String {
+        while self.depth > 0 {
+            self.close();
+        }
+
+        self.out
+    }
+}
+
+/// Returns a complete SGF file for a game with the given game information
+/// and moves, starting from an empty board.
+///
+/// # Arguments
+///
+/// * `info` - the game information
+/// * `moves` - the moves of the game
+///
+pub fn write_game<S: SgfCoordinate>(info: &GameInfo, moves: &[SgfMove]) -> String {
+    let mut writer = SgfWriter::<S>::new();
+
+    writer.open().node().game_info(info);
+    for mv in moves {
+        writer.write_move(mv);
+    }
+
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{CGoban, GameTree};
+
+    #[test]
+    fn escape_values() {
+        assert_eq!(escape("a ] b \\ c"), "a \\] b \\\\ c");
+
+        let mut writer = SgfWriter::<CGoban>::new();
+        writer.open().node().comment("see [this] \\o/");
+
+        let sgf = writer.finish();
+        let trees = GameTree::parse(sgf.as_bytes()).unwrap();
+
+        assert_eq!(sgf, "(;C[see [this\\] \\\\o/])");
+        assert_eq!(trees[0].root().value("C"), Some("see [this] \\o/"));
+    }
+
+    #[test]
+    fn game() {
+        let mut info = GameInfo::new(9, 7.5);
+        info.rules = Some("Chinese".to_string());
+        info.black = Some("dream_go".to_string());
+        info.result = Some("B+Resign".to_string());
+
+        let moves = vec! [
+            SgfMove::new(Color::Black, Some((4, 4))).with("V", "0.5".to_string()),
+            SgfMove::new(Color::White, None)
+        ];
+
+        assert_eq!(
+            write_game::<CGoban>(&info, &moves),
+            "(;GM[1]FF[4]SZ[9]RU[Chinese]KM[7.5]PB[dream_go]RE[B+Resign];B[ee]V[0.5];W[])"
+        );
+    }
+
+    #[test]
+    fn setup() {
+        let mut board = Board::new(9, 0.5);
+        board.setup(Color::Black, 0, 0);
+        board.setup(Color::Black, 2, 0);
+        board.setup(Color::White, 1, 1);
+        board.set_to_move(Color::White);
+
+        let mut writer = SgfWriter::<CGoban>::new();
+        writer.open().node().setup(&board);
+
+        assert_eq!(writer.finish(), "(;AB[aa][ca]AW[bb]PL[W])");
+    }
+}
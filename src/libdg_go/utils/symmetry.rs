@@ -17,19 +17,30 @@ use std::mem;
 
 use board_fast::{Vertex};
 use board::Board;
+use ::MAX_SIZE;
 
-fn get_transformation<F, G>(ax: F, ay: G) -> Box<[u16]>
+fn get_transformation<F, G>(size: usize, ax: F, ay: G) -> Box<[u16]>
     where F: Fn(i32, i32) -> i32, G: Fn(i32, i32) -> i32
 {
+    // the coordinates are doubled before the transformation so that the
+    // center of the board is always at an integer coordinate, even for
+    // boards with an even size.
+    let n = size as i32 - 1;
+
     (0..361)
         .map(|i| {
             let x = (i % 19) as i32;
             let y = (i / 19) as i32;
-            let tx = ax(x - 9, y - 9) + 9;
-            let ty = ay(x - 9, y - 9) + 9;
 
-            assert!(tx >= 0 && tx < 19, "tx {} -> {}", x, tx);
-            assert!(ty >= 0 && ty < 19, "ty {} -> {}", y, ty);
+            if x > n || y > n {
+                return i as u16;  // outside of the board
+            }
+
+            let tx = (ax(2 * x - n, 2 * y - n) + n) / 2;
+            let ty = (ay(2 * x - n, 2 * y - n) + n) / 2;
+
+            assert!(tx >= 0 && tx <= n, "tx {} -> {}", x, tx);
+            assert!(ty >= 0 && ty <= n, "ty {} -> {}", y, ty);
 
             (19 * ty + tx) as u16
         })
@@ -37,30 +48,47 @@ fn get_transformation<F, G>(ax: F, ay: G) -> Box<[u16]>
         .into_boxed_slice()
 }
 
-lazy_static! {
-    /// Identity transformation.
-    static ref _IDENTITY: Box<[u16]> = get_transformation(|x,_| x, |_,y| y);
+/// Returns the transformations for a board of the given size, in the same
+/// order as they are declared in `Transform`.
+///
+/// # Arguments
+///
+/// * `size` - the width and height of the board
+///
+fn get_transformations(size: usize) -> Vec<Box<[u16]>> {
+    vec! [
+        // Identity transformation.
+        get_transformation(size, |x,_| x, |_,y| y),
 
-    /// Flip the matrix across the horizontal axis.
-    static ref _FLIP_LR: Box<[u16]> = get_transformation(|x,_| -x, |_,y| y);
+        // Flip the matrix across the horizontal axis.
+        get_transformation(size, |x,_| -x, |_,y| y),
 
-    /// Flip the matrix across the vertical axis.
-    static ref _FLIP_UD: Box<[u16]> = get_transformation(|x,_| x, |_,y| -y);
+        // Flip the matrix across the vertical axis.
+        get_transformation(size, |x,_| x, |_,y| -y),
 
-    /// Flip the matrix across the main-diagonal.
-    static ref _TRANSPOSE_MAIN: Box<[u16]> = get_transformation(|_,y| y, |x,_| x);
+        // Flip the matrix across the main-diagonal.
+        get_transformation(size, |_,y| y, |x,_| x),
 
-    /// Flip the matrix across the anti-diagonal.
-    static ref _TRANSPOSE_ANTI: Box<[u16]> = get_transformation(|_,y| -y, |x,_| -x);
+        // Flip the matrix across the anti-diagonal.
+        get_transformation(size, |_,y| -y, |x,_| -x),
 
-    /// Rotate the matrix 90 degrees clock-wise.
-    static ref _ROT_90: Box<[u16]> = get_transformation(|_,y| y, |x,_| -x);
+        // Rotate the matrix 90 degrees clock-wise.
+        get_transformation(size, |_,y| y, |x,_| -x),
 
-    /// Rotate the matrix 180 degrees clock-wise.
-    static ref _ROT_180: Box<[u16]> = get_transformation(|x,_| -x, |_,y| -y);
+        // Rotate the matrix 180 degrees clock-wise.
+        get_transformation(size, |x,_| -x, |_,y| -y),
 
-    /// Rotate the matrix 270 degrees clock-wise.
-    static ref _ROT_270: Box<[u16]> = get_transformation(|_,y| -y, |x,_| x);
+        // Rotate the matrix 270 degrees clock-wise.
+        get_transformation(size, |_,y| -y, |x,_| x),
+    ]
+}
+
+lazy_static! {
+    /// The transformations for every supported board size, where the
+    /// transformations for a board of size `n` is stored at index `n - 1`.
+    static ref TABLES: Vec<Vec<Box<[u16]>>> = (1..=MAX_SIZE)
+        .map(|size| get_transformations(size))
+        .collect();
 }
 
 /// Available transformations that are part of the go boards symmetry group.
@@ -90,32 +118,28 @@ impl Transform {
         }
     }
 
-    pub fn apply(self, index: usize) -> usize {
-        let dest = match self {
-            Transform::Identity => _IDENTITY[index],
-            Transform::FlipLR => _FLIP_LR[index],
-            Transform::FlipUD => _FLIP_UD[index],
-            Transform::Transpose => _TRANSPOSE_MAIN[index],
-            Transform::TransposeAnti => _TRANSPOSE_ANTI[index],
-            Transform::Rot90 => _ROT_90[index],
-            Transform::Rot180 => _ROT_180[index],
-            Transform::Rot270 => _ROT_270[index],
-        };
-
-        dest as usize
+    /// Returns where the given vertex ends up after this transformation
+    /// has been applied to a board of the given size.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - the width and height of the board
+    /// * `index` - the vertex to transform
+    ///
+    pub fn apply(self, size: usize, index: usize) -> usize {
+        self.get_table(size)[index] as usize
     }
 
-    pub fn get_table(self) -> &'static [u16] {
-        match self {
-            Transform::Identity => &_IDENTITY,
-            Transform::FlipLR => &_FLIP_LR,
-            Transform::FlipUD => &_FLIP_UD,
-            Transform::Transpose => &_TRANSPOSE_MAIN,
-            Transform::TransposeAnti => &_TRANSPOSE_ANTI,
-            Transform::Rot90 => &_ROT_90,
-            Transform::Rot180 => &_ROT_180,
-            Transform::Rot270 => &_ROT_270
-        }
+    /// Returns the lookup table for this transformation, for a board of the
+    /// given size. Any vertices that are outside of the board are mapped
+    /// to themselves.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - the width and height of the board
+    ///
+    pub fn get_table(self, size: usize) -> &'static [u16] {
+        &TABLES[size - 1][self as usize]
     }
 }
 
@@ -145,9 +169,10 @@ fn reorder<T: Copy>(src: &[T], dst: &mut [T], mapping: &[u16]) {
 /// # Arguments
 ///
 /// * `values` -
+/// * `size` - the width and height of the board
 /// * `transform` -
 ///
-pub fn apply<T: Copy>(values: &mut [T], transform: Transform) {
+pub fn apply<T: Copy>(values: &mut [T], size: usize, transform: Transform) {
     thread_local! {
         static WORKSPACE: RefCell<[i32; 361]> = RefCell::new([0; 361]);
     }
@@ -157,7 +182,7 @@ pub fn apply<T: Copy>(values: &mut [T], transform: Transform) {
     WORKSPACE.with(|workspace| { unsafe {
         let mut workspace = workspace.borrow_mut();
         let workspace = &mut *(&mut *workspace as *mut [i32] as *mut [T]);
-        let lookup: &[u16] = transform.get_table();
+        let lookup: &[u16] = transform.get_table(size);
         let n = values.len() / 361;
 
         for i in 0..n {
@@ -183,7 +208,7 @@ pub fn apply<T: Copy>(values: &mut [T], transform: Transform) {
 /// * `transform` -
 ///
 pub fn is_symmetric(board: &Board, transform: Transform) -> bool {
-    let lookup: &[u16] = transform.get_table();
+    let lookup: &[u16] = transform.get_table(board.size());
 
     (0..361).all(|i| {
         let j = lookup[i] as usize;
@@ -211,7 +236,7 @@ mod tests {
     #[test]
     pub fn identity() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        apply(&mut seq, Transform::Identity);
+        apply(&mut seq, 19, Transform::Identity);
 
         test_uniq(&seq);
     }
@@ -219,7 +244,7 @@ mod tests {
     #[test]
     pub fn flip_lr() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        apply(&mut seq, Transform::FlipLR);
+        apply(&mut seq, 19, Transform::FlipLR);
 
         test_uniq(&seq);
     }
@@ -227,7 +252,7 @@ mod tests {
     #[test]
     pub fn flip_ud() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        apply(&mut seq, Transform::FlipUD);
+        apply(&mut seq, 19, Transform::FlipUD);
 
         test_uniq(&seq);
     }
@@ -235,7 +260,7 @@ mod tests {
     #[test]
     pub fn transpose() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        apply(&mut seq, Transform::Transpose);
+        apply(&mut seq, 19, Transform::Transpose);
 
         test_uniq(&seq);
     }
@@ -243,7 +268,7 @@ mod tests {
     #[test]
     pub fn transpose_anti() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        apply(&mut seq, Transform::TransposeAnti);
+        apply(&mut seq, 19, Transform::TransposeAnti);
 
         test_uniq(&seq);
     }
@@ -251,7 +276,7 @@ mod tests {
     #[test]
     pub fn rot90() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        apply(&mut seq, Transform::Rot90);
+        apply(&mut seq, 19, Transform::Rot90);
 
         test_uniq(&seq);
     }
@@ -259,7 +284,7 @@ mod tests {
     #[test]
     pub fn rot180() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        apply(&mut seq, Transform::Rot180);
+        apply(&mut seq, 19, Transform::Rot180);
 
         test_uniq(&seq);
     }
@@ -267,8 +292,33 @@ mod tests {
     #[test]
     pub fn rot270() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        apply(&mut seq, Transform::Rot270);
+        apply(&mut seq, 19, Transform::Rot270);
 
         test_uniq(&seq);
     }
+
+    #[test]
+    pub fn small_boards() {
+        for &size in &[9, 10, 13] {
+            let is_on_board = |i: usize| i % 19 < size && i / 19 < size;
+
+            for &t in &ALL {
+                let u = (0..361)
+                    .filter(|&i| is_on_board(i))
+                    .map(|i| t.apply(size, i))
+                    .inspect(|&j| assert!(is_on_board(j)))
+                    .collect::<HashSet<usize>>();
+
+                assert_eq!(u.len(), size * size);
+
+                for i in 0..361 {
+                    if !is_on_board(i) {
+                        assert_eq!(t.apply(size, i), i);
+                    }
+
+                    assert_eq!(t.inverse().apply(size, t.apply(size, i)), i);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,245 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+/// The number of elements in the first chunk of an arena, every chunk after
+/// that is twice as large as the previous one.
+const FIRST_CHUNK_SIZE: usize = 64;
+
+/// The maximum number of chunks in an arena, this is enough to hold every
+/// index that fits in an `u32`.
+const MAX_CHUNKS: usize = 27;
+
+/// Returns the chunk, and the offset within that chunk, of the given slot.
+///
+/// # Arguments
+///
+/// * `slot` - the zero-based slot to look up
+///
+#[inline]
+fn chunk_of(slot: usize) -> (usize, usize) {
+    let t = slot / FIRST_CHUNK_SIZE + 1;
+    let chunk = (8 * mem::size_of::<usize>() - 1) - t.leading_zeros() as usize;
+
+    (chunk, slot - FIRST_CHUNK_SIZE * ((1 << chunk) - 1))
+}
+
+/// Returns the number of elements in the given chunk.
+///
+/// # Arguments
+///
+/// * `chunk` - the chunk to get the size of
+///
+#[inline]
+fn chunk_size(chunk: usize) -> usize {
+    FIRST_CHUNK_SIZE << chunk
+}
+
+/// An append-only allocator that hands out compact `u32` indices to its
+/// elements, which are stored in a small number of exponentially growing
+/// chunks. Elements are never moved once allocated, so pointers to them
+/// stay valid for as long as the arena is alive.
+///
+/// Allocation is lock-free and may happen concurrently from any number of
+/// threads. The elements are never dropped, so `T` should not own any
+/// resources, and the whole arena is freed in bulk when it is dropped.
+pub struct Arena<T> {
+    chunks: [AtomicPtr<T>; MAX_CHUNKS],
+    len: AtomicUsize
+}
+
+impl<T> Drop for Arena<T> {
+    fn drop(&mut self) {
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            let chunk = chunk.load(Ordering::Acquire);
+
+            if !chunk.is_null() {
+                unsafe { Vec::from_raw_parts(chunk, 0, chunk_size(i)) };
+            }
+        }
+    }
+}
+
+impl<T> Arena<T> {
+    /// Returns an empty arena.
+    pub fn new() -> Arena<T> {
+        Arena {
+            // an `AtomicPtr` is represented as a pointer, and a zeroed pointer
+            // is the null pointer.
+            chunks: unsafe { mem::zeroed() },
+            len: AtomicUsize::new(0)
+        }
+    }
+
+    /// Returns the number of elements that has been allocated in this arena.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes used by the elements in this arena.
+    pub fn memory_usage(&self) -> usize {
+        self.len() * mem::size_of::<T>()
+    }
+
+    /// Returns the given chunk, allocating it if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk` - the chunk to return
+    ///
+    fn get_or_alloc_chunk(&self, chunk: usize) -> *mut T {
+        let ptr = self.chunks[chunk].load(Ordering::Acquire);
+
+        if !ptr.is_null() {
+            return ptr;
+        }
+
+        let mut other = Vec::<T>::with_capacity(chunk_size(chunk));
+        let other_ptr = other.as_mut_ptr();
+
+        match self.chunks[chunk].compare_exchange(ptr::null_mut(), other_ptr, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => {
+                mem::forget(other);
+                other_ptr
+            },
+            Err(existing) => existing  // someone else allocated it first
+        }
+    }
+
+    /// Adds the given value to this arena and returns its index, which is
+    /// never zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value to add
+    ///
+    pub fn alloc(&self, value: T) -> u32 {
+        let slot = self.len.fetch_add(1, Ordering::Relaxed);
+        let (chunk, offset) = chunk_of(slot);
+
+        assert!(chunk < MAX_CHUNKS && slot < ::std::u32::MAX as usize, "arena is full");
+
+        unsafe {
+            ptr::write(self.get_or_alloc_chunk(chunk).add(offset), value);
+        }
+
+        (slot + 1) as u32
+    }
+
+    /// Returns a pointer to the element with the given index, or a null
+    /// pointer if the index is zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the index of the element, as returned by `alloc`
+    ///
+    #[inline]
+    pub fn get(&self, index: u32) -> *mut T {
+        if index == 0 {
+            ptr::null_mut()
+        } else {
+            let (chunk, offset) = chunk_of(index as usize - 1);
+
+            unsafe { self.chunks.get_unchecked(chunk).load(Ordering::Acquire).add(offset) }
+        }
+    }
+}
+
+/// A reference to an element in an `Arena`, which is only valid for as long
+/// as the arena is alive. Dropping it does not drop the element, since the
+/// memory of every element is owned by the arena.
+pub struct ArenaBox<T> {
+    ptr: *mut T
+}
+
+impl<T> ArenaBox<T> {
+    /// Returns a reference to the element with the given index in the given
+    /// arena.
+    ///
+    /// # Arguments
+    ///
+    /// * `arena` - the arena that contains the element
+    /// * `index` - the index of the element
+    ///
+    pub unsafe fn from_index(arena: &Arena<T>, index: u32) -> ArenaBox<T> {
+        debug_assert!(index != 0);
+
+        ArenaBox { ptr: arena.get(index) }
+    }
+}
+
+impl<T> Deref for ArenaBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.ptr }
+    }
+}
+
+impl<T> DerefMut for ArenaBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.ptr }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arena::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn chunks() {
+        assert_eq!(chunk_of(0), (0, 0));
+        assert_eq!(chunk_of(63), (0, 63));
+        assert_eq!(chunk_of(64), (1, 0));
+        assert_eq!(chunk_of(191), (1, 127));
+        assert_eq!(chunk_of(192), (2, 0));
+        assert!(chunk_of(::std::u32::MAX as usize).0 < MAX_CHUNKS);
+    }
+
+    #[test]
+    fn alloc() {
+        let arena = Arena::new();
+        let indices = (0..1000).map(|i| arena.alloc(i)).collect::<Vec<_>>();
+
+        assert_eq!(arena.len(), 1000);
+        assert!(arena.get(0).is_null());
+        assert!(indices.iter().enumerate().all(|(i, &index)| unsafe { *arena.get(index) } == i));
+    }
+
+    #[test]
+    fn alloc_concurrent() {
+        let arena = Arc::new(Arena::new());
+        let handles = (0..4).map(|t| {
+            let arena = arena.clone();
+
+            thread::spawn(move || {
+                (0..1000).map(|i| (arena.alloc(1000 * t + i), 1000 * t + i)).collect::<Vec<_>>()
+            })
+        }).collect::<Vec<_>>();
+
+        for handle in handles.into_iter() {
+            for (index, value) in handle.join().unwrap() {
+                assert_eq!(unsafe { *arena.get(index) }, value);
+            }
+        }
+
+        assert_eq!(arena.len(), 4000);
+    }
+}
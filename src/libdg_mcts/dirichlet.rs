@@ -13,20 +13,8 @@
 // limitations under the License.
 
 use rand::distributions::{Distribution, Gamma};
-use rand::thread_rng;
 
-use dg_utils::config;
-
-/// Add a dirichlet distribution of the given scale to `x`.
-///
-/// # Arguments
-///
-/// * `x` - the vector to add the distribution to
-/// * `scale` - the scale of the distribution
-///
-pub fn add(x: &mut [f32], shape: f32) {
-    add_ex(x, shape, *config::DIRICHLET_NOISE)
-}
+use rng;
 
 /// Add a dirichlet distribution of the given scale to `x`.
 ///
@@ -51,7 +39,7 @@ pub fn add_ex(x: &mut [f32], shape: f32, beta: f32) {
 
         for (i, x_) in x.iter().enumerate() {
             if x_.is_finite() {
-                let g_ = gamma.sample(&mut thread_rng());
+                let g_ = rng::with(|rng| gamma.sample(rng));
 
                 count += 1;
                 g_sum += g_;
@@ -74,13 +62,13 @@ pub fn add_ex(x: &mut [f32], shape: f32, beta: f32) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use dg_utils::config;
 
     #[test]
     fn dirichlet() {
         let mut x = vec! [0.0; 1000];
         let mut s = 0.0;
-        add(&mut x, 0.03);
+        let beta = 0.25;
+        add_ex(&mut x, 0.03, beta);
 
         for &v in x.iter() {
             assert!(v.is_finite());
@@ -89,7 +77,7 @@ mod tests {
             s += v;
         }
 
-        assert!(s >= *config::DIRICHLET_NOISE - 0.01 && s <= *config::DIRICHLET_NOISE + 0.01, "{}", s);
+        assert!(s >= beta - 0.01 && s <= beta + 0.01, "{}", s);
     }
 
     #[test]
@@ -122,6 +122,12 @@ impl<K: Clone + Hash + Eq, V: Clone> LruCache<K, V> {
         }
     }
 
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.head = ptr::null_mut();
+        self.tail = ptr::null_mut();
+    }
+
     fn insert(&mut self, key: &K, value: V) {
         let key_ref = KeyRef { inner: key };
 
@@ -153,40 +159,45 @@ impl<K: Clone + Hash + Eq, V: Clone> LruCache<K, V> {
 
 /* -------- get_or_insert -------- */
 
+lazy_static! {
+    /// The transposition table of all network evaluations.
+    static ref TABLE: Mutex<LruCache<BoardTuple, (f32, Vec<f32>, Option<Vec<f32>>)>> = {
+        Mutex::new(LruCache::with_capacity(MAX_CACHE_SIZE + 1))
+    };
+}
+
 #[derive(Clone, Hash, PartialEq, Eq)]
 struct BoardTuple {
+    network_id: usize,
     board: Board,
     to_move: Color,
     symmetry: symmetry::Transform
 }
 
-/// Retrieve the value and policy from the transposition table, if
-/// the `(board, color)`  tuple is not in the transposition table then
+/// Retrieve the value, policy, and ownership from the transposition table, if
+/// the `(network, board, color)`  tuple is not in the transposition table then
 /// it is computed from the given supplier.
 /// 
 /// # Arguments
 /// 
+/// * `network_id` - the identifier of the network that computed the entry
 /// * `board` - the board to get from the table
 /// * `to_move` - the color to get from the table
 /// * `symmetry` - the symmetry to get from the table
-/// * `supplier` - a function that can be used to compute the value
-///   and policy if they are missing from the table.
+/// * `supplier` - a function that can be used to compute the value,
+///   policy, and ownership if they are missing from the table.
 /// 
 pub fn get_or_insert<F>(
+    network_id: usize,
     board: &Board,
     to_move: Color,
     symmetry: symmetry::Transform,
     supplier: F
-) -> Option<(f32, Vec<f32>)>
-    where F: FnOnce() -> Option<(f32, Vec<f32>)>
+) -> Option<(f32, Vec<f32>, Option<Vec<f32>>)>
+    where F: FnOnce() -> Option<(f32, Vec<f32>, Option<Vec<f32>>)>
 {
-    lazy_static! {
-        static ref TABLE: Mutex<LruCache<BoardTuple, (f32, Vec<f32>)>> = {
-            Mutex::new(LruCache::with_capacity(MAX_CACHE_SIZE + 1))
-        };
-    }
-
     let key = BoardTuple {
+        network_id: network_id,
         board: board.clone(),
         to_move: to_move,
         symmetry: symmetry
@@ -194,24 +205,30 @@ pub fn get_or_insert<F>(
     let existing = {
         let mut table = TABLE.lock().unwrap();
 
-        table.get(&key).map(|&(value, ref policy)| {
-            (value, policy.clone())
+        table.get(&key).map(|&(value, ref policy, ref ownership)| {
+            (value, policy.clone(), ownership.clone())
         })
     };
 
-    if let Some((value, policy)) = existing {
-        Some((value, policy))
-    } else if let Some((value, policy)) = supplier() {
+    if let Some((value, policy, ownership)) = existing {
+        Some((value, policy, ownership))
+    } else if let Some((value, policy, ownership)) = supplier() {
         let mut table = TABLE.lock().unwrap();
 
-        table.insert(&key, (value, policy.clone()));
+        table.insert(&key, (value, policy.clone(), ownership.clone()));
 
-        Some((value, policy))
+        Some((value, policy, ownership))
     } else {
         None
     }
 }
 
+/// Removes all entries from the transposition table, this must be done
+/// whenever the network of a predictor changes.
+pub fn clear() {
+    TABLE.lock().unwrap().clear();
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -242,4 +259,16 @@ mod tests {
             assert!(lru.get(&i).is_none(), "{:?}", lru);
         }
     }
+
+    #[test]
+    fn clear() {
+        let mut lru = LruCache::with_capacity(10);
+
+        for i in 0..10 { lru.insert(&i, i); }
+        lru.clear();
+        for i in 10..30 { lru.insert(&i, i); }
+
+        assert!(lru.get(&0).is_none(), "{:?}", lru);
+        assert!(lru.get(&29).is_some(), "{:?}", lru);
+    }
 }
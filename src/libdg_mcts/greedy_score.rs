@@ -14,7 +14,7 @@
 
 use ordered_float::OrderedFloat;
 
-use dg_go::utils::sgf::{CGoban, SgfCoordinate};
+use dg_go::utils::sgf::SgfMove;
 use dg_go::{Board, Color};
 use super::predict::Predictor;
 use super::{tree, full_forward, ScoringSearch};
@@ -30,17 +30,18 @@ use super::{tree, full_forward, ScoringSearch};
 /// * `board` - the board to score
 /// * `to_move` - the color of the player whose turn it is to play
 /// 
-pub fn greedy_score<P: Predictor>(server: &P, board: &Board, mut to_move: Color) -> (Board, String) {
+pub fn greedy_score<P: Predictor>(server: &P, board: &Board, mut to_move: Color) -> (Board, Vec<SgfMove>) {
     let mut board = board.clone();
-    let mut sgf = String::new();
+    let mut moves = vec! [];
     let mut pass_count = 0;
     let mut count = 0;
+    let max_count = 2 * board.size() * board.size();
 
-    while count < 722 && pass_count < 2 {
+    while count < max_count && pass_count < 2 {
         let policy = if let Some(response) = full_forward::<_, ScoringSearch>(server, &board, to_move) {
             response.1
         } else {
-            return (board, sgf)
+            return (board, moves)
         };
 
         // pick the move with the largest prior value that does not fill an
@@ -52,11 +53,11 @@ pub fn greedy_score<P: Predictor>(server: &P, board: &Board, mut to_move: Color)
         if let Some(index) = index {
             let (x, y) = (tree::X[index] as usize, tree::Y[index] as usize);
 
-            sgf += &format!(";{}[{}]", to_move, CGoban::to_sgf(x, y));
+            moves.push(SgfMove::new(to_move, Some((x, y))));
             pass_count = 0;
             board.place(to_move, x, y);
         } else {  // no valid moves remaining
-            sgf += &format!(";{}[]", to_move);
+            moves.push(SgfMove::new(to_move, None));
             pass_count += 1;
         }
 
@@ -65,5 +66,5 @@ pub fn greedy_score<P: Predictor>(server: &P, board: &Board, mut to_move: Color)
         count += 1;
     }
 
-    (board, sgf)
+    (board, moves)
 }
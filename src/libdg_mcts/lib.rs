@@ -28,15 +28,18 @@ extern crate time;
 
 /* -------- Modules -------- */
 
+mod arena;
 pub mod asm;
 mod dirichlet;
 mod global_cache;
 mod greedy_score;
+mod match_play;
 pub mod options;
 mod parallel;
 mod policy_play;
 pub mod predict;
 pub mod predict_service;
+mod rng;
 mod self_play;
 pub mod tree;
 pub mod time_control;
@@ -44,13 +47,14 @@ pub mod time_control;
 /* -------- Exports -------- */
 
 pub use self::greedy_score::*;
+pub use self::match_play::*;
 pub use self::self_play::*;
 pub use self::policy_play::*;
 
 /* -------- Code -------- */
 
 use rand::prelude::SliceRandom;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use std::cell::UnsafeCell;
 use std::fmt;
 use std::sync::Arc;
@@ -58,33 +62,37 @@ use std::thread::{self, JoinHandle};
 
 use dg_go::utils::features::{HWC, Features};
 use dg_go::utils::score::{Score};
+use dg_go::utils::sgf::{CGoban, GameInfo, SgfMove, write_game};
 use dg_go::utils::symmetry;
 use dg_go::{Board, Color};
 use self::options::{SearchOptions, ScoringSearch};
 use self::time_control::TimeStrategy;
 use self::predict::Predictor;
 use dg_nn::Profiler;
-use dg_utils::config;
+use dg_utils::config::{Config, SearchParams};
 use dg_utils::types::f16;
 use self::asm::sum_finite_f32;
 use self::asm::normalize_finite_f32;
 use self::parallel::global_rwlock;
 
+/// The result of a self-play game, which contains the moves that were played
+/// (and their search statistics) and the final board position.
 pub enum GameResult {
-    Resign(String, Board, Color, f32),
-    Ended(String, Board)
+    Resign(Vec<SgfMove>, Board, Color, f32),
+    Ended(Vec<SgfMove>, Board)
 }
 
-impl fmt::Display for GameResult {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+impl GameResult {
+    /// Returns the game information of this game, such as the komi and the
+    /// result.
+    pub fn game_info(&self) -> GameInfo {
         let now = time::now_utc();
         let iso8601 = time::strftime("%Y-%m-%dT%H:%M:%S%z", &now).unwrap();
-
-        match *self {
-            GameResult::Resign(ref sgf, ref board, winner, _) => {
-                write!(fmt, "(;GM[1]FF[4]DT[{}]SZ[19]RU[Chinese]KM[{:.1}]RE[{}+Resign]{})", iso8601, board.komi(), winner, sgf)
+        let (board, result) = match *self {
+            GameResult::Resign(_, ref board, winner, _) => {
+                (board, format!("{}+Resign", winner))
             },
-            GameResult::Ended(ref sgf, ref board) => {
+            GameResult::Ended(_, ref board) => {
                 let (black, white) = board.get_score();
                 let black = black as f32;
                 let white = white as f32 + board.komi();
@@ -98,14 +106,57 @@ impl fmt::Display for GameResult {
                     }
                 };
 
-                write!(fmt, "(;GM[1]FF[4]DT[{}]SZ[19]RU[Chinese]KM[{:.1}]RE[{}]{})", iso8601, board.komi(), winner, sgf)
+                (board, winner)
             }
+        };
+
+        let mut info = GameInfo::new(board.size(), board.komi());
+        info.date = Some(iso8601);
+        info.rules = Some(board.rules().sgf_name());
+        info.result = Some(result);
+        info
+    }
+
+    /// Returns the color of the winner of this game, or `None` if the game
+    /// was drawn.
+    pub fn winner(&self) -> Option<Color> {
+        match *self {
+            GameResult::Resign(_, _, winner, _) => Some(winner),
+            GameResult::Ended(_, ref board) => {
+                let (black, white) = board.get_score();
+                let black = black as f32;
+                let white = white as f32 + board.komi();
+
+                if black > white {
+                    Some(Color::Black)
+                } else if white > black {
+                    Some(Color::White)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Returns the moves that were played in this game.
+    pub fn moves(&self) -> &[SgfMove] {
+        match *self {
+            GameResult::Resign(ref moves, _, _, _) => moves,
+            GameResult::Ended(ref moves, _) => moves
         }
     }
 }
 
-/// Return the value and policy for the given board position, as the interpolation
-/// of their value for every symmetry.
+impl fmt::Display for GameResult {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "{}", write_game::<CGoban>(&self.game_info(), self.moves()))
+    }
+}
+
+/// Return the value, policy, and ownership for the given board position, as the
+/// interpolation of their value for every symmetry. The ownership is only
+/// available if the network has an ownership head, and is the average of
+/// the symmetries that has an ownership in the cache.
 ///
 /// # Arguments
 ///
@@ -113,19 +164,26 @@ impl fmt::Display for GameResult {
 /// * `board` - the board position to evaluate
 /// * `to_move` - the color to evaluate for
 ///
-fn full_forward<P: Predictor, O: SearchOptions>(server: &P, board: &Board, to_move: Color) -> Option<(f32, Vec<f32>)> {
+fn full_forward<P: Predictor, O: SearchOptions>(server: &P, board: &Board, to_move: Color) -> Option<(f32, Vec<f32>, Option<Vec<f32>>)> {
     let (initial_policy, indices) = create_initial_policy::<O>(board, to_move);
     let mut policy = initial_policy.clone();
     let mut value = 0.0f32;
+    let mut ownership = vec! [0.0f32; 361];
+    let mut ownership_count = 0;
 
     // find out which symmetries has already been calculated, and which ones has not
     let mut new_requests = vec! [];
     let mut new_symmetries = vec! [];
 
     for &t in &symmetry::ALL {
-        if let Some((other_value, other_policy)) = global_cache::get_or_insert(board, to_move, t, || { None }) {
+        if let Some((other_value, other_policy, other_ownership)) = global_cache::get_or_insert(server.network_id(), board, to_move, t, || { None }) {
             for i in 0..362 { policy[i] += other_policy[i]; }
             value += other_value;
+
+            if let Some(other_ownership) = other_ownership {
+                for i in 0..361 { ownership[i] += other_ownership[i]; }
+                ownership_count += 1;
+            }
         } else {
             new_requests.push(board.get_features::<HWC, f16>(to_move, t));
             new_symmetries.push(t);
@@ -137,22 +195,39 @@ fn full_forward<P: Predictor, O: SearchOptions>(server: &P, board: &Board, to_mo
     let new_responses = server.predict_all(new_requests.into_iter());
 
     for (new_response, t) in new_responses.into_iter().zip(new_symmetries.into_iter()) {
-        let (other_value, other_policy) = new_response?;
-        let (other_value, other_policy) = global_cache::get_or_insert(board, to_move, t, || {
+        let (other_value, other_policy, other_ownership) = new_response?;
+        let (other_value, other_policy, other_ownership) = global_cache::get_or_insert(server.network_id(), board, to_move, t, || {
             let mut identity_policy = initial_policy.clone();
-            add_valid_candidates(&mut identity_policy, other_policy, &indices, t);
+            add_valid_candidates(&mut identity_policy, other_policy, &indices, board.size(), t);
             normalize_policy(&mut identity_policy);
 
-            Some((0.5 + 0.5 * other_value, identity_policy))
+            let identity_ownership = other_ownership.map(|other_ownership| {
+                untransform_ownership(&other_ownership, board.size(), t)
+            });
+
+            Some((0.5 + 0.5 * other_value, identity_policy, identity_ownership))
         }).unwrap();
 
         for i in 0..362 { policy[i] += other_policy[i]; }
         value += other_value;
+
+        if let Some(other_ownership) = other_ownership {
+            for i in 0..361 { ownership[i] += other_ownership[i]; }
+            ownership_count += 1;
+        }
     }
 
     normalize_policy(&mut policy);
 
-    Some((value * 0.125, policy))
+    let ownership = if ownership_count > 0 {
+        for i in 0..361 { ownership[i] /= ownership_count as f32; }
+
+        Some(ownership)
+    } else {
+        None
+    };
+
+    Some((value * 0.125, policy, ownership))
 }
 
 /// Performs a forward pass through the neural network for the given board
@@ -165,12 +240,12 @@ fn full_forward<P: Predictor, O: SearchOptions>(server: &P, board: &Board, to_mo
 /// * `to_move` - the current player
 ///
 fn forward<P: Predictor, O: SearchOptions>(server: &P, board: &Board, to_move: Color) -> Option<(f32, Vec<f32>)> {
-    let t = *symmetry::ALL.choose(&mut thread_rng()).unwrap();
+    let t = rng::with(|rng| *symmetry::ALL.choose(rng).unwrap());
 
-    global_cache::get_or_insert(board, to_move, t, || {
+    global_cache::get_or_insert(server.network_id(), board, to_move, t, || {
         // run a forward pass through the network using this transformation
         // and when we are done undo it using the opposite.
-        let (value, original_policy) = server.predict(
+        let (value, original_policy, _ownership) = server.predict(
             board.get_features::<HWC, f16>(
                 to_move,
                 t
@@ -179,11 +254,61 @@ fn forward<P: Predictor, O: SearchOptions>(server: &P, board: &Board, to_move: C
 
         // fix-up the potentially broken policy
         let (mut policy, indices) = create_initial_policy::<O>(board, to_move);
-        add_valid_candidates(&mut policy, original_policy, &indices, t);
+        add_valid_candidates(&mut policy, original_policy, &indices, board.size(), t);
         normalize_policy(&mut policy);
 
-        Some((0.5 + 0.5 * value, policy))
-    })
+        // the ownership is not used during search, so avoid keeping it in
+        // the cache
+        Some((0.5 + 0.5 * value, policy, None))
+    }).map(|(value, policy, _ownership)| (value, policy))
+}
+
+/// Returns the expected ownership of every vertex on the given board position
+/// from the perspective of `to_move`, where `1.0` means that `to_move` owns the
+/// vertex and `-1.0` that the opponent does. Returns `None` if the network does
+/// not have an ownership head.
+///
+/// # Arguments
+///
+/// * `server` - the server to use for predictions
+/// * `board` - the board position to evaluate
+/// * `to_move` - the color to evaluate for
+///
+pub fn get_ownership<P: Predictor>(server: &P, board: &Board, to_move: Color) -> Option<Vec<f32>> {
+    let new_responses = server.predict_all_with_ownership(symmetry::ALL.iter().map(|&t| {
+        board.get_features::<HWC, f16>(to_move, t)
+    }));
+    let mut ownership = vec! [0.0f32; 361];
+
+    for (new_response, &t) in new_responses.into_iter().zip(symmetry::ALL.iter()) {
+        let (_value, _policy, other_ownership) = new_response?;
+        let other_ownership = untransform_ownership(&other_ownership?, board.size(), t);
+
+        for i in 0..361 { ownership[i] += other_ownership[i]; }
+    }
+
+    for i in 0..361 { ownership[i] *= 0.125; }
+
+    Some(ownership)
+}
+
+/// Returns the number of points that `to_move` is expected to win by (using
+/// area scoring), according to the given ownership.
+///
+/// # Arguments
+///
+/// * `board` - the board position the ownership is for
+/// * `to_move` - the color the ownership is from the perspective of
+/// * `ownership` - the ownership, as returned by `get_ownership`
+///
+pub fn get_score_lead(board: &Board, to_move: Color, ownership: &[f32]) -> f32 {
+    let area: f32 = ownership.iter().sum();
+
+    if to_move == Color::Black {
+        area - board.komi()
+    } else {
+        area + board.komi()
+    }
 }
 
 /// Returns a initial accumulator policy where all illegal moves has been set
@@ -218,7 +343,7 @@ fn create_initial_policy<O: SearchOptions>(board: &Board, to_move: Color) -> (Ve
     indices[361] = 361;
 
     for i in 0..361 {
-        if let Some(target) = symmetries.iter().map(|t| t.apply(i)).min() {
+        if let Some(target) = symmetries.iter().map(|t| t.apply(board.size(), i)).min() {
             indices[i] = target;
 
             if i != target {
@@ -240,12 +365,14 @@ fn create_initial_policy<O: SearchOptions>(board: &Board, to_move: Color) -> (Ve
 /// * `dst` -
 /// * `src` -
 /// * `indices` - the symmetry elimination map
+/// * `size` - the width and height of the board
 /// * `t` - the symmetry
 ///
 fn add_valid_candidates(
     dst: &mut Vec<f32>,
     src: Vec<f32>,
     indices: &[usize],
+    size: usize,
     t: symmetry::Transform
 ) {
     // always copy the _passing_ move since it is never an illegal move.
@@ -254,12 +381,33 @@ fn add_valid_candidates(
     // de-transform each index in the source policy, to the identity board position
     // before adding it to the destination.
     for i in 0..361 {
-        let j = indices[t.inverse().apply(i)];
+        let j = indices[t.inverse().apply(size, i)];
 
         dst[j] += src[i];
     }
 }
 
+/// Returns the given ownership after the inverse of the given symmetry has
+/// been applied to it, with every vertex outside of the board set to zero.
+///
+/// # Arguments
+///
+/// * `src` - the ownership of the transformed board position
+/// * `size` - the width and height of the board
+/// * `t` - the symmetry
+///
+fn untransform_ownership(src: &[f32], size: usize, t: symmetry::Transform) -> Vec<f32> {
+    let mut dst = vec! [0.0; 361];
+
+    for i in 0..361 {
+        if i % 19 < size && i / 19 < size {
+            dst[t.inverse().apply(size, i)] = src[i];
+        }
+    }
+
+    dst
+}
+
 /// Normalize the given vector so that its elements sums to `1.0`.
 ///
 /// # Arguments
@@ -291,6 +439,12 @@ struct ThreadContext<T: TimeStrategy + Clone + Send, O: SearchOptions> {
     /// The initial board position at the root the tree.
     starting_point: Board,
 
+    /// The search parameters to use for this search.
+    params: Arc<SearchParams>,
+
+    /// The maximum number of bytes the search tree is allowed to use.
+    max_tree_memory: usize,
+
     /// Time control element
     time_strategy: T,
 }
@@ -298,6 +452,18 @@ struct ThreadContext<T: TimeStrategy + Clone + Send, O: SearchOptions> {
 unsafe impl<T: TimeStrategy + Clone + Send, O: SearchOptions> Send for ThreadContext<T, O> { }
 
 
+/// Returns true if the given search tree is using more memory than it is allowed
+/// to, and it has been visited at least once. A budget of zero is unlimited.
+///
+/// # Arguments
+///
+/// * `root` - the search tree that is being expanded
+/// * `max_memory` - the maximum number of bytes the search tree may use
+///
+fn is_out_of_memory<O: SearchOptions>(root: &tree::Node<O>, max_memory: usize) -> bool {
+    root.size() > 0 && max_memory > 0 && root.live_memory_estimate() >= max_memory
+}
+
 /// Worker that probes into the given monte carlo search tree until the context
 /// is exhausted.
 ///
@@ -314,10 +480,10 @@ fn predict_worker<T, P, O>(context: ThreadContext<T, O>, server: P)
     let root = unsafe { &mut *context.root.get() };
 
     global_rwlock::read_lock();
-    while !time_control::is_done(root, &context.time_strategy) {
+    while !time_control::is_done(root, &context.time_strategy) && !is_out_of_memory(root, context.max_tree_memory) {
         loop {
             let mut board = context.starting_point.clone();
-            let trace = unsafe { tree::probe(root, &mut board) };
+            let trace = unsafe { tree::probe(root, &mut board, &context.params) };
             global_rwlock::read_unlock();
 
             if let Some(trace) = trace {
@@ -329,11 +495,11 @@ fn predict_worker<T, P, O>(context: ThreadContext<T, O>, server: P)
                     global_rwlock::read_lock();
 
                     unsafe {
-                        tree::insert(&trace, to_move, value, policy);
+                        tree::insert(&trace, to_move, value, policy, &context.params);
                         break
                     }
                 } else {
-                    unsafe { tree::undo(trace, true) };
+                    unsafe { tree::undo(trace, true, &context.params) };
 
                     return  // unrecognized error
                 }
@@ -354,6 +520,7 @@ fn predict_worker<T, P, O>(context: ThreadContext<T, O>, server: P)
 /// # Arguments
 ///
 /// * `server` - the server to use during evaluation
+/// * `config` - the configuration to search with
 /// * `num_workers` -
 /// * `starting_tree` -
 /// * `starting_point` -
@@ -362,6 +529,7 @@ fn predict_worker<T, P, O>(context: ThreadContext<T, O>, server: P)
 ///
 fn predict_aux<T, P, O>(
     server: &P,
+    config: &Config,
     num_workers: usize,
     time_strategy: T,
     starting_tree: Option<tree::Node<O>>,
@@ -372,12 +540,17 @@ fn predict_aux<T, P, O>(
           P: Predictor + 'static,
           O: SearchOptions + 'static
 {
-    let (starting_value, mut starting_policy) = full_forward::<P, O>(server, starting_point, starting_color)?;
+    let params = &config.search;
+    let (starting_value, mut starting_policy, _) = full_forward::<P, O>(server, starting_point, starting_color)?;
+
+    // the order in which multiple threads expands the tree is not reproducible,
+    // so only use one if the user asked for reproducible results
+    let num_workers = if config.seed.is_some() { 1 } else { num_workers };
 
     // add some dirichlet noise to the root node of the search tree in order to increase
     // the entropy of the search and avoid overfitting to the prior value
     if !O::deterministic() {
-        dirichlet::add(&mut starting_policy[..362], 0.03);
+        dirichlet::add_ex(&mut starting_policy[..362], 0.03, params.dirichlet_noise);
     }
 
     // if we have a starting tree given, then re-use that tree (after some sanity
@@ -392,6 +565,15 @@ fn predict_aux<T, P, O>(
         // - a pre-expanded pass move, which does not get a prior computed.
         //
         starting_tree.prior[0..362].clone_from_slice(&starting_policy[..362]);
+
+        // if the search tree is getting close to its memory budget, then remove
+        // the least visited parts of it to make room for the coming search.
+        let max_memory = 3 * (config.max_tree_memory_bytes() / 4);
+
+        if max_memory > 0 && starting_tree.live_memory_estimate() >= max_memory {
+            starting_tree.prune(max_memory);
+        }
+
         starting_tree
     } else {
         tree::Node::new(starting_color, starting_value, starting_policy)
@@ -402,6 +584,8 @@ fn predict_aux<T, P, O>(
     let context: ThreadContext<T, O> = ThreadContext {
         root: Arc::new(UnsafeCell::new(starting_tree)),
         starting_point: starting_point.clone(),
+        params: Arc::new(params.clone()),
+        max_tree_memory: config.max_tree_memory_bytes(),
 
         time_strategy: time_strategy.clone()
     };
@@ -431,13 +615,13 @@ fn predict_aux<T, P, O>(
     // choose the best move according to the search tree
     let root = UnsafeCell::into_inner(Arc::try_unwrap(context.root).ok().expect(""));
     let (value, index) = root.best(if !O::deterministic() && starting_point.count() < 8 {
-        *config::TEMPERATURE
+        params.temperature
     } else {
         0.0
-    });
+    }, params);
 
     #[cfg(feature = "trace-mcts")]
-    eprintln!("{}", tree::to_sgf::<dg_go::utils::sgf::CGoban>(&root, starting_point, true));
+    eprintln!("{}", tree::to_sgf::<dg_go::utils::sgf::CGoban, _>(&root, starting_point, params, true));
 
     Some((value, index, root))
 }
@@ -448,6 +632,7 @@ fn predict_aux<T, P, O>(
 /// # Arguments
 ///
 /// * `server` - the server to use during evaluation
+/// * `config` - the configuration to search with
 /// * `num_workers` -
 /// * `starting_tree` -
 /// * `starting_point` -
@@ -456,6 +641,7 @@ fn predict_aux<T, P, O>(
 ///
 pub fn predict<T, P, O>(
     server: &P,
+    config: &Config,
     num_workers: Option<usize>,
     time_control: T,
     starting_tree: Option<tree::Node<O>>,
@@ -466,10 +652,14 @@ pub fn predict<T, P, O>(
           P: Predictor + 'static,
           O: SearchOptions + 'static
 {
-    let num_workers = num_workers.unwrap_or(*config::NUM_THREADS);
+    let num_workers = num_workers.unwrap_or(config.num_threads);
+
+    // make the search only depend on the position, and not on what has been
+    // searched before it, if the user asked for reproducible results
+    rng::reseed(config.seed, starting_point.zobrist_hash() ^ starting_color as u64);
 
     Profiler::with(move || {
-        predict_aux::<T, _, O>(server, num_workers, time_control, starting_tree, starting_point, starting_color)
+        predict_aux::<T, _, O>(server, config, num_workers, time_control, starting_tree, starting_point, starting_color)
     })
 }
 
@@ -482,24 +672,27 @@ pub fn predict<T, P, O>(
 /// - 10% chance of a random komi between `-7.5` and `7.5`.
 ///
 fn get_random_komi() -> f32 {
-    let value = thread_rng().gen::<f32>();
-
-    if value < 0.4 {
-        7.5
-    } else if value < 0.8 {
-        6.5
-    } else if value < 0.9 {
-        0.5
-    } else {
-        let value: i32 = thread_rng().gen_range(-8, 8);
+    rng::with(|rng| {
+        let value = rng.gen::<f32>();
+
+        if value < 0.4 {
+            7.5
+        } else if value < 0.8 {
+            6.5
+        } else if value < 0.9 {
+            0.5
+        } else {
+            let value: i32 = rng.gen_range(-8, 8);
 
-        value as f32 + 0.5
-    }
+            value as f32 + 0.5
+        }
+    })
 }
 
 #[cfg(test)]
 mod tests {
-    use dg_go::{Board, Color};
+    use dg_go::{DEFAULT_SIZE, Board, Color};
+    use dg_utils::config::Procedure;
     use dg_utils::types::f16;
     use super::*;
 
@@ -523,7 +716,9 @@ mod tests {
         let root = Arc::new(UnsafeCell::new(tree::Node::new(Color::Black, 0.0, vec! [1.0; 362])));
         let context = ThreadContext {
             root: root.clone(),
-            starting_point: Board::new(7.5),
+            starting_point: Board::new(DEFAULT_SIZE, 7.5),
+            params: Arc::new(SearchParams::default()),
+            max_tree_memory: 0,
             time_strategy: time_control::RolloutLimit::new(100)
         };
 
@@ -532,18 +727,18 @@ mod tests {
         }
 
         predict_worker::<_, _, StandardSearch>(context, predict::RandomPredictor::default());
-        assert_eq!(unsafe { &*root.get() }.best(0.0), (::std::f32::NEG_INFINITY, 361));
+        assert_eq!(unsafe { &*root.get() }.best(0.0, &SearchParams::default()), (::std::f32::NEG_INFINITY, 361));
     }
 
     #[derive(Clone, Default)]
     struct NanPredictor;
 
     impl predict::Predictor for NanPredictor {
-        fn predict(&self, _features: Vec<f16>) -> Option<(f32, Vec<f32>)> {
-            Some((0.0, vec! [::std::f32::NEG_INFINITY; 362]))
+        fn predict(&self, _features: Vec<f16>) -> Option<(f32, Vec<f32>, Option<Vec<f32>>)> {
+            Some((0.0, vec! [::std::f32::NEG_INFINITY; 362], None))
         }
 
-        fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>)>> {
+        fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>, Option<Vec<f32>>)>> {
             features_list.map(|features| self.predict(features)).collect()
         }
 
@@ -556,10 +751,11 @@ mod tests {
     fn no_finite_candidates() {
         let (value, index, root) = predict::<_, _, StandardSearch>(
             &NanPredictor::default(),
+            &Config::new(Procedure::Gtp),
             None,
             time_control::RolloutLimit::new(1600),
             None,
-            &Board::new(7.5),
+            &Board::new(DEFAULT_SIZE, 7.5),
             Color::Black
         ).unwrap();
 
@@ -568,4 +764,129 @@ mod tests {
         assert_eq!(root.total_count, 0);
         assert_eq!(root.vtotal_count, 0);
     }
+
+    #[test]
+    fn seeded_search_is_reproducible() {
+        let mut config = Config::new(Procedure::Gtp);
+        config.seed = Some(1);
+
+        let mut board = Board::new(DEFAULT_SIZE, 7.5);
+        board.place(Color::Black, 15, 3);
+        board.place(Color::White, 3, 15);
+
+        let search = || {
+            let (value, index, root) = predict::<_, _, StandardSearch>(
+                &predict::RandomPredictor::default(),
+                &config,
+                Some(4),
+                time_control::RolloutLimit::new(400),
+                None,
+                &board,
+                Color::Black
+            ).unwrap();
+            let sgf = tree::to_sgf::<CGoban, _>(&root, &board, &config.search, false).to_string();
+
+            (value, index, root.total_count, sgf)
+        };
+
+        assert_eq!(search(), search());
+    }
+
+    #[test]
+    fn memory_budget_is_per_tree() {
+        let mut board = Board::new(DEFAULT_SIZE, 7.5);
+        board.place(Color::Black, 2, 2);
+
+        let (_, _, other) = predict::<_, _, StandardSearch>(
+            &predict::RandomPredictor::default(),
+            &Config::new(Procedure::Gtp),
+            Some(2),
+            time_control::RolloutLimit::new(1000),
+            None,
+            &board,
+            Color::White
+        ).unwrap();
+
+        // the other tree alone uses more than the budget, but it should not
+        // stop (or prune) the search of this tree
+        let mut config = Config::new(Procedure::Gtp);
+        config.max_tree_memory = 1;
+
+        let other_usage = other.memory_usage();
+        assert!(other_usage > config.max_tree_memory_bytes());
+
+        let (_, _, root) = predict::<_, _, StandardSearch>(
+            &predict::RandomPredictor::default(),
+            &config,
+            Some(2),
+            time_control::RolloutLimit::new(100),
+            None,
+            &board,
+            Color::Black
+        ).unwrap();
+
+        assert!(root.size() >= 50, "{}", root.size());
+        assert_eq!(other.memory_usage(), other_usage);
+    }
+
+    #[test]
+    fn small_board_candidates() {
+        let board = Board::new(9, 7.5);
+        let (policy, indices) = create_initial_policy::<StandardSearch>(&board, Color::Black);
+
+        for i in 0..361 {
+            let is_on_board = i % 19 < 9 && i / 19 < 9;
+
+            assert!(is_on_board || !policy[i].is_finite(), "{} is a candidate", i);
+            assert!(indices[i] % 19 < 9 && indices[i] / 19 < 9 || indices[i] == i);
+        }
+
+        // the empty board is fully symmetric, so only the 15 vertices in one
+        // eighth of the board (between the corner, the center, and the middle
+        // of an edge) should remain, and the pass
+        let candidates = (0..362).filter(|&i| policy[i].is_finite()).count();
+
+        assert_eq!(candidates, 15 + 1);
+    }
+
+    #[test]
+    fn small_board_search() {
+        let (_value, index, _root) = predict::<_, _, StandardSearch>(
+            &predict::RandomPredictor::default(),
+            &Config::new(Procedure::Gtp),
+            None,
+            time_control::RolloutLimit::new(200),
+            None,
+            &Board::new(9, 7.5),
+            Color::Black
+        ).unwrap();
+
+        assert!(index == 361 || (index % 19 < 9 && index / 19 < 9), "{}", index);
+    }
+
+    #[test]
+    fn untransform_small_ownership() {
+        let ownership = (0..361).map(|i| i as f32).collect::<Vec<_>>();
+
+        for &t in &symmetry::ALL {
+            let other = untransform_ownership(&ownership, 9, t);
+
+            for i in 0..361 {
+                if i % 19 < 9 && i / 19 < 9 {
+                    assert_eq!(other[t.inverse().apply(9, i)], ownership[i]);
+                } else {
+                    assert_eq!(other[i], 0.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn score_lead() {
+        let board = Board::new(9, 7.5);
+        let ownership = (0..361).map(|i| if i % 19 < 9 && i / 19 < 9 { 1.0 } else { 0.0 }).collect::<Vec<_>>();
+
+        assert_eq!(get_score_lead(&board, Color::Black, &ownership), 73.5);
+        assert_eq!(get_score_lead(&board, Color::White, &ownership), 88.5);
+    }
 }
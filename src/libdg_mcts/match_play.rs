@@ -0,0 +1,280 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use dg_go::utils::sgf::SgfMove;
+use dg_go::{DEFAULT_KOMI, DEFAULT_SIZE, Board, Color};
+use dg_utils::config::Config;
+use super::predict::Predictor;
+use super::time_control::KlDivergence;
+use super::GameResult;
+use super::{predict_service, predict_aux, rng, tree};
+use dg_nn::Network;
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::mem;
+use options::StandardSearch;
+
+/// Play a game between two engines and return the result of the game.
+///
+/// # Arguments
+///
+/// * `black` - the server to use during evaluation for the black player
+/// * `white` - the server to use during evaluation for the white player
+/// * `config` - the configuration to play with
+/// * `num_parallel` - the number of games that are being played in parallel
+///
+fn match_one<P: Predictor + 'static>(
+    black: &P,
+    white: &P,
+    config: &Config,
+    num_parallel: &Arc<AtomicUsize>
+) -> Option<GameResult>
+{
+    let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
+    let mut moves = vec! [];
+    let mut current = Color::Black;
+    let mut pass_count = 0;
+    let mut count = 0;
+
+    // each player keeps its own search tree, since the trees of the two
+    // networks do not agree on any of the values
+    let mut root_current = None;
+    let mut root_other = None;
+
+    while count < 722 {
+        let num_workers = config.num_threads / num_parallel.load(Ordering::Acquire);
+        let server = if current == Color::Black { black } else { white };
+        let (value, index, tree) = predict_aux::<_, _, StandardSearch>(
+            server,
+            config,
+            num_workers,
+            KlDivergence::new(config.num_rollout.into(), config.kl_threshold),
+            root_current,
+            &board,
+            current
+        )?;
+
+        debug_assert!(0.0 <= value && value <= 1.0);
+        debug_assert!(index < 362);
+
+        let value_sgf = if current == Color::Black { 2.0 * value - 1.0 } else { -2.0 * value + 1.0 };
+
+        if !config.no_resign && value < 0.05 {  // resign the game if the evaluation looks bad
+            return Some(GameResult::Resign(moves, board, current.opposite(), -value))
+        } else if index == 361 {  // passing move
+            moves.push(SgfMove::new(current, None).with("V", format!("{}", value_sgf)));
+            pass_count += 1;
+
+            if pass_count >= 2 {
+                return Some(GameResult::Ended(moves, board))
+            }
+        } else {
+            let (x, y) = (tree::X[index] as usize, tree::Y[index] as usize);
+
+            moves.push(SgfMove::new(current, Some((x, y))).with("V", format!("{}", value_sgf)));
+            pass_count = 0;
+            board.place(current, x, y);
+        }
+
+        // update the search trees
+        root_current = tree::Node::forward(tree, index);
+        root_other = if let Some(other) = root_other {
+            tree::Node::forward(other, index)
+        } else {
+            None
+        };
+
+        // swap whose turn it is to place a stone
+        mem::swap(&mut root_current, &mut root_other);
+        current = current.opposite();
+        count += 1;
+    }
+
+    Some(GameResult::Ended(moves, board))
+}
+
+/// Play games between two networks, alternating which one plays black, and
+/// return the result of each game (and the color the first network played)
+/// over the channel.
+///
+/// # Arguments
+///
+/// * `first` - the neural network of the first player
+/// * `second` - the neural network of the second player
+/// * `num_games` - the number of games to play
+/// * `config` - the configuration to play with
+///
+pub fn match_play(
+    first: Network,
+    second: Network,
+    num_games: usize,
+    config: &Config
+) -> (Receiver<(Color, GameResult)>, predict_service::PredictService, predict_service::PredictService)
+{
+    let first_server = predict_service::service(first, config);
+    let second_server = predict_service::service(second, config);
+    let config = Arc::new(config.clone());
+    let (sender, receiver) = channel();
+
+    // spawn the worker threads that play the games, every game is sent to
+    // the same two services so that the evaluations of concurrent games are
+    // batched together
+    let num_parallel = ::std::cmp::min(num_games, config.num_games);
+    let num_workers = Arc::new(AtomicUsize::new(num_parallel));
+    let processed = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0..num_parallel {
+        let num_workers = num_workers.clone();
+        let processed = processed.clone();
+        let sender = sender.clone();
+        let first_server = first_server.lock().clone_to_static();
+        let second_server = second_server.lock().clone_to_static();
+        let config = config.clone();
+
+        thread::spawn(move || {
+            loop {
+                let index = processed.fetch_add(1, Ordering::SeqCst);
+
+                if index >= num_games {
+                    break
+                }
+
+                rng::reseed(config.seed, index as u64);
+
+                let (first_color, result) = if index % 2 == 0 {
+                    (Color::Black, match_one(&first_server, &second_server, &config, &num_workers))
+                } else {
+                    (Color::White, match_one(&second_server, &first_server, &config, &num_workers))
+                };
+
+                if let Some(result) = result {
+                    if sender.send((first_color, result)).is_err() {
+                        break
+                    }
+                }
+            }
+
+            num_workers.fetch_sub(1, Ordering::Release);
+        });
+    }
+
+    (receiver, first_server, second_server)
+}
+
+/// The number of games won by the first player of a match, by the color
+/// it played. A drawn game counts as half a win.
+#[derive(Clone, Debug, Default)]
+pub struct MatchSummary {
+    /// The number of games won as black, and as white.
+    wins: [f32; 2],
+
+    /// The number of games played as black, and as white.
+    games: [usize; 2]
+}
+
+impl MatchSummary {
+    /// Adds the result of a game to this summary.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_color` - the color the first player played
+    /// * `winner` - the color of the winner, or `None` if the game was drawn
+    ///
+    pub fn add(&mut self, first_color: Color, winner: Option<Color>) {
+        let i = if first_color == Color::Black { 0 } else { 1 };
+
+        self.wins[i] += match winner {
+            Some(winner) if winner == first_color => 1.0,
+            Some(_) => 0.0,
+            None => 0.5
+        };
+        self.games[i] += 1;
+    }
+
+    /// Returns the 95% confidence interval of the win rate of the first
+    /// player, using the _Wilson score interval_.
+    pub fn confidence_interval(&self) -> (f32, f32) {
+        const Z: f32 = 1.96;
+
+        let n = (self.games[0] + self.games[1]) as f32;
+
+        if n == 0.0 {
+            (0.0, 1.0)
+        } else {
+            let p = (self.wins[0] + self.wins[1]) / n;
+            let denom = 1.0 + Z * Z / n;
+            let center = (p + Z * Z / (2.0 * n)) / denom;
+            let spread = Z * (p * (1.0 - p) / n + Z * Z / (4.0 * n * n)).sqrt() / denom;
+
+            (center - spread, center + spread)
+        }
+    }
+}
+
+impl fmt::Display for MatchSummary {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let (lower, upper) = self.confidence_interval();
+
+        write!(
+            fmt,
+            "won {:.1} of {} games ({:.1}% to {:.1}% with 95% confidence), {:.1} of {} as black and {:.1} of {} as white",
+            self.wins[0] + self.wins[1],
+            self.games[0] + self.games[1],
+            100.0 * lower,
+            100.0 * upper,
+            self.wins[0],
+            self.games[0],
+            self.wins[1],
+            self.games[1]
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dg_go::Color;
+    use match_play::*;
+
+    #[test]
+    fn confidence_interval() {
+        let mut summary = MatchSummary::default();
+
+        for i in 0..100 {
+            let first_color = if i % 2 == 0 { Color::Black } else { Color::White };
+            let winner = if i < 60 { first_color } else { first_color.opposite() };
+
+            summary.add(first_color, Some(winner));
+        }
+
+        let (lower, upper) = summary.confidence_interval();
+
+        assert!((lower - 0.502).abs() < 1e-3, "{}", lower);
+        assert!((upper - 0.691).abs() < 1e-3, "{}", upper);
+    }
+
+    #[test]
+    fn draws_count_half() {
+        let mut summary = MatchSummary::default();
+
+        summary.add(Color::Black, None);
+        summary.add(Color::White, Some(Color::White));
+
+        assert_eq!(summary.wins, [0.5, 1.0]);
+        assert_eq!(summary.games, [1, 1]);
+    }
+}
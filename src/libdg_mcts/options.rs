@@ -28,6 +28,13 @@ pub trait SearchOptions : Clone {
 
     /// Returns true if the search should be deterministic.
     fn deterministic() -> bool;
+
+    /// Returns true if nodes that represent the same position should be
+    /// shared between all of their parents, turning the search tree into a
+    /// directed acyclic graph.
+    fn transpositions() -> bool {
+        false
+    }
 }
 
 #[derive(Clone)]
@@ -43,6 +50,43 @@ impl SearchOptions for StandardSearch {
     }
 }
 
+/// The same search as `StandardSearch`, but where positions that are reached
+/// through different move orders share a single node (_Monte-Carlo Graph
+/// Search_).
+#[derive(Clone)]
+pub struct GraphSearch;
+
+impl SearchOptions for GraphSearch {
+    fn is_policy_candidate(_board: &Board, _to_move: Color, _index: usize) -> bool {
+        true
+    }
+
+    fn deterministic() -> bool {
+        false
+    }
+
+    fn transpositions() -> bool {
+        true
+    }
+}
+
+/// The same search as `StandardSearch`, but without any exploration noise at
+/// the root or temperature when picking the move. This is used for the cheap
+/// searches during self-play, whose only purpose is to play a reasonable move
+/// to continue the game from.
+#[derive(Clone)]
+pub struct CheapSearch;
+
+impl SearchOptions for CheapSearch {
+    fn is_policy_candidate(_board: &Board, _to_move: Color, _index: usize) -> bool {
+        true
+    }
+
+    fn deterministic() -> bool {
+        true
+    }
+}
+
 #[derive(Clone)]
 pub struct ScoringSearch;
 
@@ -71,8 +115,10 @@ fn is_vertex_filled(board: &Board, color: Color, index: usize, dx: i8, dy: i8) -
     let other_x = x + dx as isize;
     let other_y = y + dy as isize;
 
-    other_x >= 0 && other_x < 19 &&
-        other_y >= 0 && other_y < 19 &&
+    let size = board.size() as isize;
+
+    other_x >= 0 && other_x < size &&
+        other_y >= 0 && other_y < size &&
         board.at(other_x as usize, other_y as usize) == Some(color)
 }
 
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 pub mod global_rwlock;
+pub mod reclaimer;
 mod service;
 pub mod spin;
 
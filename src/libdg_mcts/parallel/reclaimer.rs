@@ -0,0 +1,108 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crossbeam_channel::{unbounded, Sender};
+use std::thread;
+
+/// A heap allocated value that should be dropped by the reclaimer thread.
+struct Garbage {
+    ptr: *mut (),
+    drop_fn: unsafe fn(*mut ())
+}
+
+unsafe impl Send for Garbage { }
+
+lazy_static! {
+    /// The channel to the reclaimer thread, which drops everything that is
+    /// sent to it in the order they were received.
+    static ref RECLAIMER: Sender<Garbage> = {
+        let (sender, receiver) = unbounded::<Garbage>();
+
+        thread::Builder::new()
+            .name("reclaimer".into())
+            .spawn(move || {
+                for garbage in receiver.iter() {
+                    unsafe { (garbage.drop_fn)(garbage.ptr) };
+                }
+            })
+            .unwrap();
+
+        sender
+    };
+}
+
+/// Drops the given boxed value.
+///
+/// # Arguments
+///
+/// * `ptr` - the value to drop
+///
+unsafe fn drop_box<T>(ptr: *mut ()) {
+    drop(Box::from_raw(ptr as *mut T));
+}
+
+/// Drops the given value on a background thread, so that the caller does not
+/// have to wait for large data structures (such as search trees) to be freed.
+///
+/// The value may be dropped at any time after this call, so it must not
+/// reference anything that is not owned by itself. If the reclaimer thread
+/// is not available, then the value is dropped immediately.
+///
+/// # Arguments
+///
+/// * `value` - the value to drop
+///
+pub unsafe fn reclaim<T>(value: Box<T>) {
+    let garbage = Garbage {
+        ptr: Box::into_raw(value) as *mut (),
+        drop_fn: drop_box::<T>
+    };
+
+    if let Err(err) = RECLAIMER.send(garbage) {
+        let garbage = err.into_inner();
+
+        (garbage.drop_fn)(garbage.ptr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parallel::reclaimer::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn reclaim_drops() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let started = Instant::now();
+
+        for _ in 0..10 {
+            unsafe { reclaim(Box::new(Counted(count.clone()))) };
+        }
+
+        while count.load(Ordering::SeqCst) < 10 {
+            assert!(started.elapsed() < Duration::from_secs(10));
+            thread::yield_now();
+        }
+    }
+}
@@ -232,6 +232,12 @@ impl<'a, I: ServiceImpl + 'static> ServiceGuard<'a, I> {
         self.state.lock().unwrap()
     }
 
+    /// Returns an identifier of the service of this guard, which is unique
+    /// among all services that are alive.
+    pub fn id(&self) -> usize {
+        &*self.state as *const Mutex<I::State> as usize
+    }
+
     /// Sends a request to the service and returns the response.
     /// 
     /// # Arguments
@@ -13,19 +13,20 @@
 // limitations under the License.
 
 use ordered_float::OrderedFloat;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{channel, Receiver};
 use std::sync::Arc;
 use std::thread;
 
-use dg_go::utils::sgf::{CGoban, SgfCoordinate};
-use dg_go::{Board, Color};
-use dg_utils::{b85, config, min};
+use dg_go::utils::sgf::SgfMove;
+use dg_go::{DEFAULT_SIZE, Board, Color};
+use dg_utils::config::{Config, SamplingStrategy};
+use dg_utils::{b85, min};
 use super::asm::sum_finite_f32;
 use super::predict::Predictor;
-use super::time_control::RolloutLimit;
-use super::{dirichlet, tree, predict_service};
+use super::time_control::{KlDivergence, RolloutLimit};
+use super::{dirichlet, rng, tree, predict_service};
 use super::{GameResult, full_forward, get_random_komi, predict_aux};
 use dg_nn::Network;
 use options::StandardSearch;
@@ -87,7 +88,7 @@ fn policy_choose(policy: &[f32], temperature: f32) -> Option<usize> {
     // if there are no valid moves remaining then pass, otherwise pick
     // a random move using binary search over the `subtotals`.
     if subtotals[361] > 0.0 {
-        let threshold = 0.8 * thread_rng().gen::<f32>() * subtotals[361];
+        let threshold = 0.8 * rng::with(|rng| rng.gen::<f32>()) * subtotals[361];
         let mut index = match subtotals.binary_search_by_key(&OrderedFloat(threshold), |&s| OrderedFloat(s)) {
             Ok(i) => i,
             Err(i) => i
@@ -110,11 +111,12 @@ fn policy_choose(policy: &[f32], temperature: f32) -> Option<usize> {
     }
 }
 
-fn policy_ex_it<P: Predictor + 'static>(server: &P, board: &Board, to_move: Color) -> Option<(String, f32)> {
+fn policy_ex_it<P: Predictor + 'static>(server: &P, config: &Config, board: &Board, to_move: Color) -> Option<(String, f32)> {
     let (value, _index, tree) = predict_aux::<_, _, StandardSearch>(
         server,
+        config,
         1,
-        RolloutLimit::new((*config::NUM_ROLLOUT).into()),
+        KlDivergence::new(config.num_rollout.into(), config.kl_threshold),
         None,
         board,
         to_move
@@ -131,30 +133,33 @@ fn policy_ex_it<P: Predictor + 'static>(server: &P, board: &Board, to_move: Colo
 }
 
 /// Perform a _search_ of the given board state, using the number of rollouts
-/// specified in `NUM_POLICY_ROLLOUT`.
+/// specified in `config.num_policy_rollout`.
 ///
 /// # Arguments
 ///
 /// * `server` -
+/// * `config` - the configuration to search with
 /// * `board` -
 /// * `to_move` -
 ///
 fn policy_forward<P: Predictor + 'static>(
     server: &P,
+    config: &Config,
     board: &Board,
     to_move: Color
 ) -> Option<(f32, Vec<f32>)>
 {
-    let num_policy_rollout = *config::NUM_POLICY_ROLLOUT;
+    let num_policy_rollout = config.num_policy_rollout;
 
     if num_policy_rollout <= 1 {
-        let (value, mut policy) = full_forward::<_, StandardSearch>(server, board, to_move)?;
-        dirichlet::add(&mut policy[0..362], 0.03);
+        let (value, mut policy, _) = full_forward::<_, StandardSearch>(server, board, to_move)?;
+        dirichlet::add_ex(&mut policy[0..362], 0.03, config.search.dirichlet_noise);
 
         Some((value, policy))
     } else {
         let (value, _index, tree) = predict_aux::<_, _, StandardSearch>(
             server,
+            config,
             1,
             RolloutLimit::new(num_policy_rollout),
             None,
@@ -174,22 +179,23 @@ fn policy_forward<P: Predictor + 'static>(
 /// # Arguments
 ///
 /// * `server` - the server to use during evaluation
+/// * `config` - the configuration to play with
 /// * `ex_it` - whether to emit one full policy
 ///
-fn policy_play_one<P: Predictor + 'static>(server: &P, ex_it: bool) -> Option<GameResult> {
-    let mut temperature = (*config::TEMPERATURE + 1e-3).recip();
-    let mut sgf = vec! [];
+fn policy_play_one<P: Predictor + 'static>(server: &P, config: &Config, ex_it: bool) -> Option<GameResult> {
+    let mut temperature = (config.search.temperature + 1e-3).recip();
+    let mut moves = vec! [];
 
     // loop until we run or of legal moves, the board is fully scorable, or
     // we have played 722 moves in total.
-    let mut board = Board::new(get_random_komi());
+    let mut board = Board::new(DEFAULT_SIZE, get_random_komi());
     let mut color = Color::Black;
     let mut pass_count = 0;
     let mut total_skew = 0.0;
 
     while pass_count < 2 && board.count() < 722 {
         let (index, skew) = {
-            let (_value, policy) = policy_forward(server, &board, color)?;
+            let (_value, policy) = policy_forward(server, config, &board, color)?;
 
             match policy_choose(&policy, temperature) {
                 Some(index) => (index, skewness(&policy)),
@@ -198,12 +204,12 @@ fn policy_play_one<P: Predictor + 'static>(server: &P, ex_it: bool) -> Option<Ga
         };
 
         if index == 361 {
-            sgf.push((board.clone(), color, skew, format!(";{}[]", color)));
+            moves.push((board.clone(), skew, SgfMove::new(color, None)));
             pass_count += 1;
         } else {
             let (x, y) = (tree::X[index] as usize, tree::Y[index] as usize);
 
-            sgf.push((board.clone(), color, skew, format!(";{}[{}]", color, CGoban::to_sgf(x, y))));
+            moves.push((board.clone(), skew, SgfMove::new(color, Some((x, y)))));
             board.place(color, x, y);
             pass_count = 0;
         }
@@ -218,21 +224,21 @@ fn policy_play_one<P: Predictor + 'static>(server: &P, ex_it: bool) -> Option<Ga
     if ex_it {
         // reject the top 50% most skewed prior values, since they will not produce useful
         // search trees anyway.
-        let mut indices = (0..sgf.len()).collect::<Vec<_>>();
-        let num_samples = match *config::NUM_SAMPLES {
-            config::SamplingStrategy::Percent(pct) => (pct * indices.len() as f32) as usize,
-            config::SamplingStrategy::Fixed(num) => ::std::cmp::min(num, indices.len())
+        let mut indices = (0..moves.len()).collect::<Vec<_>>();
+        let num_samples = match config.num_samples {
+            SamplingStrategy::Percent(pct) => (pct * indices.len() as f32) as usize,
+            SamplingStrategy::Fixed(num) => ::std::cmp::min(num, indices.len())
         };
 
         for _j in 0..num_samples {
-            let cutoff = thread_rng().gen::<f32>() * total_skew;
+            let cutoff = rng::with(|rng| rng.gen::<f32>()) * total_skew;
             let mut so_far = 0.0;
             let mut i;
             let mut j = 0;
 
             loop {
                 i = indices[j];
-                so_far += sgf[i].2;
+                so_far += moves[i].1;
 
                 if so_far >= cutoff {
                     break
@@ -242,18 +248,19 @@ fn policy_play_one<P: Predictor + 'static>(server: &P, ex_it: bool) -> Option<Ga
             }
 
             // for each `i`, compute the _true_ policy using MCTS
-            let (policy_sgf, value_sgf) = policy_ex_it(server, &sgf[i].0, sgf[i].1)?;
+            let (policy_sgf, value_sgf) = policy_ex_it(server, config, &moves[i].0, moves[i].2.color)?;
 
-            sgf[i].3 = format!("{}P[{}]V[{:.4}]", sgf[i].3, policy_sgf, value_sgf);
+            moves[i].2.properties.push(("P", policy_sgf));
+            moves[i].2.properties.push(("V", format!("{:.4}", value_sgf)));
 
             // remove the sample from the available samples so that we do not compute it twice
-            total_skew -= sgf[i].2;
+            total_skew -= moves[i].1;
             indices.swap_remove(j);
         }
     }
 
     Some(GameResult::Ended(
-        sgf.into_iter().fold(String::new(), |acc, (_board, _color, _skew, sgf)| acc + &sgf),
+        moves.into_iter().map(|(_board, _skew, mv)| mv).collect(),
         board
     ))
 }
@@ -268,25 +275,38 @@ fn policy_play_one<P: Predictor + 'static>(server: &P, ex_it: bool) -> Option<Ga
 /// * `network` - the neural network to use during evaluation
 /// * `num_games` -
 /// * `ex_it` - whether to emit one full policy per game
+/// * `config` - the configuration to play with
 ///
-pub fn policy_play(network: Network, num_games: usize, ex_it: bool) -> (Receiver<GameResult>, predict_service::PredictService) {
-    let server = predict_service::service(network);
+pub fn policy_play(
+    network: Network,
+    num_games: usize,
+    ex_it: bool,
+    config: &Config
+) -> (Receiver<GameResult>, predict_service::PredictService)
+{
+    let server = predict_service::service(network, config);
+    let config = Arc::new(config.clone());
     let (sender, receiver) = channel();
 
     // spawn the worker threads that generate the self-play games
-    let num_workers = ::std::cmp::min(*config::NUM_GAMES, num_games);
+    let num_workers = ::std::cmp::min(config.num_games, num_games);
     let remaining = Arc::new(AtomicUsize::new(num_games));
 
     for _ in 0..num_workers {
         let remaining = remaining.clone();
         let sender = sender.clone();
         let server = server.lock().clone_to_static();
+        let config = config.clone();
 
         thread::spawn(move || {
             while remaining.load(Ordering::Acquire) > 0 {
-                remaining.fetch_sub(1, Ordering::AcqRel);
+                let index = remaining.fetch_sub(1, Ordering::AcqRel);
+
+                // make every game reproducible from its index, if the user asked
+                // for reproducible results
+                rng::reseed(config.seed, index as u64);
 
-                if let Some(result) = policy_play_one(&server, ex_it) {
+                if let Some(result) = policy_play_one(&server, &config, ex_it) {
                     if sender.send(result).is_err() {
                         break
                     }
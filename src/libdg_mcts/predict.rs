@@ -15,13 +15,15 @@
 use dg_utils::types::f16;
 
 pub trait Predictor : Clone + Send {
-    /// Returns the result of the given query.
+    /// Returns the value, policy, and ownership (if supported by the network)
+    /// of the given query. The value and ownership are both from the
+    /// perspective of the player whose turn it is in the features.
     ///
     /// # Arguments
     ///
     /// * `features` - the features to query
     ///
-    fn predict(&self, features: Vec<f16>) -> Option<(f32, Vec<f32>)>;
+    fn predict(&self, features: Vec<f16>) -> Option<(f32, Vec<f32>, Option<Vec<f32>>)>;
 
     /// Returns the results of the given queries.
     ///
@@ -29,38 +31,69 @@ pub trait Predictor : Clone + Send {
     ///
     /// * `features_list` - the features to query over
     ///
-    fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>)>>;
+    fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>, Option<Vec<f32>>)>>;
+
+    /// Returns the results of the given queries, including the ownership if
+    /// the network has an ownership head. The ownership head is only
+    /// evaluated for batches that contains such a query, since it is
+    /// otherwise unused.
+    ///
+    /// # Arguments
+    ///
+    /// * `features_list` - the features to query over
+    ///
+    fn predict_all_with_ownership<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>, Option<Vec<f32>>)>> {
+        self.predict_all(features_list)
+    }
 
     /// waits until all other predicts that are currently running in the
     /// background has finished.
     fn synchronize(&self);
+
+    /// Returns an identifier of the network that this predictor evaluates,
+    /// which is used to keep the cached predictions of different networks
+    /// apart when more than one network is in use.
+    fn network_id(&self) -> usize {
+        0
+    }
 }
 
 /// An implementation of `Predictor` that returns completely random predictions. This
-/// is useful for testing purposes.
+/// is useful for testing purposes. The predictions are random, but always the same
+/// for the same features, so that a search using them can be reproduced.
 #[derive(Clone, Default)]
 pub struct RandomPredictor;
 
 impl Predictor for RandomPredictor {
-    fn predict(&self, _features: Vec<f16>) -> Option<(f32, Vec<f32>)> {
-        use rand::{thread_rng, Rng};
+    fn predict(&self, features: Vec<f16>) -> Option<(f32, Vec<f32>, Option<Vec<f32>>)> {
+        use rand::rngs::SmallRng;
+        use rand::{Rng, SeedableRng};
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
         use super::asm::normalize_finite_f32;
 
+        let mut hasher = DefaultHasher::new();
+
+        for x in &features {
+            x.to_bits().hash(&mut hasher);
+        }
+
+        let mut rng = SmallRng::seed_from_u64(hasher.finish());
         let mut policy = vec! [0.0; 368];
         let mut total_policy = 0.0;
 
         for i in 0..362 {
-            let value = thread_rng().gen();
+            let value = rng.gen();
 
             policy[i] = value;
             total_policy += value;
         }
 
         normalize_finite_f32(&mut policy, total_policy);
-        Some((thread_rng().gen_range(-1.0, 1.0), policy))
+        Some((rng.gen_range(-1.0, 1.0), policy, None))
     }
 
-    fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>)>> {
+    fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>, Option<Vec<f32>>)>> {
         features_list.map(|features| self.predict(features)).collect()
     }
 
@@ -15,25 +15,64 @@
 use crossbeam_channel::Sender;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Mutex, MutexGuard};
+use std::thread;
 
-use super::parallel;
+use super::{global_cache, parallel};
 use dg_go::utils::features::{FEATURE_SIZE};
 use super::predict::Predictor;
 use dg_nn::devices::{DEVICES, set_current_device};
 use dg_nn::{self as nn, Network, Output, OutputSet, Workspace};
 use dg_utils::types::f16;
-use dg_utils::config;
+use dg_utils::config::Config;
 
 pub type PredictGuard<'a> = parallel::ServiceGuard<'a, PredictState>;
 pub type PredictService = parallel::Service<PredictState>;
 
-pub fn service(network: Network) -> PredictService {
-    PredictService::new(None, PredictState::new(network))
+/// Returns a service that evaluates the given network, using the batch size
+/// and number of threads of the given configuration.
+///
+/// # Arguments
+///
+/// * `network` - the network to evaluate
+/// * `config` - the configuration to use
+///
+pub fn service(network: Network, config: &Config) -> PredictService {
+    let num_busy = config.num_threads / config.batch_size;
+    let num_threads = ::std::cmp::max(<PredictState as parallel::ServiceImpl>::get_thread_count(), num_busy);
+
+    PredictService::new(Some(num_threads), PredictState::new(network, config.batch_size))
+}
+
+/// Replaces the network of the given service, once all batches that are
+/// currently being evaluated have finished. Every cached evaluation is
+/// discarded, since they were computed by the old network.
+///
+/// # Arguments
+///
+/// * `service` - the service to replace the network of
+/// * `network` - the new network
+///
+pub fn set_network(service: &PredictService, network: Network) {
+    let guard = service.lock();
+
+    loop {
+        let mut state = guard.get_state();
+
+        if state.sender_list.is_empty() && state.running_count.load(Ordering::SeqCst) == 0 {
+            state.network = network;
+            global_cache::clear();
+            break
+        }
+
+        drop(state);
+        thread::yield_now();
+    }
 }
 
 pub enum PredictRequest {
-    /// Request to compute the value and policy for some feature.
-    Ask(Vec<f16>),
+    /// Request to compute the value and policy for some feature, and
+    /// whether to also compute the ownership.
+    Ask(Vec<f16>, bool),
 
     /// Indicate that a worker is waiting for some other thread to finish
     /// and should be awaken after the next batch of computations finish.
@@ -53,20 +92,29 @@ pub struct PredictState {
 
     /// The sender to response to each of the features in `features_list`
     /// over.
-    sender_list: Vec<Sender<Option<(f32, Vec<f32>)>>>,
+    sender_list: Vec<Sender<Option<(f32, Vec<f32>, Option<Vec<f32>>)>>>,
+
+    /// Whether each of the features in `features_list` should also get
+    /// the ownership.
+    ownership_list: Vec<bool>,
 
     /// All threads that want to get notified when something changed.
-    waiting_list: Vec<Sender<Option<(f32, Vec<f32>)>>>,
+    waiting_list: Vec<Sender<Option<(f32, Vec<f32>, Option<Vec<f32>>)>>>,
+
+    /// The maximum number of features to evaluate at the same time.
+    batch_size: usize
 }
 
 impl PredictState {
-    pub fn new(network: Network) -> PredictState {
+    pub fn new(network: Network, batch_size: usize) -> PredictState {
         PredictState {
             network: network,
             running_count: AtomicUsize::new(0),
             features_list: vec! [],
             sender_list: vec! [],
-            waiting_list: vec! []
+            ownership_list: vec! [],
+            waiting_list: vec! [],
+            batch_size: batch_size
         }
     }
 
@@ -83,20 +131,36 @@ impl PredictState {
     /// 
     /// * `workspace` - 
     /// * `features_list` - 
+    /// * `has_ownership` - whether to also compute the ownership
     /// 
-    fn forward_once(workspace: &mut Workspace, features_list: &[f16]) -> Result<(Vec<f32>, Vec<Vec<f32>>), nn::Error> {
+    fn forward_once(
+        workspace: &mut Workspace,
+        features_list: &[f16],
+        has_ownership: bool
+    ) -> Result<Vec<(f32, Vec<f32>, Option<Vec<f32>>)>, nn::Error>
+    {
+        let output_set = OutputSet::default().with(Output::Policy).with(Output::Value);
         let mut outputs = nn::forward(
             workspace,
             features_list,
-            OutputSet::default().with(Output::Policy).with(Output::Value)
+            if has_ownership { output_set.with(Output::Ownership) } else { output_set }
         )?;
 
         let value_list = outputs.take(Output::Value);
-        let policy_list = outputs.take(Output::Policy).chunks(362)
-            .map(|p| p.to_vec())
-            .collect();
+        let policy_list = outputs.take(Output::Policy);
+        let ownership_list = if has_ownership {
+            outputs.take(Output::Ownership).chunks(361)
+                .map(|o| Some(o.to_vec()))
+                .collect()
+        } else {
+            vec! [None; value_list.len()]
+        };
 
-        Ok((value_list, policy_list))
+        Ok(value_list.into_iter()
+            .zip(policy_list.chunks(362))
+            .zip(ownership_list.into_iter())
+            .map(|((value, policy), ownership)| (value, policy.to_vec(), ownership))
+            .collect())
     }
 
     /// Run the `nn::forward` function for the given features and wrap the
@@ -108,19 +172,27 @@ impl PredictState {
     /// * `network` -
     /// * `batch_size` -
     /// * `features_list` -
+    /// * `with_ownership` - whether any of the features asked for the ownership
     ///
-    fn forward(network: &Network, batch_size: usize, features_list: &[f16]) -> Result<(Vec<f32>, Vec<Vec<f32>>), ()> {
+    fn forward(
+        network: &Network,
+        batch_size: usize,
+        features_list: &[f16],
+        with_ownership: bool
+    ) -> Result<Vec<(f32, Vec<f32>, Option<Vec<f32>>)>, ()>
+    {
+        let has_ownership = with_ownership && network.has_ownership();
         let mut count = 0;
 
         loop {
             let result = network.get_workspace(batch_size).and_then(|mut workspace| {
-                PredictState::forward_once(&mut workspace, features_list)
+                PredictState::forward_once(&mut workspace, features_list, has_ownership)
             });
 
             match result {
                 Ok(content) => { return Ok(content) },
                 Err(reason) => {
-                    eprintln!("Encountered CUDA error, retrying {} more times -- {:?}", 2 - count, reason);
+                    eprintln!("Encountered neural network error, retrying {} more times -- {:?}", 2 - count, reason);
 
                     count += 1;
                     if count >= 3 {
@@ -143,6 +215,7 @@ impl PredictState {
         let split_index = num_items - batch_size;
         let features_list = state_lock.features_list.split_off(split_index * FEATURE_SIZE);
         let sender_list = state_lock.sender_list.split_off(split_index);
+        let with_ownership = state_lock.ownership_list.split_off(split_index).into_iter().any(|x| x);
         let network = state_lock.network.clone();  // just a bunch of Arc<...> so cheap to clone
 
         // keep track of the number of running evaluations so that we avoid
@@ -155,11 +228,9 @@ impl PredictState {
 
         // perform the neural network predictions and then inform all of
         // the receivers
-        if let Ok((value_list, policy_list)) = PredictState::forward(&network, batch_size, &features_list) {
+        if let Ok(response_list) = PredictState::forward(&network, batch_size, &features_list, with_ownership) {
             // send out our predictions to all of the receivers
-            let response_iter = value_list.into_iter().zip(policy_list.into_iter());
-
-            for (sender, response) in sender_list.into_iter().zip(response_iter) {
+            for (sender, response) in sender_list.into_iter().zip(response_list.into_iter()) {
                 sender.send(Some(response)).expect("Failed to send predictor response");
             }
         } else {
@@ -186,7 +257,7 @@ impl PredictState {
     )
     {
         let num_requests = state_lock.sender_list.len();
-        let batch_size = *config::BATCH_SIZE;
+        let batch_size = state_lock.batch_size;
 
         if has_more {
             if num_requests >= batch_size {
@@ -222,19 +293,20 @@ impl PredictState {
 impl parallel::ServiceImpl for PredictState {
     type State = PredictState;
     type Request = PredictRequest;
-    type Response = Option<(f32, Vec<f32>)>;
+    type Response = Option<(f32, Vec<f32>, Option<Vec<f32>>)>;
 
     fn get_thread_count() -> usize {
-        let num_devices = DEVICES.len();
-        let num_busy = *config::NUM_THREADS / *config::BATCH_SIZE;
+        let num_devices = ::std::cmp::max(1, DEVICES.len());
 
-        ::std::cmp::max(2 * num_devices, num_busy)
+        2 * num_devices
     }
 
     fn setup_thread(index: usize) {
-        let device_id = DEVICES[index % DEVICES.len()];
+        if !DEVICES.is_empty() {
+            let device_id = DEVICES[index % DEVICES.len()];
 
-        set_current_device(device_id).expect("Failed to set the device for the current thread");
+            set_current_device(device_id).expect("Failed to set the device for the current thread");
+        }
     }
 
     fn check_sleep(state: MutexGuard<Self::State>) {
@@ -254,9 +326,10 @@ impl parallel::ServiceImpl for PredictState {
     )
     {
         match req {
-            PredictRequest::Ask(features) => {
+            PredictRequest::Ask(features, with_ownership) => {
                 state_lock.features_list.extend_from_slice(&features);
                 state_lock.sender_list.push(sender);
+                state_lock.ownership_list.push(with_ownership);
             },
             PredictRequest::Wait => {
                 state_lock.waiting_list.push(sender);
@@ -274,14 +347,20 @@ impl Clone for PredictGuard<'_> {
 }
 
 impl Predictor for PredictGuard<'_> {
-    fn predict(&self, features: Vec<f16>) -> Option<(f32, Vec<f32>)> {
-        self.send(PredictRequest::Ask(features))
+    fn predict(&self, features: Vec<f16>) -> Option<(f32, Vec<f32>, Option<Vec<f32>>)> {
+        self.send(PredictRequest::Ask(features, false))
             .expect("predict_service could not provide a response")
     }
 
-    fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>)>> {
+    fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>, Option<Vec<f32>>)>> {
         self.send_all(features_list.into_iter().map(|features| {
-            PredictRequest::Ask(features)
+            PredictRequest::Ask(features, false)
+        })).expect("predict_service could not provide a response")
+    }
+
+    fn predict_all_with_ownership<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>, Option<Vec<f32>>)>> {
+        self.send_all(features_list.into_iter().map(|features| {
+            PredictRequest::Ask(features, true)
         })).expect("predict_service could not provide a response")
     }
 
@@ -291,4 +370,8 @@ impl Predictor for PredictGuard<'_> {
 
         debug_assert!(result.is_none());
     }
+
+    fn network_id(&self) -> usize {
+        self.id()
+    }
 }
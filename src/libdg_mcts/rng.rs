@@ -0,0 +1,84 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use rand::rngs::SmallRng;
+use rand::{FromEntropy, SeedableRng};
+use std::cell::RefCell;
+
+thread_local! {
+    /// The random number generator of the current thread, which is seeded
+    /// from the system entropy until `reseed` is called with a seed.
+    static RNG: RefCell<SmallRng> = RefCell::new(new_rng(None, 0));
+}
+
+/// Returns a random number generator that is seeded by the given `seed`
+/// mixed with `value`, or from the system entropy if there is no seed.
+///
+/// # Arguments
+///
+/// * `seed` - the seed given by the user, if any
+/// * `value` - the value to mix into the seed
+///
+fn new_rng(seed: Option<u64>, value: u64) -> SmallRng {
+    match seed {
+        Some(seed) => SmallRng::seed_from_u64(seed ^ value.wrapping_mul(0x9e37_79b9_7f4a_7c15)),
+        None => SmallRng::from_entropy()
+    }
+}
+
+/// Re-seeds the random number generator of the current thread from the
+/// given seed and value, so that everything this thread does afterwards is
+/// reproducible from the value alone. This does nothing if no seed was given.
+///
+/// # Arguments
+///
+/// * `seed` - the seed given by the user, if any
+/// * `value` - the value to mix into the seed, such as the index of a game
+///
+pub fn reseed(seed: Option<u64>, value: u64) {
+    if seed.is_some() {
+        RNG.with(|rng| *rng.borrow_mut() = new_rng(seed, value));
+    }
+}
+
+/// Returns the result of the given callback, after it has been called with
+/// the random number generator of the current thread.
+///
+/// # Arguments
+///
+/// * `callback` - the function to call with the random number generator
+///
+pub fn with<T, F: FnOnce(&mut SmallRng) -> T>(callback: F) -> T {
+    RNG.with(|rng| callback(&mut rng.borrow_mut()))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use rng::*;
+
+    #[test]
+    fn same_seed() {
+        let mut a = new_rng(Some(42), 7);
+        let mut b = new_rng(Some(42), 7);
+        let mut c = new_rng(Some(42), 8);
+
+        let a = (0..8).map(|_| a.gen::<u32>()).collect::<Vec<_>>();
+        let b = (0..8).map(|_| b.gen::<u32>()).collect::<Vec<_>>();
+        let c = (0..8).map(|_| c.gen::<u32>()).collect::<Vec<_>>();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+}
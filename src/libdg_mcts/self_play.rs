@@ -12,34 +12,114 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use dg_go::utils::sgf::{CGoban, SgfCoordinate};
-use dg_go::{Board, Color};
-use dg_utils::{b85, config};
+use dg_go::utils::sgf::{CGoban, SgfCoordinate, SgfMove};
+use dg_go::{DEFAULT_SIZE, Board, Color};
+use dg_utils::b85;
+use dg_utils::config::Config;
 use super::predict::Predictor;
-use super::time_control::RolloutLimit;
+use super::time_control::{KlDivergence, RolloutLimit};
 use super::{GameResult, get_random_komi};
-use super::{predict_service, predict_aux, tree};
+use super::{predict_service, predict_aux, rng, tree};
 use dg_nn::Network;
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{channel, Receiver};
 use std::sync::Arc;
 use std::thread;
 use std::mem;
-use options::StandardSearch;
+use options::{CheapSearch, StandardSearch};
+
+/// The search tree of either a full search, or a cheap search. The two are
+/// searched with different options, so a tree is only continued from by a
+/// search of the same kind.
+enum SearchTree {
+    Full(tree::Node<StandardSearch>),
+    Cheap(tree::Node<CheapSearch>)
+}
+
+impl SearchTree {
+    /// Returns the sub-tree of the given move, if it has been expanded.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the move to return the sub-tree for
+    ///
+    fn forward(self, index: usize) -> Option<SearchTree> {
+        match self {
+            SearchTree::Full(tree) => tree::Node::forward(tree, index).map(SearchTree::Full),
+            SearchTree::Cheap(tree) => tree::Node::forward(tree, index).map(SearchTree::Cheap)
+        }
+    }
+
+    /// Returns the best move according to the prior value of the root node.
+    fn prior(&self) -> (f32, usize) {
+        match *self {
+            SearchTree::Full(ref tree) => tree.prior(),
+            SearchTree::Cheap(ref tree) => tree.prior()
+        }
+    }
+}
+
+/// Returns the result of a search of the given board position. With
+/// probability `1 - config.full_search_prob` only a cheap search is
+/// performed, whose result should not be used as a training target since its
+/// policy is too close to the prior. A cheap search does not add any noise
+/// or temperature, since it should only play a reasonable move.
+///
+/// # Arguments
+///
+/// * `server` - the server to use during evaluation
+/// * `config` - the configuration to search with
+/// * `num_workers` - the number of threads to search with
+/// * `starting_tree` - the search tree to continue from, if any
+/// * `board` - the board position to search
+/// * `to_move` - the color of the player whose turn it is
+///
+fn search<P: Predictor + 'static>(
+    server: &P,
+    config: &Config,
+    num_workers: usize,
+    starting_tree: Option<SearchTree>,
+    board: &Board,
+    to_move: Color
+) -> Option<(f32, usize, SearchTree)>
+{
+    let is_full_search = config.full_search_prob >= 1.0 || rng::with(|rng| rng.gen::<f32>()) < config.full_search_prob;
+
+    if is_full_search {
+        let time_strategy = KlDivergence::new(config.num_rollout.into(), config.kl_threshold);
+        let starting_tree = match starting_tree {
+            Some(SearchTree::Full(tree)) => Some(tree),
+            _ => None
+        };
+        let (value, index, tree) = predict_aux::<_, _, StandardSearch>(server, config, num_workers, time_strategy, starting_tree, board, to_move)?;
+
+        Some((value, index, SearchTree::Full(tree)))
+    } else {
+        let time_strategy = RolloutLimit::new(config.num_cheap_rollout);
+        let starting_tree = match starting_tree {
+            Some(SearchTree::Cheap(tree)) => Some(tree),
+            _ => None
+        };
+        let (value, index, tree) = predict_aux::<_, _, CheapSearch>(server, config, num_workers, time_strategy, starting_tree, board, to_move)?;
+
+        Some((value, index, SearchTree::Cheap(tree)))
+    }
+}
 
 /// Play a game against the engine and return the result of the game.
 ///
 /// # Arguments
 ///
 /// * `server` - the server to use during evaluation
+/// * `config` - the configuration to play with
 /// * `num_parallel` - the number of games that are being played in parallel
 ///
-fn self_play_one<P: Predictor + 'static>(server: &P, num_parallel: &Arc<AtomicUsize>) -> Option<GameResult>
+fn self_play_one<P: Predictor + 'static>(server: &P, config: &Config, num_parallel: &Arc<AtomicUsize>) -> Option<GameResult>
 {
-    let mut board = Board::new(get_random_komi());
-    let mut sgf = String::new();
+    let mut board = Board::new(DEFAULT_SIZE, get_random_komi());
+    let mut moves = vec! [];
     let mut current = Color::Black;
     let mut pass_count = 0;
     let mut count = 0;
@@ -47,16 +127,16 @@ fn self_play_one<P: Predictor + 'static>(server: &P, num_parallel: &Arc<AtomicUs
     // limit the maximum number of moves to `2 * 19 * 19` to avoid the
     // engine playing pointless capture sequences at the end of the game
     // that does not change the final result.
-    let allow_resign = thread_rng().gen::<f32>() < 0.95;
+    let allow_resign = rng::with(|rng| rng.gen::<f32>()) < 0.95;
     let mut root_current = None;
-    let mut root_other = None;
+    let mut root_other: Option<SearchTree> = None;
 
     while count < 722 {
-        let num_workers = *config::NUM_THREADS / num_parallel.load(Ordering::Acquire);
-        let (value, index, tree) = predict_aux::<_, _, StandardSearch>(
+        let num_workers = config.num_threads / num_parallel.load(Ordering::Acquire);
+        let (value, index, tree) = search(
             server,
+            config,
             num_workers,
-            RolloutLimit::new((*config::NUM_ROLLOUT).into()),
             root_current,
             &board,
             current
@@ -65,45 +145,58 @@ fn self_play_one<P: Predictor + 'static>(server: &P, num_parallel: &Arc<AtomicUs
         debug_assert!(0.0 <= value && value <= 1.0);
         debug_assert!(index < 362);
 
-        let policy = tree.softmax();
+        // only record the policy of full searches, since that is what decides
+        // which moves are used as training examples
+        let policy = match tree {
+            SearchTree::Full(ref tree) => Some(b85::encode(&tree.softmax())),
+            SearchTree::Cheap(_) => None
+        };
         let (_, prior_index) = tree.prior();
         let value_sgf = if current == Color::Black { 2.0 * value - 1.0 } else { -2.0 * value + 1.0 };
 
         if allow_resign && value < 0.05 {  // resign the game if the evaluation looks bad
-            return Some(GameResult::Resign(sgf, board, current.opposite(), -value))
+            return Some(GameResult::Resign(moves, board, current.opposite(), -value))
         } else if index == 361 {  // passing move
-            sgf += &format!(";{}[]P[{}]V[{}]", current, b85::encode(&policy), value_sgf);
+            let mut mv = SgfMove::new(current, None);
+
+            if let Some(policy) = policy {
+                mv = mv.with("P", policy);
+            }
+
+            moves.push(mv.with("V", format!("{}", value_sgf)));
             pass_count += 1;
 
             if pass_count >= 2 {
-                return Some(GameResult::Ended(sgf, board))
+                return Some(GameResult::Ended(moves, board))
             }
         } else {
             let (x, y) = (tree::X[index] as usize, tree::Y[index] as usize);
 
-            sgf += &format!(";{}[{}]P[{}]V[{}]",
-                current,
-                CGoban::to_sgf(x, y),
-                b85::encode(&policy),
-                value_sgf
-            );
+            let mut mv = SgfMove::new(current, Some((x, y)));
+
+            if let Some(policy) = policy {
+                mv = mv.with("P", policy);
+            }
+
+            mv = mv.with("V", format!("{}", value_sgf));
+
             if prior_index != 361 {
-                sgf += &format!("TR[{}]",
-                    CGoban::to_sgf(
-                        tree::X[prior_index] as usize,
-                        tree::Y[prior_index] as usize
-                    )
-                );
+                mv = mv.with("TR", CGoban::to_sgf(
+                    tree::X[prior_index] as usize,
+                    tree::Y[prior_index] as usize
+                ));
             };
 
+            moves.push(mv);
+
             pass_count = 0;
             board.place(current, x, y);
         }
 
         // update the search trees
-        root_current = tree::Node::forward(tree, index);
+        root_current = tree.forward(index);
         root_other = if let Some(other) = root_other {
-            tree::Node::forward(other, index)
+            other.forward(index)
         } else {
             None
         };
@@ -114,7 +207,7 @@ fn self_play_one<P: Predictor + 'static>(server: &P, num_parallel: &Arc<AtomicUs
         count += 1;
     }
 
-    Some(GameResult::Ended(sgf, board))
+    Some(GameResult::Ended(moves, board))
 }
 
 /// Play games against the engine and return the result of the games
@@ -124,13 +217,15 @@ fn self_play_one<P: Predictor + 'static>(server: &P, num_parallel: &Arc<AtomicUs
 ///
 /// * `network` - the neural network to use during evaluation
 /// * `num_games` - the number of games to generate
+/// * `config` - the configuration to play with
 ///
-pub fn self_play(network: Network, num_games: usize) -> (Receiver<GameResult>, predict_service::PredictService) {
-    let server = predict_service::service(network);
+pub fn self_play(network: Network, num_games: usize, config: &Config) -> (Receiver<GameResult>, predict_service::PredictService) {
+    let server = predict_service::service(network, config);
+    let config = Arc::new(config.clone());
     let (sender, receiver) = channel();
 
     // spawn the worker threads that generate the self-play games
-    let num_parallel = ::std::cmp::min(num_games, *config::NUM_GAMES);
+    let num_parallel = ::std::cmp::min(num_games, config.num_games);
     let num_workers = Arc::new(AtomicUsize::new(num_parallel));
     let processed = Arc::new(AtomicUsize::new(0));
 
@@ -139,10 +234,21 @@ pub fn self_play(network: Network, num_games: usize) -> (Receiver<GameResult>, p
         let processed = processed.clone();
         let sender = sender.clone();
         let server = server.lock().clone_to_static();
+        let config = config.clone();
 
         thread::spawn(move || {
-            while processed.fetch_add(1, Ordering::SeqCst) < num_games {
-                if let Some(result) = self_play_one(&server, &num_workers) {
+            loop {
+                let index = processed.fetch_add(1, Ordering::SeqCst);
+
+                if index >= num_games {
+                    break
+                }
+
+                // make every game reproducible from its index, if the user asked
+                // for reproducible results
+                rng::reseed(config.seed, index as u64);
+
+                if let Some(result) = self_play_one(&server, &config, &num_workers) {
                     if sender.send(result).is_err() {
                         break
                     }
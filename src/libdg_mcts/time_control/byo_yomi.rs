@@ -15,7 +15,6 @@
 use super::{TimeStrategy, TimeStrategyResult};
 use options::SearchOptions;
 use tree;
-use dg_utils::config::SAFE_TIME_MS;
 
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -44,20 +43,20 @@ pub struct ByoYomi {
 }
 
 impl ByoYomi {
-    pub fn new(move_number: usize, starting_visits: i32, main_time: f32, byo_yomi_time: f32, byo_yomi_periods: usize) -> ByoYomi {
+    pub fn new(move_number: usize, starting_visits: i32, main_time: f32, byo_yomi_time: f32, byo_yomi_periods: usize, safe_time_ms: usize) -> ByoYomi {
         let main_time_ms = (990.0 * main_time) as usize;
         let byo_yomi_time_ms = (990.0 * byo_yomi_time) as usize;
 
         ByoYomi {
-            total_time_ms: (main_time_ms + byo_yomi_time_ms * byo_yomi_periods).saturating_sub(*SAFE_TIME_MS),
+            total_time_ms: (main_time_ms + byo_yomi_time_ms * byo_yomi_periods).saturating_sub(safe_time_ms),
             starting_visits: starting_visits,
             count: Arc::new(AtomicUsize::new(0)),
 
             start_time: Instant::now(),
             expire_time: Arc::new(AtomicUsize::new({
-                let safe_main_time_ms = main_time_ms.saturating_sub(*SAFE_TIME_MS);
+                let safe_main_time_ms = main_time_ms.saturating_sub(safe_time_ms);
                 let safe_byo_yomi_time_ms = if safe_main_time_ms == 0 {
-                    byo_yomi_time_ms.saturating_sub(*SAFE_TIME_MS)
+                    byo_yomi_time_ms.saturating_sub(safe_time_ms)
                 } else {
                     byo_yomi_time_ms
                 };
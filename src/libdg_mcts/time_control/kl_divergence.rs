@@ -0,0 +1,224 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{TimeStrategy, TimeStrategyResult};
+use options::SearchOptions;
+use tree;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The number of visits between two snapshots of the visit distribution.
+const SNAPSHOT_INTERVAL: i32 = 100;
+
+/// The visit distribution of the root of a search tree at some point in time.
+struct Snapshot {
+    /// The total number of visits to the root when the snapshot was taken.
+    total_count: i32,
+
+    /// The fraction of the visits that went to each child.
+    distribution: Vec<f32>
+}
+
+/// Stops the search once the visit distribution of the root has stopped
+/// changing, as measured by the _Kullback-Leibler divergence_ between two
+/// snapshots of it taken `SNAPSHOT_INTERVAL` visits apart, or once the root
+/// has reached the maximum number of visits.
+#[derive(Clone)]
+pub struct KlDivergence {
+    /// The maximum number of visits to the root.
+    limit: i32,
+
+    /// The divergence between two snapshots that the search stops at, or
+    /// zero if the search should always run until `limit`.
+    threshold: f32,
+
+    /// The most recent snapshot of the visit distribution.
+    snapshot: Arc<Mutex<Option<Snapshot>>>,
+
+    /// Whether the divergence between two snapshots has fallen below the
+    /// threshold.
+    is_converged: Arc<AtomicBool>
+}
+
+impl KlDivergence {
+    /// Returns a time strategy that stops once the divergence between two
+    /// snapshots is below `threshold`, or after `limit` visits.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - the maximum number of visits to the root
+    /// * `threshold` - the divergence to stop at, or zero to never stop early
+    ///
+    pub fn new(limit: usize, threshold: f32) -> KlDivergence {
+        KlDivergence {
+            limit: if limit > ::std::i32::MAX as usize {
+                ::std::i32::MAX - 1
+            } else {
+                limit as i32
+            },
+            threshold: threshold,
+            snapshot: Arc::new(Mutex::new(None)),
+            is_converged: Arc::new(AtomicBool::new(false))
+        }
+    }
+
+    /// Takes a new snapshot of the visit distribution of the given root if
+    /// enough visits has been added to it since the previous one, and checks
+    /// whether the divergence between them is below the threshold.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - the root of the search tree
+    ///
+    fn update<O: SearchOptions>(&self, root: &tree::Node<O>) {
+        // only one thread needs to take the snapshot, so do not wait for it
+        if let Ok(mut snapshot) = self.snapshot.try_lock() {
+            let total_count = root.total_count;
+            let is_due = match *snapshot {
+                Some(ref prev) => total_count >= prev.total_count + SNAPSHOT_INTERVAL,
+                None => true
+            };
+
+            if is_due {
+                let distribution = get_distribution(root);
+
+                if let Some(ref prev) = *snapshot {
+                    if kl_divergence(&distribution, &prev.distribution) < self.threshold {
+                        self.is_converged.store(true, Ordering::Release);
+                    }
+                }
+
+                *snapshot = Some(Snapshot {
+                    total_count: total_count,
+                    distribution: distribution
+                });
+            }
+        }
+    }
+}
+
+/// Returns the fraction of the visits that went to each child of the given
+/// root.
+///
+/// # Arguments
+///
+/// * `root` - the root of the search tree
+///
+fn get_distribution<O: SearchOptions>(root: &tree::Node<O>) -> Vec<f32> {
+    let mut distribution = vec! [0.0; 362];
+    let mut total = 0.0;
+
+    for i in root.children.nonzero() {
+        let count = root.with(i, |child| child.count()) as f32;
+
+        distribution[i] = count;
+        total += count;
+    }
+
+    if total > 0.0 {
+        for p in distribution.iter_mut() {
+            *p /= total;
+        }
+    }
+
+    distribution
+}
+
+/// Returns the Kullback-Leibler divergence of `q` from `p`, which is infinite
+/// if `q` is zero for some element where `p` is not.
+///
+/// # Arguments
+///
+/// * `p` - the new distribution
+/// * `q` - the old distribution
+///
+fn kl_divergence(p: &[f32], q: &[f32]) -> f32 {
+    p.iter().zip(q.iter())
+        .filter(|&(&p, _)| p > 0.0)
+        .map(|(&p, &q)| if q > 0.0 { p * (p / q).ln() } else { ::std::f32::INFINITY })
+        .sum()
+}
+
+impl TimeStrategy for KlDivergence {
+    fn try_extend<O: SearchOptions, F: Fn() -> bool>(
+        &self,
+        root: &tree::Node<O>,
+        _predicate: F,
+        _factor: f32
+    ) -> TimeStrategyResult
+    {
+        if self.threshold > 0.0 {
+            self.update(root);
+        }
+
+        if root.total_count >= self.limit || self.is_converged.load(Ordering::Acquire) {
+            TimeStrategyResult::Expired
+        } else {
+            TimeStrategyResult::NotExpired((self.limit - root.total_count) as usize)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dg_go::{Board, Color};
+    use dg_utils::config::{Config, Procedure};
+    use options::StandardSearch;
+    use predict;
+    use time_control::kl_divergence::*;
+
+    #[test]
+    fn divergence() {
+        let p = [0.5, 0.5, 0.0];
+
+        assert_eq!(kl_divergence(&p, &p), 0.0);
+        assert!((kl_divergence(&p, &[0.25, 0.75, 0.0]) - 0.1438).abs() < 1e-4);
+        assert!(kl_divergence(&p, &[1.0, 0.0, 0.0]).is_infinite());
+    }
+
+    #[test]
+    fn stops_early() {
+        let time_strategy = KlDivergence::new(2000, 0.05);
+        let (_value, _index, root) = predict::<_, _, StandardSearch>(
+            &predict::RandomPredictor::default(),
+            &Config::new(Procedure::Gtp),
+            None,
+            time_strategy.clone(),
+            None,
+            &Board::new(9, 7.5),
+            Color::Black
+        ).unwrap();
+
+        assert!(time_strategy.is_converged.load(Ordering::Acquire));
+        assert!(root.total_count < 2000);
+    }
+
+    #[test]
+    fn disabled() {
+        let time_strategy = KlDivergence::new(200, 0.0);
+        let (_value, _index, root) = predict::<_, _, StandardSearch>(
+            &predict::RandomPredictor::default(),
+            &Config::new(Procedure::Gtp),
+            None,
+            time_strategy.clone(),
+            None,
+            &Board::new(9, 7.5),
+            Color::Black
+        ).unwrap();
+
+        assert!(root.total_count > 0);
+        assert!(time_strategy.snapshot.lock().unwrap().is_none());
+    }
+}
@@ -13,9 +13,11 @@
 // limitations under the License.
 
 mod byo_yomi;
+mod kl_divergence;
 mod rollout_limit;
 
 pub use self::byo_yomi::*;
+pub use self::kl_divergence::*;
 pub use self::rollout_limit::*;
 
 use options::SearchOptions;
@@ -60,8 +62,8 @@ fn is_stable<O: SearchOptions>(root: &tree::Node<O>) -> bool {
     let max_wins = root.children.argmax_value();
 
     max_visits == max_wins || {
-        let max_value = root.children.with(max_wins, |child| child.value(), root.initial_value);
-        let other_value = root.children.with(max_visits, |child| child.value(), root.initial_value);
+        let max_value = root.with(max_wins, |child| child.value());
+        let other_value = root.with(max_visits, |child| child.value());
 
         max_value - other_value < 0.005  // within 0.025%
     }
@@ -81,15 +83,15 @@ fn min_promote_rollouts<O: SearchOptions>(root: &tree::Node<O>) -> usize {
     let mut top_2 = if top_1 == 0 { 1 } else { 0 };
 
     for i in root.children.nonzero() {
-        let count_i = root.children.with(i, |child| child.count(), root.initial_value);
+        let count_i = root.with(i, |child| child.count());
 
-        if i != top_1 && count_i > root.children.with(top_2, |child| child.count(), root.initial_value) {
+        if i != top_1 && count_i > root.with(top_2, |child| child.count()) {
             top_2 = i;
         }
     }
 
-    let count_1 = root.children.with(top_1, |child| child.count(), root.initial_value);
-    let count_2 = root.children.with(top_2, |child| child.count(), root.initial_value);
+    let count_1 = root.with(top_1, |child| child.count());
+    let count_2 = root.with(top_2, |child| child.count());
 
     if count_1 > count_2 {
         (count_1 - count_2) as usize
@@ -12,21 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use dg_go::utils::sgf::SgfCoordinate;
+use dg_go::utils::sgf::{GameInfo, SgfCoordinate, SgfWriter};
 use dg_go::{Board, Color};
 use dg_utils::lcb::normal_lcb_m;
-use dg_utils::{config, max};
+use dg_utils::config::SearchParams;
+use dg_utils::max;
+use super::arena::{Arena, ArenaBox};
 use super::asm::{argmax_f32, argmax_i32};
 use super::parallel::spin::Mutex;
 use super::parallel::global_rwlock;
+use super::parallel::reclaimer;
+use super::rng;
 use super::SearchOptions;
 
 use ordered_float::OrderedFloat;
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::mem::ManuallyDrop;
+use std::io::{self, Read, Write};
+use std::mem;
 use std::intrinsics::{atomic_xadd, atomic_xsub, atomic_cxchg, fmul_fast};
 use std::ptr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 lazy_static! {
     /// Mapping from policy index to the `x` coordinate it represents.
@@ -45,12 +53,12 @@ pub struct PUCT;
 
 impl PUCT {
     #[inline(always)]
-    unsafe fn get_big_impl<O: SearchOptions>(node: &Node<O>, big: &BigChildrenImpl<O>, value: &mut [f32]) {
+    unsafe fn get_big_impl<O: SearchOptions>(node: &Node<O>, big: &BigChildrenImpl<O>, value: &mut [f32], params: &SearchParams) {
         use std::intrinsics::{fadd_fast, fdiv_fast, fmul_fast};
 
         let n = node.total_count + node.vtotal_count;
         let sqrt_n = ((1 + n) as f32).sqrt();
-        let uct_exp = config::get_uct_exp(n);
+        let uct_exp = params.uct_exp(n);
         let uct_exp_sqrt_n = fmul_fast(uct_exp, sqrt_n);
 
         for i in 0..362 {
@@ -65,19 +73,19 @@ impl PUCT {
 
     #[allow(unused_attributes)]
     #[target_feature(enable = "avx,avx2")]
-    unsafe fn get_big_avx2<O: SearchOptions>(node: &Node<O>, big: &BigChildrenImpl<O>, value: &mut [f32]) {
-        PUCT::get_big_impl(node, big, value);
+    unsafe fn get_big_avx2<O: SearchOptions>(node: &Node<O>, big: &BigChildrenImpl<O>, value: &mut [f32], params: &SearchParams) {
+        PUCT::get_big_impl(node, big, value, params);
     }
 
     #[inline(always)]
-    unsafe fn get_small_impl<O: SearchOptions>(node: &Node<O>, small: &SmallChildrenImpl<O>, value: &mut [f32]) {
+    unsafe fn get_small_impl<O: SearchOptions>(node: &Node<O>, small: &SmallChildrenImpl<O>, value: &mut [f32], params: &SearchParams) {
         debug_assert!(SMALL_SIZE == 8);
 
         use std::intrinsics::{fadd_fast, fdiv_fast, fmul_fast};
 
         let n = node.total_count + node.vtotal_count;
         let sqrt_n = ((1 + n) as f32).sqrt();
-        let uct_exp = config::get_uct_exp(n);
+        let uct_exp = params.uct_exp(n);
         let uct_exp_sqrt_n = fmul_fast(uct_exp, sqrt_n);
 
         for i in 0..362 {
@@ -98,43 +106,60 @@ impl PUCT {
 
     #[allow(unused_attributes)]
     #[target_feature(enable = "avx,avx2")]
-    unsafe fn get_small_avx2<O: SearchOptions>(node: &Node<O>, small: &SmallChildrenImpl<O>, value: &mut [f32]) {
-        PUCT::get_small_impl(node, small, value);
+    unsafe fn get_small_avx2<O: SearchOptions>(node: &Node<O>, small: &SmallChildrenImpl<O>, value: &mut [f32], params: &SearchParams) {
+        PUCT::get_small_impl(node, small, value, params);
     }
 
-    /// Update the trace backwards with the given value (and color).
+    /// Update the trace backwards with the given value (and color). If the
+    /// search uses transpositions then the value of each edge is instead set
+    /// to the average value of the node it points to, since that node may
+    /// also have been visited through other parents.
     ///
     /// # Arguments
     ///
     /// * `trace` -
     /// * `color` -
     /// * `value` -
+    /// * `params` - the search parameters
     ///
     #[inline]
-    unsafe fn update<O: SearchOptions>(trace: &NodeTrace<O>, color: Color, value: f32) {
+    unsafe fn update<O: SearchOptions>(trace: &NodeTrace<O>, color: Color, value: f32, params: &SearchParams) {
         use std::intrinsics::{fadd_fast, fsub_fast, fdiv_fast};
 
-        for &(node, _, index) in trace.iter() {
+        // update from the leaf and up, so that the average value of every node
+        // already includes this visit when the edges pointing to it are updated.
+        for &(node, _, index) in trace.iter().rev() {
             let value_ = if color == (*node).to_move { value } else { 1.0 - value };
+            let next = if O::transpositions() {
+                (*node).with(index, |child| child.ptr())
+            } else {
+                ptr::null_mut()
+            };
 
             // incremental update of the average value and remove any additional
             // virtual losses we added to the node
             atomic_xadd(&mut (*node).total_count, 1);
-            atomic_xsub(&mut (*node).vtotal_count, *config::VLOSS_CNT);
+            atomic_xsub(&mut (*node).vtotal_count, params.vloss_cnt);
 
-            (*node).children.with_mut(index, |mut child| {
+            (*node).with_mut(index, |mut child| {
                 let _guard = (*node).lock.lock();
 
                 let prev_value = child.value();
                 let prev_value_s = child.value_s();
                 let prev_count = child.add_count(1);
-                let next_value = child.set_value(fadd_fast(
-                    prev_value,
-                    fdiv_fast(
-                        fsub_fast(value_, prev_value),
-                        (prev_count + 1) as f32
+                let next_value = child.set_value(if next.is_null() {
+                    fadd_fast(
+                        prev_value,
+                        fdiv_fast(
+                            fsub_fast(value_, prev_value),
+                            (prev_count + 1) as f32
+                        )
                     )
-                ));
+                } else if (*next).to_move == (*node).to_move {
+                    (*next).average_value()
+                } else {
+                    1.0 - (*next).average_value()
+                });
                 child.set_value_s(fadd_fast(
                     prev_value_s,
                     fmul_fast(
@@ -142,8 +167,8 @@ impl PUCT {
                         fsub_fast(value_, next_value)
                     )
                 ));
-                child.sub_vcount(*config::VLOSS_CNT);
-            }, (*node).initial_value);
+                child.sub_vcount(params.vloss_cnt);
+            });
         }
     }
 
@@ -153,21 +178,22 @@ impl PUCT {
     ///
     /// * `node` -
     /// * `value` - the winrates to use in the calculations
+    /// * `params` - the search parameters
     ///
     #[inline(always)]
-    fn get<O: SearchOptions>(node: &Node<O>, value: &mut [f32]) {
+    fn get<O: SearchOptions>(node: &Node<O>, value: &mut [f32], params: &SearchParams) {
         if is_x86_feature_detected!("avx2") {
             unsafe {
                 match node.children {
-                    ChildrenImpl::Small(ref small) => PUCT::get_small_avx2(node, small, value),
-                    ChildrenImpl::Big(ref big) => PUCT::get_big_avx2(node, big, value),
+                    ChildrenImpl::Small(ref small) => PUCT::get_small_avx2(node, small, value, params),
+                    ChildrenImpl::Big(ref big) => PUCT::get_big_avx2(node, big, value, params),
                 }
             }
         } else {
             unsafe {
                 match node.children {
-                    ChildrenImpl::Small(ref small) => PUCT::get_small_impl(node, small, value),
-                    ChildrenImpl::Big(ref big) => PUCT::get_big_impl(node, big, value),
+                    ChildrenImpl::Small(ref small) => PUCT::get_small_impl(node, small, value, params),
+                    ChildrenImpl::Big(ref big) => PUCT::get_big_impl(node, big, value, params),
                 }
             }
         }
@@ -315,17 +341,18 @@ impl<O: SearchOptions> Child<O> {
     ///
     /// # Arguments
     ///
+    /// * `arena` - the arena that the child node is allocated in
     /// * `small` - the `SmallChildrenImpl` to initialize from
     /// * `index` - the sparse index in `SmallChildrenImpl` to initialize from
     ///
-    fn from_small(small: &SmallChildrenImpl<O>, index: usize) -> Child<O> {
+    fn from_small(arena: &NodeArena<O>, small: &SmallChildrenImpl<O>, index: usize) -> Child<O> {
         Child {
             count: small.count[index],
             vcount: small.vcount[index],
             value: small.value[index],
             value_s: small.value_s[index],
             expanding: small.expanding[index],
-            ptr: small.ptr[index]
+            ptr: arena.node(small.node[index])
         }
     }
 
@@ -333,10 +360,11 @@ impl<O: SearchOptions> Child<O> {
     ///
     /// # Arguments
     ///
+    /// * `arena` - the arena that the child node is allocated in
     /// * `big` - the `BigChildrenImpl` to initialize from
     /// * `index` - the dense index in `BigChildrenImpl` to initialize from
     ///
-    fn from_big(big: &BigChildrenImpl<O>, index: usize) -> Child<O> {
+    fn from_big(arena: &NodeArena<O>, big: &BigChildrenImpl<O>, index: usize) -> Child<O> {
         debug_assert!(index < 362, "{}", index);
 
         Child {
@@ -345,7 +373,7 @@ impl<O: SearchOptions> Child<O> {
             value: big.value[index],
             value_s: big.value_s[index],
             expanding: big.expanding[index],
-            ptr: big.ptr[index]
+            ptr: arena.node(big.node[index])
         }
     }
 
@@ -391,40 +419,44 @@ pub struct ChildMut<O: SearchOptions> {
     expanding: *mut bool,
     count: *mut i32,
     vcount: *mut i16,
-    ptr: *mut *mut Node<O>,
+    node: *mut u32,
+    arena: *const NodeArena<O>,
     value: *mut f32,
     value_s: *mut f32
 }
 
 impl<O: SearchOptions> ChildMut<O> {
     /// Returns a child that is initialized from a `SmallChildrenImpl` at the given `index`. The
-    /// given `small` node must outlive the returned `ChildMut`.
+    /// given `small` node, and `arena`, must outlive the returned `ChildMut`.
     ///
     /// # Arguments
     ///
+    /// * `arena` - the arena that the child node is allocated in
     /// * `small` - the `SmallChildrenImpl` to initialize from
     /// * `index` - the sparse index in `SmallChildrenImpl` to initialize from
     ///
-    unsafe fn from_small(small: &mut SmallChildrenImpl<O>, index: usize) -> ChildMut<O> {
+    unsafe fn from_small(arena: &NodeArena<O>, small: &mut SmallChildrenImpl<O>, index: usize) -> ChildMut<O> {
         ChildMut {
             count: small.count.get_unchecked_mut(index),
             vcount: small.vcount.get_unchecked_mut(index),
             value: small.value.get_unchecked_mut(index),
             value_s: small.value_s.get_unchecked_mut(index),
             expanding: small.expanding.get_unchecked_mut(index),
-            ptr: small.ptr.get_unchecked_mut(index)
+            node: small.node.get_unchecked_mut(index),
+            arena: arena
         }
     }
 
     /// Returns a child that is initialized from a `BigChildrenImpl` at the given `index`. The
-    /// given `big` node must outlive the returned `ChildMut`.
+    /// given `big` node, and `arena`, must outlive the returned `ChildMut`.
     ///
     /// # Arguments
     ///
+    /// * `arena` - the arena that the child node is allocated in
     /// * `big` - the `BigChildrenImpl` to initialize from
     /// * `index` - the dense index in `BigChildrenImpl` to initialize from
     ///
-    unsafe fn from_big(big: &mut BigChildrenImpl<O>, index: usize) -> ChildMut<O> {
+    unsafe fn from_big(arena: &NodeArena<O>, big: &mut BigChildrenImpl<O>, index: usize) -> ChildMut<O> {
         debug_assert!(index < 362);
 
         ChildMut {
@@ -433,7 +465,8 @@ impl<O: SearchOptions> ChildMut<O> {
             value: big.value.get_unchecked_mut(index),
             value_s: big.value_s.get_unchecked_mut(index),
             expanding: big.expanding.get_unchecked_mut(index),
-            ptr: big.ptr.get_unchecked_mut(index)
+            node: big.node.get_unchecked_mut(index),
+            arena: arena
         }
     }
 
@@ -454,7 +487,13 @@ impl<O: SearchOptions> ChildMut<O> {
 
     /// Return the child node itself.
     pub fn ptr(&self) -> *mut Node<O> {
-        unsafe { *self.ptr }
+        unsafe { (*self.arena).node(*self.node) }
+    }
+
+    /// Returns the index of the child node in the arena of the tree, or zero
+    /// if it has not been expanded.
+    fn index(&self) -> u32 {
+        unsafe { *self.node }
     }
 
     /// Returns the average value of this child.
@@ -525,15 +564,15 @@ impl<O: SearchOptions> ChildMut<O> {
         unsafe { atomic_xsub(self.vcount, count as i16) }
     }
 
-    /// Sets the actual child node. If there is already a child node set, then you
-    /// are responsible for freeing the old node.
+    /// Sets the actual child node, as an index into the arena of the tree. An
+    /// index of zero removes the child node.
     ///
     /// # Arguments
     ///
-    /// * `value` - the new child `Node`
+    /// * `value` - the index of the new child `Node`
     ///
-    fn set_ptr(&mut self, value: *mut Node<O>) {
-        unsafe { *self.ptr = value; }
+    fn set_index(&mut self, value: u32) {
+        unsafe { *self.node = value; }
     }
 
     /// Sets the average value of this child.
@@ -580,18 +619,11 @@ pub struct BigChildrenImpl<O: SearchOptions> {
     /// times by different threads.
     expanding: [bool; 362],
 
-    /// The sub-tree that each edge points towards.
-    ptr: [*mut Node<O>; 362]
-}
+    /// The index (in the arena of the tree) of the sub-tree that each edge
+    /// points towards, or zero if it has not been expanded.
+    node: [u32; 362],
 
-impl<O: SearchOptions> Drop for BigChildrenImpl<O> {
-    fn drop(&mut self) {
-        for &child in self.ptr.iter() {
-            if !child.is_null() {
-                unsafe { Box::from_raw(child); }
-            }
-        }
-    }
+    _phantom: ::std::marker::PhantomData<O>
 }
 
 impl<O: SearchOptions> BigChildrenImpl<O> {
@@ -609,7 +641,8 @@ impl<O: SearchOptions> BigChildrenImpl<O> {
             value: [value; 368],
             value_s: [0.0; 368],
             expanding: [false; 362],
-            ptr: [ptr::null_mut(); 362]
+            node: [0; 362],
+            _phantom: ::std::marker::PhantomData
         };
 
         for (index, &other) in small.indices.iter().enumerate() {
@@ -621,7 +654,7 @@ impl<O: SearchOptions> BigChildrenImpl<O> {
                 big.value[other] = small.value[index];
                 big.value_s[other] = small.value_s[index];
                 big.expanding[other] = small.expanding[index];
-                big.ptr[other] = small.ptr[index];
+                big.node[other] = small.node[index];
             }
         }
 
@@ -668,21 +701,14 @@ pub struct SmallChildrenImpl<O: SearchOptions> {
     /// times by different threads.
     expanding: [bool; SMALL_SIZE],
 
-    /// The sub-tree that each edge points towards.
-    ptr: [*mut Node<O>; SMALL_SIZE],
+    /// The index (in the arena of the tree) of the sub-tree that each edge
+    /// points towards, or zero if it has not been expanded.
+    node: [u32; SMALL_SIZE],
 
     /// Indices of the children stored in this node.
-    indices: [i16; SMALL_SIZE]
-}
+    indices: [i16; SMALL_SIZE],
 
-impl<O: SearchOptions> Drop for SmallChildrenImpl<O> {
-    fn drop(&mut self) {
-        for &child in self.ptr.iter() {
-            if !child.is_null() {
-                unsafe { Box::from_raw(child); }
-            }
-        }
-    }
+    _phantom: ::std::marker::PhantomData<O>
 }
 
 impl<O: SearchOptions> SmallChildrenImpl<O> {
@@ -699,8 +725,9 @@ impl<O: SearchOptions> SmallChildrenImpl<O> {
             value: [value; SMALL_SIZE],
             value_s: [0.0; SMALL_SIZE],
             expanding: [false; SMALL_SIZE],
-            ptr: [ptr::null_mut(); SMALL_SIZE],
-            indices: [::std::i16::MIN; SMALL_SIZE]
+            node: [0; SMALL_SIZE],
+            indices: [::std::i16::MIN; SMALL_SIZE],
+            _phantom: ::std::marker::PhantomData
         }
     }
 
@@ -783,10 +810,42 @@ impl Iterator for ChildrenNonZeroIter {
     }
 }
 
-/// Union of `SmallChildrenImpl` and `BigChildrenImpl`, where the later is stored on the heap.
+/// Iterator over the children of a node that has been expanded.
+struct ChildrenExpandedIter {
+    node: *const u32,
+    indices: *const i16,
+    index: usize,
+    len: usize
+}
+
+impl Iterator for ChildrenExpandedIter {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        unsafe {
+            while self.index < self.len {
+                let prev_index = self.index;
+                self.index += 1;
+
+                if *self.node.add(prev_index) != 0 {
+                    return Some(if self.indices.is_null() {
+                        prev_index
+                    } else {
+                        *self.indices.add(prev_index) as usize
+                    });
+                }
+            }
+
+            None
+        }
+    }
+}
+
+/// Union of `SmallChildrenImpl` and `BigChildrenImpl`, where the later is stored in the
+/// arena of the tree.
 pub enum ChildrenImpl<O: SearchOptions> {
-    Small(ManuallyDrop<SmallChildrenImpl<O>>),
-    Big(Box<BigChildrenImpl<O>>)
+    Small(SmallChildrenImpl<O>),
+    Big(ArenaBox<BigChildrenImpl<O>>)
 }
 
 unsafe impl<O: SearchOptions> Send for ChildrenImpl<O> {}
@@ -870,23 +929,48 @@ impl<O: SearchOptions> ChildrenImpl<O> {
         }
     }
 
+    /// Returns an iterator over all children that has a sub-tree. This can
+    /// include children that has not been visited yet when transpositions
+    /// are enabled.
+    fn expanded(&self) -> ChildrenExpandedIter {
+        match self {
+            ChildrenImpl::Small(ref small) => {
+                ChildrenExpandedIter {
+                    node: &small.node as *const u32,
+                    indices: &small.indices as *const i16,
+                    index: 0,
+                    len: SMALL_SIZE
+                }
+            },
+            ChildrenImpl::Big(ref big) => {
+                ChildrenExpandedIter {
+                    node: &big.node as *const u32,
+                    indices: ptr::null(),
+                    index: 0,
+                    len: 362
+                }
+            }
+        }
+    }
+
     /// Returns the result of the given callback, and being called with an immutable
     /// reference for the child for index.
     ///
     /// # Arguments
     ///
+    /// * `arena` - the arena that the child nodes are allocated in
     /// * `index` -
     /// * `callback` -
     /// * `initial_value` -
     ///
-    pub fn with<T, F>(&self, index: usize, callback: F, initial_value: f32) -> T
+    fn with<T, F>(&self, arena: &NodeArena<O>, index: usize, callback: F, initial_value: f32) -> T
         where F: FnOnce(Child<O>) -> T
     {
         callback(match self {
             ChildrenImpl::Small(ref small) => {
                 match small.find_index(index) {
                     SmallChildrenResult::Found(other) => {
-                        Child::from_small(small, other)
+                        Child::from_small(arena, small, other)
                     },
                     _ => {
                         Child::with_value(initial_value)
@@ -894,7 +978,7 @@ impl<O: SearchOptions> ChildrenImpl<O> {
                 }
             },
             ChildrenImpl::Big(ref big) => {
-                Child::from_big(big, index)
+                Child::from_big(arena, big, index)
             }
         })
     }
@@ -904,14 +988,15 @@ impl<O: SearchOptions> ChildrenImpl<O> {
     ///
     /// # Arguments
     ///
+    /// * `arena` - the arena that the child nodes are allocated in
     /// * `small` - the children implementation
     /// * `index` - the index to fetch
     ///
-    fn with_mut_small(small: &mut SmallChildrenImpl<O>, index: usize) -> Option<ChildMut<O>> {
+    fn with_mut_small(arena: &NodeArena<O>, small: &mut SmallChildrenImpl<O>, index: usize) -> Option<ChildMut<O>> {
         'retry: loop {
             return match small.find_index(index) {
                 SmallChildrenResult::Found(other) => {
-                    Some(unsafe { ChildMut::from_small(small, other) })
+                    Some(unsafe { ChildMut::from_small(arena, small, other) })
                 },
                 SmallChildrenResult::NotFound(other) => {
                     unsafe {
@@ -922,7 +1007,7 @@ impl<O: SearchOptions> ChildrenImpl<O> {
                         }
                     }
 
-                    Some(unsafe { ChildMut::from_small(small, other) })
+                    Some(unsafe { ChildMut::from_small(arena, small, other) })
                 },
                 SmallChildrenResult::Overflow => {
                     None
@@ -936,19 +1021,20 @@ impl<O: SearchOptions> ChildrenImpl<O> {
     ///
     /// # Arguments
     ///
+    /// * `arena` - the arena that the child nodes are allocated in
     /// * `index` -
     /// * `callback` -
     /// * `initial_value` -
     ///
-    pub fn with_mut<T, F>(&mut self, index: usize, callback: F, initial_value: f32) -> T
+    fn with_mut<T, F>(&mut self, arena: &NodeArena<O>, index: usize, callback: F, initial_value: f32) -> T
         where F: FnOnce(ChildMut<O>) -> T
     {
         let child = match self {
             ChildrenImpl::Small(ref mut small) => {
-                ChildrenImpl::with_mut_small(small, index)
+                ChildrenImpl::with_mut_small(arena, small, index)
             },
             ChildrenImpl::Big(ref mut big) => {
-                Some(unsafe { ChildMut::from_big(big, index) })
+                Some(unsafe { ChildMut::from_big(arena, big, index) })
             }
         };
 
@@ -960,17 +1046,127 @@ impl<O: SearchOptions> ChildrenImpl<O> {
                     *self = ChildrenImpl::Big(match self {
                         ChildrenImpl::Big(ref _big) => { return },
                         ChildrenImpl::Small(ref small) => {
-                            Box::new(BigChildrenImpl::from_small(small, initial_value))
+                            arena.alloc_big(BigChildrenImpl::from_small(small, initial_value))
                         }
                     });
                 }
             });
 
-            self.with_mut(index, callback, initial_value)
+            self.with_mut(arena, index, callback, initial_value)
+        }
+    }
+}
+
+/// The memory of a search tree. Every node in the tree, except the root, and
+/// every dense children array is allocated from here, and freed all at once
+/// when the last root that uses it is dropped.
+struct NodeArena<O: SearchOptions> {
+    nodes: Arena<Node<O>>,
+    big: Arena<BigChildrenImpl<O>>,
+
+    /// Mapping from the key of a position to the index of the node that
+    /// represents it, if the search uses transpositions.
+    transpositions: ::std::sync::Mutex<HashMap<u64, u32>>,
+
+    /// The number of bytes in this arena that are known to belong to
+    /// discarded sub-trees, which are no longer reachable from the root.
+    discarded: AtomicUsize
+}
+
+impl<O: SearchOptions> NodeArena<O> {
+    /// Returns an empty arena.
+    fn new() -> NodeArena<O> {
+        NodeArena {
+            nodes: Arena::new(),
+            big: Arena::new(),
+            transpositions: ::std::sync::Mutex::new(HashMap::new()),
+            discarded: AtomicUsize::new(0)
         }
     }
+
+    /// Returns the number of bytes used by this arena.
+    fn memory_usage(&self) -> usize {
+        self.nodes.memory_usage() + self.big.memory_usage()
+    }
+
+    /// Adds the given node to this arena and returns its index.
+    ///
+    /// # Arguments
+    ///
+    /// * `node` - the node to add
+    ///
+    fn alloc_node(&self, node: Node<O>) -> u32 {
+        self.nodes.alloc(node)
+    }
+
+    /// Returns the node with the given index, or a null pointer if the index
+    /// is zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the index of the node
+    ///
+    #[inline]
+    fn node(&self, index: u32) -> *mut Node<O> {
+        self.nodes.get(index)
+    }
+
+    /// Adds the given dense children array to this arena.
+    ///
+    /// # Arguments
+    ///
+    /// * `big` - the dense children array to add
+    ///
+    fn alloc_big(&self, big: BigChildrenImpl<O>) -> ArenaBox<BigChildrenImpl<O>> {
+        unsafe { ArenaBox::from_index(&self.big, self.big.alloc(big)) }
+    }
+
+    /// Returns the index of the node that represents the position with the
+    /// given key, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the key of the position
+    ///
+    fn transposition(&self, key: u64) -> Option<u32> {
+        self.transpositions.lock().unwrap().get(&key).cloned()
+    }
+
+    /// Sets the node with the given index as the one that represents the
+    /// position with the given key, unless some other node already does.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the key of the position
+    /// * `index` - the index of the node
+    ///
+    fn register(&self, key: u64, index: u32) {
+        self.transpositions.lock().unwrap().entry(key).or_insert(index);
+    }
 }
 
+/// Returns the key that identifies the position on the given board, with the
+/// given color to move, in the transposition table of a search tree. The
+/// number of moves played is part of the key, which makes it impossible for
+/// the search graph to contain any cycles.
+///
+/// # Arguments
+///
+/// * `board` - the board position
+/// * `to_move` - the color whose turn it is to play
+///
+fn transposition_key(board: &Board, to_move: Color) -> u64 {
+    let to_move_key = if to_move == Color::Black { 0x5be0_cd19_137e_2179 } else { 0x1f83_d9ab_fb41_bd6b };
+    let count_key = (board.count() as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+
+    board.zobrist_hash() ^ board.ko_hash(to_move) ^ to_move_key ^ count_key
+}
+
+/// The minimum number of bytes that the discarded sub-trees in an arena must
+/// use before `Node::forward` frees them. This avoids copying small trees on
+/// every move, when there is almost nothing to gain from it.
+const MIN_COMPACT_MEMORY: usize = 4 * 1024 * 1024;
+
 /// A monte carlo search tree.
 #[repr(align(64))]
 pub struct Node<O: SearchOptions> {
@@ -996,13 +1192,26 @@ pub struct Node<O: SearchOptions> {
     pub prior: [f32; 368],
 
     /// The sparse (or dense) representation of the remaining MCTS fields.
-    pub children: ChildrenImpl<O>
+    pub children: ChildrenImpl<O>,
+
+    /// The key of the position this node represents, if it has been added to
+    /// the transpositions of its arena, or zero otherwise.
+    key: u64,
+
+    /// The arena that the children of this node are allocated in.
+    arena: *const NodeArena<O>,
+
+    /// The arena of this tree, if this node is the root of a tree. Nodes that
+    /// are stored inside of an arena never owns it.
+    owner: Option<Arc<NodeArena<O>>>
 }
 
+unsafe impl<O: SearchOptions> Send for Node<O> {}
+
 impl<O: SearchOptions> Drop for Node<O> {
     fn drop(&mut self) {
-        if let ChildrenImpl::Small(ref mut small) = self.children {
-            unsafe { ManuallyDrop::drop(small) }
+        if let Some(owner) = self.owner.take() {
+            unsafe { reclaimer::reclaim(Box::new(owner)) };
         }
     }
 }
@@ -1017,6 +1226,23 @@ impl<O: SearchOptions> Node<O> {
     /// * `prior` - the prior values of the nodes
     ///
     pub fn new(to_move: Color, value: f32, prior: Vec<f32>) -> Node<O> {
+        let arena = Arc::new(NodeArena::new());
+        let mut root = Node::new_in(&*arena, to_move, value, prior);
+
+        root.owner = Some(arena);
+        root
+    }
+
+    /// Returns an empty search tree with the given starting color and prior
+    /// values, whose children are allocated in the given arena.
+    ///
+    /// # Arguments
+    ///
+    /// * `arena` - the arena to allocate children in
+    /// * `to_move` - the color of the first players color
+    /// * `prior` - the prior values of the nodes
+    ///
+    fn new_in(arena: *const NodeArena<O>, to_move: Color, value: f32, prior: Vec<f32>) -> Node<O> {
         assert!(prior.len() >= 362);
 
         // copy the prior values into an array size that is dividable
@@ -1032,10 +1258,19 @@ impl<O: SearchOptions> Node<O> {
             total_count: 0,
             vtotal_count: 0,
             prior: prior_padding,
-            children: ChildrenImpl::Small(ManuallyDrop::new(SmallChildrenImpl::with_value(value)))
+            children: ChildrenImpl::Small(SmallChildrenImpl::with_value(value)),
+            key: 0,
+            arena: arena,
+            owner: None
         }
     }
 
+    /// Returns the arena that the children of this node are allocated in.
+    #[inline]
+    fn arena(&self) -> &NodeArena<O> {
+        unsafe { &*self.arena }
+    }
+
     /// Returns true if the given vertex is a valid candidate move in this tree.
     ///
     /// # Arguments
@@ -1065,6 +1300,184 @@ impl<O: SearchOptions> Node<O> {
         self.total_count as usize
     }
 
+    /// Returns the average value of this node, from the perspective of the
+    /// player whose turn it is. This is the initial value of the node, and
+    /// the values of all of its children weighted by their number of visits.
+    fn average_value(&self) -> f32 {
+        let mut total_value = self.initial_value;
+        let mut total_count = 1;
+
+        for i in self.children.nonzero() {
+            let (count, value) = self.with(i, |child| (child.count(), child.value()));
+
+            total_value += count as f32 * value;
+            total_count += count;
+        }
+
+        total_value / total_count as f32
+    }
+
+    /// Returns the number of bytes used by this search tree, including all
+    /// of its sub-trees and any sub-trees that has been discarded but not yet
+    /// freed.
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Node<O>>() + self.arena().memory_usage()
+    }
+
+    /// Returns an upper bound on the number of bytes used by the nodes that
+    /// are reachable from this node. Unlike `live_memory_usage` this does not
+    /// need to walk the tree, so it is cheap enough to check during a search.
+    pub fn live_memory_estimate(&self) -> usize {
+        self.memory_usage().saturating_sub(self.arena().discarded.load(Ordering::Relaxed))
+    }
+
+    /// Returns the number of bytes used by the nodes that are reachable from
+    /// this node.
+    fn live_memory_usage(&self) -> usize {
+        let mut total = 0;
+        let mut visited = HashSet::new();
+        let mut remaining = vec! [self as *const Node<O>];
+
+        while let Some(node) = remaining.pop() {
+            let node = unsafe { &*node };
+
+            total += mem::size_of::<Node<O>>();
+            if let ChildrenImpl::Big(_) = node.children {
+                total += mem::size_of::<BigChildrenImpl<O>>();
+            }
+
+            for i in node.children.expanded() {
+                let child = node.with(i, |child| child.ptr());
+
+                if visited.insert(child) {
+                    remaining.push(child);
+                }
+            }
+        }
+
+        total
+    }
+
+    /// Moves every node that is reachable from this root into a new arena, and
+    /// frees the old arena in the background. This removes any discarded
+    /// sub-trees, and puts the remaining nodes closer together in memory.
+    ///
+    /// This must not be called while any other thread is using the tree.
+    fn compact(&mut self) {
+        let arena = Arc::new(NodeArena::new());
+        let old_arena = self.arena;
+        let mut moved = vec! [0; self.arena().nodes.len() + 1];
+        let mut remaining = vec! [self as *mut Node<O>];
+
+        while let Some(node) = remaining.pop() {
+            let node = unsafe { &mut *node };
+
+            if let ChildrenImpl::Big(ref mut big) = node.children {
+                *big = arena.alloc_big(unsafe { ptr::read(&**big) });
+            }
+
+            node.arena = &*arena;
+
+            for i in node.children.expanded() {
+                node.with_mut(i, |mut child| {
+                    let index = child.index() as usize;
+
+                    // a node that is shared between several parents (because of
+                    // transpositions) should only be moved once.
+                    if moved[index] == 0 {
+                        let next = unsafe { ptr::read((*old_arena).node(index as u32)) };
+                        let next_key = next.key;
+                        let next_index = arena.alloc_node(next);
+
+                        if next_key != 0 {
+                            arena.register(next_key, next_index);
+                        }
+
+                        moved[index] = next_index;
+                        remaining.push(arena.node(next_index));
+                    }
+
+                    child.set_index(moved[index]);
+                });
+            }
+        }
+
+        // the old arena is still referenced by `owner`, so it is only freed
+        // after everything has been copied out of it.
+        if let Some(owner) = mem::replace(&mut self.owner, Some(arena)) {
+            unsafe { reclaimer::reclaim(Box::new(owner)) };
+        }
+    }
+
+    /// Remove the least visited sub-trees of this search tree until it uses at
+    /// most the given number of bytes. The statistics of the removed edges are
+    /// kept, so they will be expanded again if they are visited. Returns the
+    /// number of bytes that were freed.
+    ///
+    /// This must not be called while any other thread is using the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_memory` - the maximum number of bytes this tree may use
+    ///
+    pub fn prune(&mut self, max_memory: usize) -> usize {
+        let before = self.memory_usage();
+        let mut memory = self.live_memory_usage();
+        let mut edges = vec! [];
+        let mut num_parents = HashMap::new();
+        let mut remaining = vec! [(self as *mut Node<O>, 0)];
+
+        while let Some((node, depth)) = remaining.pop() {
+            let node = unsafe { &mut *node };
+
+            for i in node.children.expanded() {
+                let (count, child) = node.with(i, |child| (child.count(), child.ptr()));
+                let num_parents = num_parents.entry(child).or_insert(0);
+
+                edges.push((count, depth, node as *mut Node<O>, i));
+                *num_parents += 1;
+                if *num_parents == 1 {
+                    remaining.push((child, depth + 1));
+                }
+            }
+        }
+
+        // since the number of visits to a child is never larger than the number of
+        // visits to its parent, this order removes every descendant of a node
+        // before the node itself. A node that is shared between several parents is
+        // only freed once every edge to it has been removed, so its memory is only
+        // subtracted once the last of them is removed. Its sub-tree can still share
+        // nodes with the rest of the tree, so the amount of freed memory can still
+        // be over-estimated.
+        edges.sort_by_key(|&(count, depth, _, _)| (count, -depth));
+
+        for (_, _, node, index) in edges {
+            if memory <= max_memory {
+                break
+            }
+
+            unsafe {
+                let child = (*node).with_mut(index, |mut child| {
+                    let ptr = child.ptr();
+
+                    child.set_index(0);
+                    child.unset_expanding();
+                    ptr
+                });
+
+                let num_parents = num_parents.get_mut(&child).unwrap();
+                *num_parents -= 1;
+
+                if *num_parents == 0 {
+                    memory = memory.saturating_sub((*child).live_memory_usage());
+                }
+            }
+        }
+
+        self.compact();
+        before - self.memory_usage()
+    }
+
     /// Returns the result of the given callback, and being called with an immutable
     /// reference for the child for index.
     ///
@@ -1076,7 +1489,7 @@ impl<O: SearchOptions> Node<O> {
     pub fn with<T, F>(&self, index: usize, callback: F) -> T
         where F: FnOnce(Child<O>) -> T
     {
-        self.children.with(index, callback, self.initial_value)
+        self.children.with(self.arena(), index, callback, self.initial_value)
     }
 
     /// Returns the result of the given callback, and being called with an mutable
@@ -1090,47 +1503,32 @@ impl<O: SearchOptions> Node<O> {
     pub fn with_mut<T, F>(&mut self, index: usize, callback: F) -> T
         where F: FnOnce(ChildMut<O>) -> T
     {
-        self.children.with_mut(index, callback, self.initial_value)
+        let arena = unsafe { &*self.arena };
+
+        self.children.with_mut(arena, index, callback, self.initial_value)
     }
 
-    fn as_sgf<S: SgfCoordinate>(&self, fmt: &mut fmt::Formatter, meta: bool) -> fmt::Result {
+    fn as_sgf<S: SgfCoordinate>(&self, writer: &mut SgfWriter<S>, params: &SearchParams, meta: bool) {
         // annotate the top-10 moves to make it easier to navigate for the
         // user.
         let mut children = (0..362).collect::<Vec<usize>>();
         children.sort_by_key(|&i| -self.with(i, |child| child.count()));
 
         if meta {
-            for i in 0..10 {
-                let j = children[i];
-
-                if j != 361 && self.with(j, |child| child.count()) > 0 {
-                    lazy_static! {
-                        static ref LABELS: Vec<&'static str> = vec! [
-                            "1", "2", "3", "4", "5", "6", "7", "8", "9", "10"
-                        ];
-                    }
+            let labels = (0..10)
+                .filter(|&i| children[i] != 361 && self.with(children[i], |child| child.count()) > 0)
+                .map(|i| {
+                    let j = children[i];
 
-                    write!(fmt, "LB[{}:{}]",
-                        S::to_sgf(X[j] as usize, Y[j] as usize),
-                        LABELS[i]
-                    )?;
-                }
-            }
+                    format!("{}:{}", S::to_sgf(X[j] as usize, Y[j] as usize), i + 1)
+                })
+                .collect::<Vec<_>>();
 
-            // mark all valid moves with a triangle (for debugging the symmetry code)
-            /*
-            for i in 0..361 {
-                if self.prior[i].is_finite() {
-                    write!(fmt, "TR[{}]",
-                        S::to_sgf(X[j] as usize, Y[j] as usize),
-                    )?;
-                }
-            }
-            */
+            writer.property_list("LB", &labels);
         }
 
         let mut uct = self.children.value(self.initial_value);
-        PUCT::get(self, &mut uct);
+        PUCT::get(self, &mut uct, params);
 
         for i in children {
             // do not output nodes that has not been visited to reduce the
@@ -1139,64 +1537,87 @@ impl<O: SearchOptions> Node<O> {
                 continue;
             }
 
-            write!(fmt, "(")?;
-            write!(fmt, ";{}[{}]",
-                   if self.to_move == Color::Black { "B" } else { "W" },
-                   if i == 361 { "tt".to_string() } else { S::to_sgf(X[i] as usize, Y[i] as usize) },
-            )?;
-            write!(fmt, "C[prior {:.4} value {:.4} (visits {} / total {}) uct {:.4}]",
+            let vertex = if i == 361 { None } else { Some((X[i] as usize, Y[i] as usize)) };
+
+            writer.open().node().play(self.to_move, vertex);
+            writer.comment(&format!("prior {:.4} value {:.4} (visits {} / total {}) uct {:.4}",
                 self.prior[i],
                 self.with(i, |child| child.value()),
                 self.with(i, |child| child.count()),
                 self.total_count,
                 uct[i]
-            )?;
+            ));
 
             unsafe {
                 let child = self.with(i, |child| child.ptr());
 
                 if !child.is_null() {
-                    (*child).as_sgf::<S>(fmt, meta)?;
+                    (*child).as_sgf::<S>(writer, params, meta);
                 }
             }
 
-            write!(fmt, ")")?;
+            writer.close();
         }
-
-        Ok(())
     }
 
     /// Returns the sub-tree that contains the exploration of the given move index.
+    /// The remaining sub-trees are freed in the background.
     ///
     /// # Arguments
     ///
     /// * `self` - the search tree to pluck the child from
     /// * `index` - the move to pluck the sub-tree for
     ///
-    pub fn forward(mut self, index: usize) -> Option<Node<O>> {
-        let color = self.to_move;
-        let pass_count = self.pass_count;
+    pub fn forward(self, index: usize) -> Option<Node<O>> {
+        self.forward_with(index, MIN_COMPACT_MEMORY)
+    }
 
-        self.with_mut(index, |mut child| {
-            if child.ptr().is_null() {
-                if index == 361 {
-                    // we need to record that were was a pass so that we have the correct
-                    // pass count in the root node.
-                    let prior = vec! [0.0f32; 362];
-                    let mut next = Node::new(color.opposite(), 0.5, prior);
-                    next.pass_count = pass_count + 1;
-
-                    Some(next)
-                } else {
-                    None
-                }
-            } else {
-                let next = child.ptr();
-                child.set_ptr(ptr::null_mut());
+    /// Returns the sub-tree that contains the exploration of the given move
+    /// index. The remaining sub-trees are freed if they use at least
+    /// `min_compact_memory` bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - the search tree to pluck the child from
+    /// * `index` - the move to pluck the sub-tree for
+    /// * `min_compact_memory` - the number of bytes of discarded sub-trees
+    ///   to free them at
+    ///
+    fn forward_with(mut self, index: usize, min_compact_memory: usize) -> Option<Node<O>> {
+        let child = self.with(index, |child| child.ptr());
+
+        if child.is_null() {
+            if index == 361 {
+                // we need to record that were was a pass so that we have the correct
+                // pass count in the root node.
+                let prior = vec! [0.0f32; 362];
+                let mut next = Node::new(self.to_move.opposite(), 0.5, prior);
+                next.pass_count = self.pass_count + 1;
 
-                Some(unsafe { ptr::read(next) })
+                Some(next)
+            } else {
+                None
             }
-        })
+        } else {
+            let mut next = unsafe { ptr::read(child) };
+            next.owner = self.owner.take();
+
+            // the number of nodes in a tree is at most one more than the number of
+            // visits to it, so if the arena contains a lot more nodes than that
+            // then most of it are discarded sub-trees. These are freed once they
+            // use more memory than the live nodes, so the cost of copying the live
+            // nodes is never larger than the memory that is freed by it.
+            let num_nodes = next.arena().nodes.len();
+            let num_discarded = num_nodes.saturating_sub(next.size() + 1);
+
+            if num_discarded > next.size() + 1 && num_discarded * mem::size_of::<Node<O>>() >= min_compact_memory {
+                next.compact();
+            } else {
+                next.arena().discarded.store(num_discarded * mem::size_of::<Node<O>>(), Ordering::Relaxed);
+            }
+
+            Some(next)
+        }
     }
 
     /// Returns the best move according to the current search tree. This is
@@ -1209,8 +1630,9 @@ impl<O: SearchOptions> Node<O> {
     /// * `temperature` - How random the process should be, if set to +Inf
     ///   then the values are picked completely at random, and if set to 0
     ///   the selection is greedy.
+    /// * `params` - the search parameters to use
     ///
-    pub fn best(&self, temperature: f32) -> (f32, usize) {
+    pub fn best(&self, temperature: f32, params: &SearchParams) -> (f32, usize) {
         if temperature <= 9e-2 { // greedy
             let max_i = (0..362)
                 .filter(|&i| self.with(i, |child| child.count() > 0))
@@ -1220,7 +1642,8 @@ impl<O: SearchOptions> Node<O> {
                             child.value(),
                             child.value_std(),
                             child.count(),
-                            self.total_count
+                            self.total_count,
+                            params
                         );
 
                         (
@@ -1258,7 +1681,7 @@ impl<O: SearchOptions> Node<O> {
             if s_total < ::std::f64::MIN_POSITIVE {
                 (self.initial_value, 361)  // no valid moves
             } else {
-                let threshold = s_total * thread_rng().gen::<f64>();
+                let threshold = s_total * rng::with(|rng| rng.gen::<f64>());
                 let max_i = (0..362).find(|&i| s[i] >= threshold).unwrap();
 
                 (self.with(max_i, |child| child.value()), max_i)
@@ -1309,9 +1732,11 @@ impl<O: SearchOptions> Node<O> {
     ///
     /// # Arguments
     ///
+    /// * `board` - the board position of this node, on the current path
     /// * `apply_fpu` - whether to use the first-play urgency heuristic
+    /// * `params` - the search parameters
     ///
-    fn select(&mut self, apply_fpu: bool) -> Option<usize> {
+    fn select(&mut self, board: &Board, apply_fpu: bool, params: &SearchParams) -> Option<usize> {
         let mut value = self.children.value(self.initial_value);
 
         if apply_fpu {
@@ -1324,7 +1749,7 @@ impl<O: SearchOptions> Node<O> {
             // - constant (this is currently used)
             // - zero
             //
-            let fpu_reduce = config::get_fpu_reduce(self.total_count + self.vtotal_count);
+            let fpu_reduce = params.fpu_reduce(self.total_count + self.vtotal_count);
 
             match self.children {
                 ChildrenImpl::Big(ref big) => FPU::apply_big(&mut value, big, fpu_reduce),
@@ -1338,27 +1763,37 @@ impl<O: SearchOptions> Node<O> {
             value[i] = ::std::f32::NEG_INFINITY;
         }
 
-        PUCT::get(self, &mut value);
+        PUCT::get(self, &mut value, params);
 
         // greedy selection based on the maximum ucb1 value, failing if someone else
         // is already expanding the node we want to expand.
-        let initial_value = self.initial_value;
-        let max_i = argmax_f32(&value);
+        let max_i = loop {
+            match argmax_f32(&value) {
+                // a shared node only checked its moves against the super-ko history
+                // of the first path that reached it, which does not always agree
+                // with the history of the current path. So skip any move that is
+                // not legal on this path.
+                Some(i) if O::transpositions() && i != 361 && !board.is_valid(self.to_move, X[i] as usize, Y[i] as usize) => {
+                    value[i] = ::std::f32::NEG_INFINITY;
+                },
+                other => break other
+            }
+        };
         let max_i = max_i.and_then(|i| {
-            self.children.with_mut(i, |mut child| {
+            self.with_mut(i, |mut child| {
                 if child.set_expanding() && child.ptr().is_null() {
                     None  // someone else is already expanding this node
                 } else {
-                    child.add_vcount(*config::VLOSS_CNT);
+                    child.add_vcount(params.vloss_cnt);
 
                     Some(i)
                 }
-            }, initial_value)
+            })
         });
 
         if max_i.is_some() {
             unsafe {
-                atomic_xadd(&mut self.vtotal_count, *config::VLOSS_CNT);
+                atomic_xadd(&mut self.vtotal_count, params.vloss_cnt);
             }
         }
 
@@ -1375,18 +1810,19 @@ pub type NodeTrace<O> = Vec<(*mut Node<O>, Color, usize)>;
 ///
 /// * `trace` - the trace to undo
 /// * `undo_expanding` - whether to also revert the `expanding` flag
+/// * `params` - the search parameters used during the probe
 ///
-pub unsafe fn undo<O: SearchOptions>(trace: NodeTrace<O>, undo_expanding: bool) {
+pub unsafe fn undo<O: SearchOptions>(trace: NodeTrace<O>, undo_expanding: bool, params: &SearchParams) {
     for (node, _, next_child) in trace.into_iter() {
-        atomic_xsub(&mut (*node).vtotal_count, *config::VLOSS_CNT as i32);
+        atomic_xsub(&mut (*node).vtotal_count, params.vloss_cnt);
 
-        (*node).children.with_mut(next_child, |mut child| {
-            child.sub_vcount(*config::VLOSS_CNT);
+        (*node).with_mut(next_child, |mut child| {
+            child.sub_vcount(params.vloss_cnt);
 
             if undo_expanding && child.ptr().is_null() {
                 child.unset_expanding();
             }
-        }, (*node).initial_value);
+        });
     }
 }
 
@@ -1399,13 +1835,14 @@ pub unsafe fn undo<O: SearchOptions>(trace: NodeTrace<O>, undo_expanding: bool)
 ///
 /// * `root` - the search tree to probe into
 /// * `board` - the board to update with the traversed moves
+/// * `params` - the search parameters
 ///
-pub unsafe fn probe<O: SearchOptions>(root: &mut Node<O>, board: &mut Board) -> Option<NodeTrace<O>> {
+pub unsafe fn probe<O: SearchOptions>(root: &mut Node<O>, board: &mut Board, params: &SearchParams) -> Option<NodeTrace<O>> {
     let mut trace = vec! [];
     let mut current = root;
 
     loop {
-        if let Some(next_child) = current.select(!trace.is_empty()) {
+        if let Some(next_child) = current.select(board, !trace.is_empty(), params) {
             trace.push((current as *mut Node<O>, current.to_move, next_child));
 
             if next_child != 361 {  // not a passing move
@@ -1418,7 +1855,25 @@ pub unsafe fn probe<O: SearchOptions>(root: &mut Node<O>, board: &mut Board) ->
             }
 
             //
-            let child = current.with(next_child, |child| child.ptr());
+            let mut child = current.with(next_child, |child| child.ptr());
+
+            if O::transpositions() && next_child != 361 {
+                let to_move = current.to_move.opposite();
+
+                if child.is_null() {
+                    // if some other parent has already expanded this position then
+                    // share its node instead of creating a new one.
+                    if let Some(other) = current.arena().transposition(transposition_key(board, to_move)) {
+                        current.with_mut(next_child, |mut child| child.set_index(other));
+                        child = current.arena().node(other);
+                    }
+                } else if (*child).key == 0 {
+                    let index = current.with_mut(next_child, |child| child.index());
+
+                    (*child).key = transposition_key(board, to_move);
+                    current.arena().register((*child).key, index);
+                }
+            }
 
             if child.is_null() {
                 break
@@ -1428,7 +1883,7 @@ pub unsafe fn probe<O: SearchOptions>(root: &mut Node<O>, board: &mut Board) ->
         } else {
             // undo the entire trace, since we added virtual losses (optimistically)
             // on the way down.
-            undo(trace, false);
+            undo(trace, false, params);
 
             return None;
         }
@@ -1446,17 +1901,19 @@ pub unsafe fn probe<O: SearchOptions>(root: &mut Node<O>, board: &mut Board) ->
 /// * `color` -
 /// * `value` -
 /// * `prior` -
+/// * `params` - the search parameters used during the probe
 ///
-pub unsafe fn insert<O: SearchOptions>(trace: &NodeTrace<O>, color: Color, value: f32, prior: Vec<f32>) {
+pub unsafe fn insert<O: SearchOptions>(trace: &NodeTrace<O>, color: Color, value: f32, prior: Vec<f32>, params: &SearchParams) {
     if let Some(&(node, _, index)) = trace.last() {
-        let mut next = Box::new(Node::new(color, value, prior));
-        if index == 361 {
-            next.pass_count = (*node).pass_count + 1;
-        }
+        let arena = (*node).arena;
+        let pass_count = if index == 361 { (*node).pass_count + 1 } else { 0 };
 
         let updated = (*node).with_mut(index, |mut child| {
             if child.ptr().is_null() {
-                child.set_ptr(Box::into_raw(next));
+                let mut next = Node::new_in(arena, color, value, prior);
+                next.pass_count = pass_count;
+
+                child.set_index((*arena).alloc_node(next));
                 true
             } else {
                 false
@@ -1468,11 +1925,11 @@ pub unsafe fn insert<O: SearchOptions>(trace: &NodeTrace<O>, color: Color, value
 
             // since we stop probing into a tree once two consecutive passes has
             // occurred we can double-expand those nodes. This is too prevent that
-            // from causing memory leaks.
+            // from allocating the same node twice.
         }
     }
 
-    PUCT::update(trace, color, value);
+    PUCT::update(trace, color, value, params);
 }
 
 /// Type alias for `Node` that acts as a wrapper for calling `as_sgf` from
@@ -1481,38 +1938,31 @@ pub struct ToSgf<'a, S: SgfCoordinate, O: SearchOptions> {
     _coordinate_format: ::std::marker::PhantomData<S>,
     starting_point: Board,
     root: &'a Node<O>,
+    params: &'a SearchParams,
     meta: bool
 }
 
 impl<'a, S: SgfCoordinate, O: SearchOptions> fmt::Display for ToSgf<'a, S, O> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        if self.meta {
-            // add the standard SGF prefix
-            write!(fmt, "(;GM[1]FF[4]SZ[19]RU[Chinese]KM[{:.1}]PL[{}]",
-                self.starting_point.komi(),
-                if self.root.to_move == Color::Black { "B" } else { "W" }
-            )?;
+        let mut writer = SgfWriter::<S>::new();
+        let params = self.params;
 
-            // write the starting point to the SGF file as pre-set variables
-            for y in 0..19 {
-                for x in 0..19 {
-                    match self.starting_point.at(x, y) {
-                        None => Ok(()),
-                        Some(Color::Black) => write!(fmt, "AB[{}]", S::to_sgf(x, y)),
-                        Some(Color::White) => write!(fmt, "AW[{}]", S::to_sgf(x, y))
-                    }?
-                }
-            }
+        if self.meta {
+            let mut info = GameInfo::new(self.starting_point.size(), self.starting_point.komi());
+            let mut starting_point = self.starting_point.clone();
 
-            // write the actual search tree
-            self.root.as_sgf::<S>(fmt, self.meta)?;
+            info.rules = Some(self.starting_point.rules().sgf_name());
+            starting_point.set_to_move(self.root.to_move);
 
-            // add the standard SGF suffix
-            write!(fmt, ")")
+            // write the starting point to the SGF file as pre-set variables,
+            // followed by the actual search tree
+            writer.open().node().game_info(&info).setup(&starting_point);
+            self.root.as_sgf::<S>(&mut writer, params, self.meta);
         } else {
-            // write the actual search tree
-            self.root.as_sgf::<S>(fmt, self.meta)
+            self.root.as_sgf::<S>(&mut writer, params, self.meta);
         }
+
+        write!(fmt, "{}", writer.finish())
     }
 }
 
@@ -1523,29 +1973,287 @@ impl<'a, S: SgfCoordinate, O: SearchOptions> fmt::Display for ToSgf<'a, S, O> {
 ///
 /// * `root` -
 /// * `starting_point` -
+/// * `params` - the search parameters to judge the candidate moves with
 /// * `meta` - whether to include the SGF meta data (rules, etc.)
 ///
-pub fn to_sgf<'a, S, O: SearchOptions>(root: &'a Node<O>, starting_point: &Board, meta: bool) -> ToSgf<'a, S, O>
+pub fn to_sgf<'a, S, O: SearchOptions>(
+    root: &'a Node<O>,
+    starting_point: &Board,
+    params: &'a SearchParams,
+    meta: bool
+) -> ToSgf<'a, S, O>
     where S: SgfCoordinate
 {
     ToSgf {
         _coordinate_format: ::std::marker::PhantomData::default(),
         starting_point: starting_point.clone(),
         root: &root,
+        params: params,
         meta: meta
     }
 }
 
-/// Type alias for pretty-printing an index based vertex.
-struct PrettyVertex {
-    inner: usize
-}
+/// The magic bytes at the start of every search tree file.
+const TREE_MAGIC: &[u8; 8] = b"DGTREE\0\0";
 
-impl fmt::Display for PrettyVertex {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        if self.inner == 361 {
-            fmt.pad("pass")
-        } else {
+/// The version of the search tree file format written by `save`.
+const TREE_VERSION: u32 = 1;
+
+/// The maximum depth of a search tree that `load` accepts. The loader itself
+/// does not recurse, but the functions that walk a loaded tree, such as
+/// `to_sgf`, do.
+const MAX_LOAD_DEPTH: usize = 4096;
+
+/// Returns an error that indicates that a search tree file is malformed.
+///
+/// # Arguments
+///
+/// * `reason` - why the file is malformed
+///
+fn invalid_tree(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+fn read_u8<R: Read>(input: &mut R) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    input.read_exact(&mut buf)?;
+
+    Ok(buf[0])
+}
+
+fn read_u16<R: Read>(input: &mut R) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    input.read_exact(&mut buf)?;
+
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    input.read_exact(&mut buf)?;
+
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    input.read_exact(&mut buf)?;
+
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_f32<R: Read>(input: &mut R) -> io::Result<f32> {
+    read_u32(input).map(f32::from_bits)
+}
+
+impl<O: SearchOptions> Node<O> {
+    /// Writes this node, and all of its sub-trees, to the given writer in
+    /// depth-first order.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - the writer to write to
+    ///
+    fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let children = self.children.nonzero().collect::<Vec<_>>();
+
+        out.write_all(&[if self.to_move == Color::Black { 0 } else { 1 }])?;
+        out.write_all(&self.pass_count.to_le_bytes())?;
+        out.write_all(&self.initial_value.to_bits().to_le_bytes())?;
+        out.write_all(&self.total_count.to_le_bytes())?;
+        for prior in self.prior[..362].iter() {
+            out.write_all(&prior.to_bits().to_le_bytes())?;
+        }
+
+        out.write_all(&(children.len() as u16).to_le_bytes())?;
+        for &i in children.iter() {
+            let (count, value, value_s, is_expanded) = self.with(i, |child| {
+                (child.count, child.value, child.value_s, !child.ptr.is_null())
+            });
+
+            out.write_all(&(i as u16).to_le_bytes())?;
+            out.write_all(&count.to_le_bytes())?;
+            out.write_all(&value.to_bits().to_le_bytes())?;
+            out.write_all(&value_s.to_bits().to_le_bytes())?;
+            out.write_all(&[is_expanded as u8])?;
+        }
+
+        for &i in children.iter() {
+            let child = self.with(i, |child| child.ptr());
+
+            if !child.is_null() {
+                unsafe { (*child).write_to(out)?; }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns a node that has been read from the given reader, with the
+    /// statistics of all of its children, and the indices of the children
+    /// whose sub-trees follows it in the reader.
+    ///
+    /// # Arguments
+    ///
+    /// * `arena` - the arena to allocate children in
+    /// * `input` - the reader to read from
+    ///
+    fn read_from<R: Read>(arena: *const NodeArena<O>, input: &mut R) -> io::Result<(Node<O>, Vec<usize>)> {
+        let to_move = match read_u8(input)? {
+            0 => Color::Black,
+            1 => Color::White,
+            _ => return Err(invalid_tree("invalid color"))
+        };
+        let pass_count = read_u16(input)? as i16;
+        let initial_value = read_f32(input)?;
+        let total_count = read_u32(input)? as i32;
+        let prior = (0..362).map(|_| read_f32(input)).collect::<io::Result<Vec<_>>>()?;
+        let num_children = read_u16(input)? as usize;
+
+        let mut node = Node::new_in(arena, to_move, initial_value, prior);
+        let mut expanded = vec! [];
+
+        node.pass_count = pass_count;
+        node.total_count = total_count;
+
+        for _ in 0..num_children {
+            let index = read_u16(input)? as usize;
+            let count = read_u32(input)? as i32;
+            let value = read_f32(input)?;
+            let value_s = read_f32(input)?;
+            let is_expanded = read_u8(input)? != 0;
+
+            if index >= 362 || count <= 0 {
+                return Err(invalid_tree("invalid child"));
+            }
+
+            node.with_mut(index, |mut child| {
+                child.set_count(count);
+                child.set_value(value);
+                child.set_value_s(value_s);
+            });
+
+            if is_expanded {
+                expanded.push(index);
+            }
+        }
+
+        Ok((node, expanded))
+    }
+
+    /// Reads the sub-trees of the given children of this node from the given
+    /// reader. The sub-trees are read using an explicit stack, instead of by
+    /// recursion, since a malformed file can describe an arbitrarily deep tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `expanded` - the children whose sub-trees should be read
+    /// * `input` - the reader to read from
+    ///
+    fn read_children<R: Read>(&mut self, expanded: Vec<usize>, input: &mut R) -> io::Result<()> {
+        let mut remaining = vec! [(self as *mut Node<O>, expanded.into_iter())];
+
+        while let Some(&mut (node, ref mut expanded)) = remaining.last_mut() {
+            let index = match expanded.next() {
+                Some(index) => index,
+                None => {
+                    remaining.pop();
+                    continue;
+                }
+            };
+
+            if remaining.len() > MAX_LOAD_DEPTH {
+                return Err(invalid_tree("search tree is too deep"));
+            }
+
+            let (next, next_expanded) = Node::read_from(self.arena, input)?;
+            let next_index = self.arena().alloc_node(next);
+
+            unsafe {
+                (*node).with_mut(index, |mut child| {
+                    child.set_index(next_index);
+                    child.set_expanding();
+                });
+            }
+
+            remaining.push((self.arena().node(next_index), next_expanded.into_iter()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the given search tree to the given writer, in a binary format that
+/// keeps the prior, and the visits, value, and variance of every child in
+/// the tree. The tree can be read back with `load`.
+///
+/// # Arguments
+///
+/// * `root` - the search tree to write
+/// * `starting_point` - the board position at the root of the search tree
+/// * `out` - the writer to write to
+///
+pub fn save<O: SearchOptions, W: Write>(root: &Node<O>, starting_point: &Board, out: &mut W) -> io::Result<()> {
+    if O::transpositions() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "search graphs can not be saved"));
+    }
+
+    out.write_all(TREE_MAGIC)?;
+    out.write_all(&TREE_VERSION.to_le_bytes())?;
+    out.write_all(&starting_point.zobrist_hash().to_le_bytes())?;
+
+    root.write_to(out)
+}
+
+/// Returns a search tree that has been read from the given reader, as written
+/// by `save`. An error is returned if the file is malformed, or if it was not
+/// written for the given board position.
+///
+/// # Arguments
+///
+/// * `input` - the reader to read from
+/// * `starting_point` - the board position at the root of the search tree
+/// * `to_move` - the color of the player whose turn it is at the root
+///
+pub fn load<O: SearchOptions, R: Read>(input: &mut R, starting_point: &Board, to_move: Color) -> io::Result<Node<O>> {
+    if O::transpositions() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "search graphs can not be loaded"));
+    }
+
+    let mut magic = [0; 8];
+    input.read_exact(&mut magic)?;
+
+    if &magic != TREE_MAGIC {
+        return Err(invalid_tree("not a search tree"));
+    } else if read_u32(input)? != TREE_VERSION {
+        return Err(invalid_tree("unsupported version"));
+    } else if read_u64(input)? != starting_point.zobrist_hash() {
+        return Err(invalid_tree("search tree is for a different position"));
+    }
+
+    let arena = Arc::new(NodeArena::new());
+    let (mut root, expanded) = Node::read_from(&*arena, input)?;
+
+    if root.to_move != to_move {
+        return Err(invalid_tree("search tree is for a different color"));
+    }
+
+    root.read_children(expanded, input)?;
+    root.owner = Some(arena);
+
+    Ok(root)
+}
+
+/// Type alias for pretty-printing an index based vertex.
+struct PrettyVertex {
+    inner: usize
+}
+
+impl fmt::Display for PrettyVertex {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if self.inner == 361 {
+            fmt.pad("pass")
+        } else {
             const LETTERS: [char; 19] = [
                 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'j', 'k',
                 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't'
@@ -1591,22 +2299,67 @@ impl<'a, O: SearchOptions> Iterator for GreedyPath<'a, O> {
     }
 }
 
+/// Returns the lower confidence bound of the value of the given child of
+/// `root`.
+///
+/// # Arguments
+///
+/// * `root` -
+/// * `index` - the index of the child
+/// * `params` - the search parameters to use
+///
+fn child_lcb<O: SearchOptions>(root: &Node<O>, index: usize, params: &SearchParams) -> f32 {
+    root.with(index, |child| {
+        normal_lcb_m(child.value(), child.value_std(), child.count(), root.total_count, params)
+    })
+}
+
+/// Returns all children of `root` that has been visited at least once,
+/// sorted in descending order of their lower confidence bound.
+///
+/// # Arguments
+///
+/// * `root` -
+/// * `params` - the search parameters to use
+///
+fn sorted_children<O: SearchOptions>(root: &Node<O>, params: &SearchParams) -> Vec<usize> {
+    let mut children = root.children.nonzero().collect::<Vec<usize>>();
+    children.sort_by_key(|&i| OrderedFloat(-child_lcb(root, i, params)));
+    children
+}
+
+/// Returns the most likely continuation after playing the given child of
+/// `root`, excluding the child itself.
+///
+/// # Arguments
+///
+/// * `root` -
+/// * `index` - the index of the child
+///
+fn child_likely_path<O: SearchOptions>(root: &Node<O>, index: usize) -> Vec<usize> {
+    let child = root.with(index, |child| child.ptr());
+
+    if child.is_null() {
+        vec! []
+    } else {
+        GreedyPath::new(unsafe { &*child }, 1).collect()
+    }
+}
+
 /// Type alias for `Node` that acts as a wrapper for calling `as_sgf` from
 /// within a `write!` macro.
 pub struct ToPretty<'a, O: SearchOptions> {
     root: &'a Node<O>,
+    params: &'a SearchParams,
+    verbose: bool
 }
 
 impl<'a, O: SearchOptions> fmt::Display for ToPretty<'a, O> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        let mut children = self.root.children.nonzero().collect::<Vec<usize>>();
-        children.sort_by_key(|&i| {
-            OrderedFloat(-self.root.with(i, |child| {
-                normal_lcb_m(child.value(), child.value_std(), child.count(), self.root.total_count)
-            }))
-        });
+        let params = self.params;
+        let mut children = sorted_children(self.root, params);
 
-        if !*config::VERBOSE {
+        if !self.verbose {
             children.truncate(10);
         }
 
@@ -1621,8 +2374,9 @@ impl<'a, O: SearchOptions> fmt::Display for ToPretty<'a, O> {
                 .map(|v| format!("{}", v))
                 .collect::<Vec<String>>().join(" ");
 
-        writeln!(fmt, "Nodes: {}, Win: {:.1}%, PV: {}",
+        writeln!(fmt, "Nodes: {}, Memory: {:.1} MiB, Win: {:.1}%, PV: {}",
             self.root.total_count,
+            self.root.memory_usage() as f64 / 1048576.0,
             100.0 * norm_value,
             likely_path
         )?;
@@ -1631,7 +2385,7 @@ impl<'a, O: SearchOptions> fmt::Display for ToPretty<'a, O> {
         for i in children {
             let pretty_vertex = PrettyVertex { inner: i };
             let child = unsafe { &*self.root.with(i, |child| child.ptr()) };
-            let likely_path: String = GreedyPath::new(child, 1)
+            let likely_path: String = child_likely_path(self.root, i).into_iter()
                     .map(|i| PrettyVertex { inner: i })
                     .map(|v| format!("{}", v))
                     .collect::<Vec<String>>().join(" ");
@@ -1640,7 +2394,7 @@ impl<'a, O: SearchOptions> fmt::Display for ToPretty<'a, O> {
                      pretty_vertex,
                      child.total_count,
                      100.0 * self.root.with(i, |child| child.value()),
-                     100.0 * self.root.with(i, |child| normal_lcb_m(child.value(), child.value_std(), child.count(), self.root.total_count)),
+                     100.0 * child_lcb(self.root, i, params),
                      100.0 * self.root.prior[i],
                      pretty_vertex,
                      likely_path
@@ -1658,10 +2412,140 @@ impl<'a, O: SearchOptions> fmt::Display for ToPretty<'a, O> {
 /// # Arguments
 ///
 /// * `root` -
-/// * `starting_point` -
+/// * `params` - the search parameters to judge the candidate moves with
+/// * `verbose` - whether to show every candidate move, instead of the ten best
 ///
-pub fn to_pretty<O: SearchOptions>(root: &Node<O>) -> ToPretty<O> {
-    ToPretty { root }
+pub fn to_pretty<'a, O: SearchOptions>(root: &'a Node<O>, params: &'a SearchParams, verbose: bool) -> ToPretty<'a, O> {
+    ToPretty { root, params, verbose }
+}
+
+/// Type alias for `Node` that acts as a wrapper for writing the analysis
+/// format used by the `lz-analyze` GTP command from within a `write!`
+/// macro.
+pub struct ToLzAnalyze<'a, O: SearchOptions> {
+    root: &'a Node<O>,
+    params: &'a SearchParams
+}
+
+impl<'a, O: SearchOptions> fmt::Display for ToLzAnalyze<'a, O> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let params = self.params;
+
+        // probabilities are written as integers in the range `[0, 10000]`
+        let to_lz = |x: f32| (10000.0 * x.max(0.0).min(1.0)).round() as i32;
+        let to_vertex = |i: usize| {
+            if i == 361 {
+                "pass".to_string()
+            } else {
+                PrettyVertex { inner: i }.to_string().to_uppercase()
+            }
+        };
+
+        for (order, i) in sorted_children(self.root, params).into_iter().enumerate() {
+            let likely_path = ::std::iter::once(i)
+                .chain(child_likely_path(self.root, i))
+                .map(to_vertex)
+                .collect::<Vec<String>>().join(" ");
+
+            if order > 0 {
+                write!(fmt, " ")?;
+            }
+
+            write!(fmt, "info move {} visits {} winrate {} prior {} lcb {} order {} pv {}",
+                to_vertex(i),
+                self.root.with(i, |child| child.count()),
+                to_lz(self.root.with(i, |child| child.value())),
+                to_lz(self.root.prior[i]),
+                to_lz(child_lcb(self.root, i, params)),
+                order,
+                likely_path
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns a marker that contains the candidate moves of the given search
+/// tree, and can be displayed in the format used by the `lz-analyze` GTP
+/// command.
+///
+/// # Arguments
+///
+/// * `root` -
+/// * `params` - the search parameters to judge the candidate moves with
+///
+pub fn to_lz_analyze<'a, O: SearchOptions>(root: &'a Node<O>, params: &'a SearchParams) -> ToLzAnalyze<'a, O> {
+    ToLzAnalyze { root, params }
+}
+
+/// Type alias for `Node` that acts as a wrapper for writing the analysis
+/// format used by the `kata-analyze` GTP command from within a `write!`
+/// macro.
+pub struct ToKataAnalyze<'a, O: SearchOptions, F: Fn(usize) -> Option<f32>> {
+    root: &'a Node<O>,
+    params: &'a SearchParams,
+    score_lead: F
+}
+
+impl<'a, O: SearchOptions, F: Fn(usize) -> Option<f32>> fmt::Display for ToKataAnalyze<'a, O, F> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let params = self.params;
+        let to_vertex = |i: usize| {
+            if i == 361 {
+                "pass".to_string()
+            } else {
+                PrettyVertex { inner: i }.to_string().to_uppercase()
+            }
+        };
+
+        for (order, i) in sorted_children(self.root, params).into_iter().enumerate() {
+            let likely_path = ::std::iter::once(i)
+                .chain(child_likely_path(self.root, i))
+                .map(to_vertex)
+                .collect::<Vec<String>>().join(" ");
+
+            if order > 0 {
+                write!(fmt, " ")?;
+            }
+
+            write!(fmt, "info move {} visits {} winrate {:.6}",
+                to_vertex(i),
+                self.root.with(i, |child| child.count()),
+                self.root.with(i, |child| child.value()).max(0.0).min(1.0)
+            )?;
+
+            if let Some(score_lead) = (self.score_lead)(i) {
+                write!(fmt, " scoreMean {:.2} scoreLead {:.2}", score_lead, score_lead)?;
+            }
+
+            write!(fmt, " prior {:.6} lcb {:.6} order {} pv {}",
+                self.root.prior[i].max(0.0).min(1.0),
+                child_lcb(self.root, i, params).max(0.0).min(1.0),
+                order,
+                likely_path
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns a marker that contains the candidate moves of the given search
+/// tree, and can be displayed in the format used by the `kata-analyze` GTP
+/// command. The score lead of each candidate move is given by `score_lead`,
+/// and is omitted if it returns `None`.
+///
+/// # Arguments
+///
+/// * `root` -
+/// * `params` - the search parameters to judge the candidate moves with
+/// * `score_lead` -
+///
+pub fn to_kata_analyze<'a, O, F>(root: &'a Node<O>, params: &'a SearchParams, score_lead: F) -> ToKataAnalyze<'a, O, F>
+    where O: SearchOptions, F: Fn(usize) -> Option<f32>
+{
+    ToKataAnalyze { root, params, score_lead }
 }
 
 #[cfg(test)]
@@ -1671,7 +2555,7 @@ mod tests {
     use dg_go::*;
     use asm::sum_finite_f32;
     use asm::normalize_finite_f32;
-    use options::StandardSearch;
+    use options::{GraphSearch, StandardSearch};
     use super::*;
 
     fn get_prior_distribution(rng: &mut SmallRng, board: &Board, to_move: Color) -> Vec<f32> {
@@ -1691,16 +2575,17 @@ mod tests {
     }
 
     unsafe fn unsafe_visit_order() {
+        let params = SearchParams::default();
         let mut choices = vec![];
         let mut rng = SmallRng::from_seed([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
         let mut root = Node::<StandardSearch>::new(
             Color::Black,
             0.5,
-            get_prior_distribution(&mut rng, &Board::new(DEFAULT_KOMI), Color::Black)
+            get_prior_distribution(&mut rng, &Board::new(DEFAULT_SIZE, DEFAULT_KOMI), Color::Black)
         );
 
         loop {
-            let trace = probe(&mut root, &mut Board::new(DEFAULT_KOMI));
+            let trace = probe(&mut root, &mut Board::new(DEFAULT_SIZE, DEFAULT_KOMI), &params);
 
             if let Some(trace) = trace {
                 assert_eq!(trace.len(), 1);
@@ -1713,8 +2598,8 @@ mod tests {
                 choices.push(i);
 
                 // check that the virtual loss has been correctly applied.
-                assert_eq!(root.with(i, |child| child.vcount()), *config::VLOSS_CNT);
-                assert_eq!(root.vtotal_count, choices.len() as i32 * *config::VLOSS_CNT as i32);
+                assert_eq!(root.with(i, |child| child.vcount()), params.vloss_cnt);
+                assert_eq!(root.vtotal_count, choices.len() as i32 * params.vloss_cnt);
 
                 // check that all nodes that were visited before this had larger prior
                 // value.
@@ -1726,10 +2611,10 @@ mod tests {
             } else {
                 // check that we did not double-add any virtual loss
                 for &other_i in &choices {
-                    assert_eq!(root.with(other_i, |child| child.vcount()), *config::VLOSS_CNT);
+                    assert_eq!(root.with(other_i, |child| child.vcount()), params.vloss_cnt);
                 }
 
-                assert_eq!(root.vtotal_count, choices.len() as i32 * *config::VLOSS_CNT as i32);
+                assert_eq!(root.vtotal_count, choices.len() as i32 * params.vloss_cnt);
                 break;
             }
 
@@ -1743,7 +2628,8 @@ mod tests {
     }
 
     unsafe fn unsafe_virtual_loss() {
-        let mut board = Board::new(DEFAULT_KOMI);
+        let params = SearchParams::default();
+        let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
         let mut rng = SmallRng::from_seed([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
         let mut root = Node::<StandardSearch>::new(
             Color::Black,
@@ -1751,19 +2637,19 @@ mod tests {
             get_prior_distribution(&mut rng, &board, Color::Black)
         );
 
-        if let Some(trace) = probe(&mut root, &mut board) {
+        if let Some(trace) = probe(&mut root, &mut board, &params) {
             let i = trace[0].2;
 
             // check that the virtual loss was applied
-            assert_eq!(root.with(i, |child| child.vcount()), *config::VLOSS_CNT);
-            assert_eq!(root.vtotal_count, *config::VLOSS_CNT as i32);
+            assert_eq!(root.with(i, |child| child.vcount()), params.vloss_cnt);
+            assert_eq!(root.vtotal_count, params.vloss_cnt);
 
             // check that the virtual loss is un-applied after we update this move, and
             // that we we increase the `count` instead.
             let other_prior = get_prior_distribution(&mut rng, &board, Color::Black);
             let other_value = 0.9;
 
-            insert(&trace, Color::Black, other_value, other_prior);
+            insert(&trace, Color::Black, other_value, other_prior, &params);
 
             assert_eq!(root.with(i, |child| child.vcount()), 0);
             assert_eq!(root.vtotal_count, 0);
@@ -1780,7 +2666,8 @@ mod tests {
     }
 
     unsafe fn unsafe_value_update() {
-        let mut board = Board::new(DEFAULT_KOMI);
+        let params = SearchParams::default();
+        let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
         let mut root = Node::<StandardSearch>::new(
             Color::Black,
             0.5,
@@ -1790,9 +2677,9 @@ mod tests {
         // to setup a scenario where we have two parallel probes that will both update
         // the same node value we need to pre-expand a node.
         let other_prior: Vec<f32> = (0..362).map(|i| if i == 61 || i == 62 { 0.5 } else { 0.0 }).collect();
-        let trace = probe(&mut root, &mut board).unwrap();
+        let trace = probe(&mut root, &mut board, &params).unwrap();
 
-        insert(&trace, Color::Black, 0.9, other_prior.clone());
+        insert(&trace, Color::Black, 0.9, other_prior.clone(), &params);
         assert!({
             let value = root.with(60, |child| child.value());
 
@@ -1804,8 +2691,8 @@ mod tests {
         assert_eq!(root.vtotal_count, 0);
 
         // two parallel probes in the same sub-tree.
-        let trace_1 = probe(&mut root, &mut Board::new(DEFAULT_KOMI)).unwrap();
-        let trace_2 = probe(&mut root, &mut Board::new(DEFAULT_KOMI)).unwrap();
+        let trace_1 = probe(&mut root, &mut Board::new(DEFAULT_SIZE, DEFAULT_KOMI), &params).unwrap();
+        let trace_2 = probe(&mut root, &mut Board::new(DEFAULT_SIZE, DEFAULT_KOMI), &params).unwrap();
 
         assert_eq!(trace_1[0].2, 60);
         assert_eq!(trace_2[0].2, 60);
@@ -1817,20 +2704,20 @@ mod tests {
         assert_eq!(root.with(60, |child| child.value()), 0.9);
         assert_eq!(root.with(60, |child| child.count()), 1);
         assert_eq!(root.total_count, 1);
-        assert_eq!(root.with(60, |child| child.vcount()), 2 * *config::VLOSS_CNT);
-        assert_eq!(root.vtotal_count, 2 * *config::VLOSS_CNT as i32);
+        assert_eq!(root.with(60, |child| child.vcount()), 2 * params.vloss_cnt);
+        assert_eq!(root.vtotal_count, 2 * params.vloss_cnt);
 
         // check update after the first probe is inserted
-        insert(&trace_1, Color::White, 0.2, other_prior.clone());
+        insert(&trace_1, Color::White, 0.2, other_prior.clone(), &params);
 
         assert_eq!(root.with(60, |child| child.value()), 0.85);
         assert_eq!(root.with(60, |child| child.count()), 2);
         assert_eq!(root.total_count, 2);
-        assert_eq!(root.with(60, |child| child.vcount()), *config::VLOSS_CNT);
-        assert_eq!(root.vtotal_count, *config::VLOSS_CNT as i32);
+        assert_eq!(root.with(60, |child| child.vcount()), params.vloss_cnt);
+        assert_eq!(root.vtotal_count, params.vloss_cnt);
 
         // check update after the second probe is inserted
-        insert(&trace_2, Color::White, 0.3, other_prior.clone());
+        insert(&trace_2, Color::White, 0.3, other_prior.clone(), &params);
 
         assert_eq!(root.with(60, |child| child.value()), 0.8);
         assert_eq!(root.with(60, |child| child.count()), 3);
@@ -1845,7 +2732,8 @@ mod tests {
     }
 
     unsafe fn unsafe_undo_trace() {
-        let mut board = Board::new(DEFAULT_KOMI);
+        let params = SearchParams::default();
+        let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
         let mut root = Node::<StandardSearch>::new(
             Color::Black,
             0.5,
@@ -1854,15 +2742,454 @@ mod tests {
 
         // probe twice, of which the first will be undone, then check that the tree is
         // consistent with this.
-        assert!(probe(&mut root, &mut board).is_some());
-        assert!(probe(&mut root, &mut board).is_none());
+        assert!(probe(&mut root, &mut board, &params).is_some());
+        assert!(probe(&mut root, &mut board, &params).is_none());
 
-        assert_eq!(root.vtotal_count, *config::VLOSS_CNT as i32);
+        assert_eq!(root.vtotal_count, params.vloss_cnt);
     }
 
     #[test]
     fn undo_trace() {
         unsafe { unsafe_undo_trace() }
     }
+
+    unsafe fn unsafe_lz_analyze() {
+        let params = SearchParams::default();
+        let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
+        let mut root = Node::<StandardSearch>::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 { 1.0 } else { 0.0 }).collect()
+        );
+
+        assert_eq!(to_lz_analyze(&root, &SearchParams::default()).to_string(), "");
+
+        let other_prior: Vec<f32> = (0..362).map(|i| if i == 61 { 1.0 } else { 0.0 }).collect();
+        let trace = probe(&mut root, &mut board, &params).unwrap();
+        insert(&trace, Color::Black, 0.9, other_prior, &params);
+
+        let analysis = to_lz_analyze(&root, &SearchParams::default()).to_string();
+
+        assert!(analysis.starts_with("info move D4 visits 1 winrate 9000 prior 10000 "), "{}", analysis);
+        assert!(analysis.ends_with(" order 0 pv D4"), "{}", analysis);
+    }
+
+    #[test]
+    fn lz_analyze() {
+        unsafe { unsafe_lz_analyze() }
+    }
+
+    unsafe fn unsafe_kata_analyze() {
+        let params = SearchParams::default();
+        let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
+        let mut root = Node::<StandardSearch>::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 { 1.0 } else { 0.0 }).collect()
+        );
+
+        assert_eq!(to_kata_analyze(&root, &SearchParams::default(), |_| None).to_string(), "");
+
+        let other_prior: Vec<f32> = (0..362).map(|i| if i == 61 { 1.0 } else { 0.0 }).collect();
+        let trace = probe(&mut root, &mut board, &params).unwrap();
+        insert(&trace, Color::Black, 0.9, other_prior, &params);
+
+        let analysis = to_kata_analyze(&root, &SearchParams::default(), |_| None).to_string();
+
+        assert!(analysis.starts_with("info move D4 visits 1 winrate 0.900000 prior 1.000000 "), "{}", analysis);
+        assert!(analysis.ends_with(" order 0 pv D4"), "{}", analysis);
+
+        let analysis = to_kata_analyze(&root, &SearchParams::default(), |i| if i == 60 { Some(2.5) } else { None }).to_string();
+
+        assert!(analysis.starts_with("info move D4 visits 1 winrate 0.900000 scoreMean 2.50 scoreLead 2.50 prior 1.000000 "), "{}", analysis);
+    }
+
+    #[test]
+    fn kata_analyze() {
+        unsafe { unsafe_kata_analyze() }
+    }
+
+    unsafe fn unsafe_prune() {
+        let params = SearchParams::default();
+        let mut rng = SmallRng::from_seed([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
+        let mut root = Node::<StandardSearch>::new(
+            Color::Black,
+            0.5,
+            get_prior_distribution(&mut rng, &Board::new(DEFAULT_SIZE, DEFAULT_KOMI), Color::Black)
+        );
+
+        assert_eq!(root.memory_usage(), mem::size_of::<Node<StandardSearch>>());
+
+        for _ in 0..200 {
+            let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
+            let trace = probe(&mut root, &mut board, &params).unwrap();
+            let &(_, color, _) = trace.last().unwrap();
+            let prior = get_prior_distribution(&mut rng, &board, color.opposite());
+
+            insert(&trace, color.opposite(), 0.5, prior, &params);
+        }
+
+        let counts = (0..362).map(|i| root.with(i, |child| child.count())).collect::<Vec<_>>();
+        let before = root.memory_usage();
+        let freed = root.prune(before / 2);
+
+        assert!(before > 200 * mem::size_of::<Node<StandardSearch>>());
+        assert!(root.memory_usage() <= before / 2);
+        assert_eq!(root.memory_usage(), before - freed);
+        assert_eq!(root.size(), 200);
+        assert_eq!((0..362).map(|i| root.with(i, |child| child.count())).collect::<Vec<_>>(), counts);
+
+        // the pruned children should be expanded again when they are visited
+        for _ in 0..50 {
+            let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
+            let trace = probe(&mut root, &mut board, &params).unwrap();
+            let &(_, color, _) = trace.last().unwrap();
+            let prior = get_prior_distribution(&mut rng, &board, color.opposite());
+
+            insert(&trace, color.opposite(), 0.5, prior, &params);
+        }
+
+        assert_eq!(root.size(), 250);
+    }
+
+    #[test]
+    fn prune() {
+        unsafe { unsafe_prune() }
+    }
+
+    unsafe fn unsafe_forward() {
+        let params = SearchParams::default();
+        let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
+        let mut root = Node::<StandardSearch>::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 { 1.0 } else { 0.0 }).collect()
+        );
+
+        let other_prior: Vec<f32> = (0..362).map(|i| if i == 61 { 1.0 } else { 0.0 }).collect();
+        let trace = probe(&mut root, &mut board, &params).unwrap();
+        insert(&trace, Color::White, 0.9, other_prior.clone(), &params);
+
+        let trace = probe(&mut root, &mut Board::new(DEFAULT_SIZE, DEFAULT_KOMI), &params).unwrap();
+        insert(&trace, Color::Black, 0.8, other_prior, &params);
+
+        let next = root.forward(60).unwrap();
+
+        assert_eq!(next.to_move, Color::White);
+        assert_eq!(next.size(), 1);
+        assert_eq!(next.with(61, |child| child.count()), 1);
+        assert!(next.forward(62).is_none());
+    }
+
+    #[test]
+    fn forward() {
+        unsafe { unsafe_forward() }
+    }
+
+    unsafe fn unsafe_forward_compact() {
+        let params = SearchParams::default();
+        let mut rng = SmallRng::from_seed([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
+        let mut root = Node::<StandardSearch>::new(
+            Color::Black,
+            0.5,
+            get_prior_distribution(&mut rng, &Board::new(DEFAULT_SIZE, DEFAULT_KOMI), Color::Black)
+        );
+
+        for _ in 0..2000 {
+            let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
+            let trace = probe(&mut root, &mut board, &params).unwrap();
+            let &(_, color, _) = trace.last().unwrap();
+            let prior = get_prior_distribution(&mut rng, &board, color.opposite());
+
+            insert(&trace, color.opposite(), 0.5, prior, &params);
+        }
+
+        let before = root.memory_usage();
+        let index = root.children.argmax_count();
+        let count = root.with(index, |child| child.count());
+        let next = root.forward_with(index, 0).unwrap();
+
+        // the discarded siblings should have been freed, while the statistics
+        // of the sub-tree are kept.
+        assert!(next.memory_usage() < before / 2);
+        assert_eq!(next.memory_usage(), next.live_memory_usage());
+        assert_eq!(next.size() as i32, count - 1);
+    }
+
+    #[test]
+    fn forward_compact() {
+        unsafe { unsafe_forward_compact() }
+    }
+
+    unsafe fn unsafe_forward_no_compact() {
+        let params = SearchParams::default();
+        let mut rng = SmallRng::from_seed([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
+        let mut root = Node::<StandardSearch>::new(
+            Color::Black,
+            0.5,
+            get_prior_distribution(&mut rng, &Board::new(DEFAULT_SIZE, DEFAULT_KOMI), Color::Black)
+        );
+
+        for _ in 0..200 {
+            let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
+            let trace = probe(&mut root, &mut board, &params).unwrap();
+            let &(_, color, _) = trace.last().unwrap();
+            let prior = get_prior_distribution(&mut rng, &board, color.opposite());
+
+            insert(&trace, color.opposite(), 0.5, prior, &params);
+        }
+
+        let arena_usage = root.arena().memory_usage();
+        let index = root.children.argmax_count();
+        let next = root.forward(index).unwrap();
+
+        // the discarded siblings use much less than `MIN_COMPACT_MEMORY`, so
+        // they should be kept until the arena is freed.
+        assert_eq!(next.arena().memory_usage(), arena_usage);
+    }
+
+    #[test]
+    fn forward_no_compact() {
+        unsafe { unsafe_forward_no_compact() }
+    }
+
+    unsafe fn unsafe_prune_transpositions() {
+        let params = SearchParams::default();
+        let one_hot = |index: usize| (0..362).map(|i| if i == index { 1.0 } else { 0.0 }).collect::<Vec<f32>>();
+        let mut root = Node::<GraphSearch>::new(Color::Black, 0.5, one_hot(62));
+
+        let trace = probe(&mut root, &mut Board::new(DEFAULT_SIZE, DEFAULT_KOMI), &params).unwrap();
+        insert(&trace, Color::White, 0.5, one_hot(100), &params);
+        let trace = probe(&mut root, &mut Board::new(DEFAULT_SIZE, DEFAULT_KOMI), &params).unwrap();
+        insert(&trace, Color::Black, 0.5, one_hot(60), &params);
+
+        // share the sub-tree of `B[62]` with a second edge from the root
+        let index = root.with_mut(62, |child| child.index());
+        root.with_mut(60, |mut child| child.set_index(index));
+
+        assert_eq!(root.with(60, |child| child.ptr()), root.with(62, |child| child.ptr()));
+
+        root.prune(0);
+
+        assert!(root.with(60, |child| child.ptr()).is_null());
+        assert!(root.with(62, |child| child.ptr()).is_null());
+        assert_eq!(root.memory_usage(), root.live_memory_usage());
+    }
+
+    #[test]
+    fn prune_transpositions() {
+        unsafe { unsafe_prune_transpositions() }
+    }
+
+    unsafe fn unsafe_probe_skips_illegal_shared_moves() {
+        let params = SearchParams::default();
+        let one_hot = |index: usize| (0..362).map(|i| if i == index { 1.0 } else { 0.0 }).collect::<Vec<f32>>();
+        let mut root = Node::<GraphSearch>::new(Color::White, 0.5, one_hot(60));
+        let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
+
+        // simulate a shared node whose moves were checked against another
+        // path, where `60` was still legal
+        board.place(Color::Black, X[60] as usize, Y[60] as usize);
+
+        let trace = probe(&mut root, &mut board, &params).unwrap();
+
+        assert_eq!(trace.len(), 1);
+        assert_ne!(trace[0].2, 60);
+    }
+
+    #[test]
+    fn probe_skips_illegal_shared_moves() {
+        unsafe { unsafe_probe_skips_illegal_shared_moves() }
+    }
+
+    unsafe fn unsafe_transpositions() {
+        let params = SearchParams::default();
+        let one_hot = |index: usize| (0..362).map(|i| if i == index { 1.0 } else { 0.0 }).collect::<Vec<f32>>();
+        let mut root = Node::<GraphSearch>::new(Color::Black, 0.5, one_hot(62));
+
+        // reach the same position as `B[60] W[100] B[62]` through the move order
+        // `B[62] W[100] B[60]`.
+        let mut other_board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
+        other_board.place(Color::Black, 3, 3);
+        other_board.place(Color::White, 5, 5);
+        other_board.place(Color::Black, 5, 3);
+
+        let other = root.arena().alloc_node(Node::new_in(root.arena, Color::White, 0.6, one_hot(200)));
+        let key = transposition_key(&other_board, Color::White);
+
+        (*root.arena().node(other)).key = key;
+        root.arena().register(key, other);
+
+        let trace = probe(&mut root, &mut Board::new(DEFAULT_SIZE, DEFAULT_KOMI), &params).unwrap();
+        insert(&trace, Color::White, 0.5, one_hot(100), &params);
+        let trace = probe(&mut root, &mut Board::new(DEFAULT_SIZE, DEFAULT_KOMI), &params).unwrap();
+        insert(&trace, Color::Black, 0.5, one_hot(60), &params);
+        let trace = probe(&mut root, &mut Board::new(DEFAULT_SIZE, DEFAULT_KOMI), &params).unwrap();
+
+        assert_eq!(trace.len(), 4);
+        assert_eq!(trace[3].0, root.arena().node(other));
+        insert(&trace, Color::Black, 0.7, one_hot(0), &params);
+
+        // the shared node has the average value `(0.6 + 0.3) / 2` for white, which
+        // should have been propagated to the edge pointing at it
+        let node = trace[2].0;
+
+        assert_eq!((*node).with(60, |child| child.count()), 1);
+        assert!(((*node).with(60, |child| child.value()) - 0.55).abs() < 1e-4);
+
+        // the node should still be shared after it has been moved to a new arena
+        root.compact();
+
+        let child = root.with(62, |child| child.ptr());
+        let child = (*child).with(100, |child| child.ptr());
+        let child = (*child).with(60, |child| child.ptr());
+        let other = root.arena().transposition(key).unwrap();
+
+        assert_eq!(child, root.arena().node(other));
+        assert_eq!((*child).initial_value, 0.6);
+    }
+
+    #[test]
+    fn transpositions() {
+        unsafe { unsafe_transpositions() }
+    }
+
+    unsafe fn unsafe_graph_search() {
+        let params = SearchParams::default();
+        let mut rng = SmallRng::from_seed([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
+        let mut root = Node::<GraphSearch>::new(
+            Color::Black,
+            0.5,
+            get_prior_distribution(&mut rng, &Board::new(DEFAULT_SIZE, DEFAULT_KOMI), Color::Black)
+        );
+
+        for _ in 0..2000 {
+            let mut board = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
+            let trace = probe(&mut root, &mut board, &params).unwrap();
+            let &(_, color, _) = trace.last().unwrap();
+            let prior = get_prior_distribution(&mut rng, &board, color.opposite());
+
+            insert(&trace, color.opposite(), rng.gen::<f32>(), prior, &params);
+        }
+
+        assert_eq!(root.size(), 2000);
+        assert!(root.live_memory_usage() <= root.memory_usage());
+
+        let index = root.children.argmax_count();
+        let count = root.with(index, |child| child.count());
+        let next = root.forward(index).unwrap();
+
+        assert_eq!(next.size() as i32, count - 1);
+        assert!(save(&next, &Board::new(DEFAULT_SIZE, DEFAULT_KOMI), &mut vec! []).is_err());
+    }
+
+    #[test]
+    fn graph_search() {
+        unsafe { unsafe_graph_search() }
+    }
+
+    unsafe fn unsafe_save_load() {
+        let params = SearchParams::default();
+        let mut rng = SmallRng::from_seed([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
+        let starting_point = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
+        let mut root = Node::<StandardSearch>::new(
+            Color::Black,
+            0.5,
+            get_prior_distribution(&mut rng, &starting_point, Color::Black)
+        );
+
+        for _ in 0..500 {
+            let mut board = starting_point.clone();
+            let trace = probe(&mut root, &mut board, &params).unwrap();
+            let &(_, color, _) = trace.last().unwrap();
+            let prior = get_prior_distribution(&mut rng, &board, color.opposite());
+
+            insert(&trace, color.opposite(), rng.gen::<f32>(), prior, &params);
+        }
+
+        let mut buf = vec! [];
+        save(&root, &starting_point, &mut buf).unwrap();
+
+        let other = load::<StandardSearch, _>(&mut &buf[..], &starting_point, Color::Black).unwrap();
+        let mut other_buf = vec! [];
+        save(&other, &starting_point, &mut other_buf).unwrap();
+
+        assert_eq!(buf, other_buf);
+        assert_eq!(other.size(), 500);
+        assert_eq!(other.live_memory_usage(), root.live_memory_usage());
+        assert_eq!(other.prior[..362], root.prior[..362]);
+        assert_eq!(to_pretty(&other, &params, false).to_string(), to_pretty(&root, &params, false).to_string());
+
+        // the tree should only be loaded for the position it was saved for
+        let mut other_point = starting_point.clone();
+        other_point.place(Color::Black, 3, 3);
+
+        assert_eq!(
+            load::<StandardSearch, _>(&mut &buf[..], &other_point, Color::Black).err().map(|err| err.kind()),
+            Some(io::ErrorKind::InvalidData)
+        );
+        assert!(load::<StandardSearch, _>(&mut &buf[..], &starting_point, Color::White).is_err());
+        assert!(load::<StandardSearch, _>(&mut &buf[..100], &starting_point, Color::Black).is_err());
+    }
+
+    #[test]
+    fn save_load() {
+        unsafe { unsafe_save_load() }
+    }
+
+    /// Returns a search tree file that contains a chain of `num_nodes` nodes,
+    /// where every node has exactly one expanded child. The child of the last
+    /// node is missing from the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `starting_point` - the board position at the root of the search tree
+    /// * `num_nodes` - the number of nodes in the file
+    ///
+    fn deep_tree_file(starting_point: &Board, num_nodes: usize) -> Vec<u8> {
+        let mut buf = vec! [];
+
+        buf.extend_from_slice(TREE_MAGIC);
+        buf.extend_from_slice(&TREE_VERSION.to_le_bytes());
+        buf.extend_from_slice(&starting_point.zobrist_hash().to_le_bytes());
+
+        for i in 0..num_nodes {
+            buf.push(if i % 2 == 0 { 0 } else { 1 });
+            buf.extend_from_slice(&0u16.to_le_bytes());
+            buf.extend_from_slice(&0.5f32.to_bits().to_le_bytes());
+            buf.extend_from_slice(&1u32.to_le_bytes());
+            for _ in 0..362 {
+                buf.extend_from_slice(&(1.0f32 / 362.0).to_bits().to_le_bytes());
+            }
+
+            buf.extend_from_slice(&1u16.to_le_bytes());
+            buf.extend_from_slice(&60u16.to_le_bytes());
+            buf.extend_from_slice(&1u32.to_le_bytes());
+            buf.extend_from_slice(&0.5f32.to_bits().to_le_bytes());
+            buf.extend_from_slice(&0.0f32.to_bits().to_le_bytes());
+            buf.push(1);
+        }
+
+        buf
+    }
+
+    #[test]
+    fn load_deep() {
+        let starting_point = Board::new(DEFAULT_SIZE, DEFAULT_KOMI);
+
+        // a chain that is as deep as `load` accepts should be read in full,
+        // and only fail because the last node is missing
+        let buf = deep_tree_file(&starting_point, MAX_LOAD_DEPTH);
+        assert_eq!(
+            load::<StandardSearch, _>(&mut &buf[..], &starting_point, Color::Black).err().map(|err| err.kind()),
+            Some(io::ErrorKind::UnexpectedEof)
+        );
+
+        let buf = deep_tree_file(&starting_point, MAX_LOAD_DEPTH + 1);
+        assert_eq!(
+            load::<StandardSearch, _>(&mut &buf[..], &starting_point, Color::Black).err().map(|err| err.kind()),
+            Some(io::ErrorKind::InvalidData)
+        );
+    }
 }
 
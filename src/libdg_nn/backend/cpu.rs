@@ -0,0 +1,519 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, RwLock};
+
+use super::{Activation, Backend, Convolution, Slot, Stream, SLOT_SIZE};
+use graph::InferenceType;
+use tensor::Tensor;
+use Error;
+
+/// Returns the dot product of the two given slices.
+///
+/// # Arguments
+///
+/// * `a` -
+/// * `b` -
+///
+#[inline]
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    debug_assert_eq!(a.len(), b.len());
+
+    a.iter().zip(b.iter()).map(|(&x, &y)| x * y).sum()
+}
+
+/// Apply the softmax activation to the given slice in-place.
+///
+/// # Arguments
+///
+/// * `x` -
+///
+fn softmax(x: &mut [f32]) {
+    let max = x.iter().fold(::std::f32::NEG_INFINITY, |acc, &v| acc.max(v));
+    let mut total = 0.0;
+
+    for v in x.iter_mut() {
+        *v = (*v - max).exp();
+        total += *v;
+    }
+
+    for v in x.iter_mut() {
+        *v /= total;
+    }
+}
+
+/// Apply the given activation function to every chunk of `num_channels`
+/// elements in the given slice in-place.
+///
+/// # Arguments
+///
+/// * `act` -
+/// * `num_channels` -
+/// * `x` -
+///
+fn activate(act: Activation, num_channels: usize, x: &mut [f32]) {
+    match act {
+        Activation::Identity => {},
+        Activation::Relu => {
+            for v in x.iter_mut() {
+                if *v < 0.0 {
+                    *v = 0.0;
+                }
+            }
+        },
+        Activation::Tanh => {
+            for v in x.iter_mut() {
+                *v = v.tanh();
+            }
+        },
+        Activation::Softmax => {
+            for chunk in x.chunks_mut(num_channels) {
+                softmax(chunk);
+            }
+        }
+    }
+}
+
+/// Computes `output = alpha * conv(input) + beta * output + offset` for the
+/// given `NHWC` input and `KRSC` filter, using _same_ padding.
+///
+/// # Arguments
+///
+/// * `batch_size` -
+/// * `weights` -
+/// * `offset` -
+/// * `in_channels` -
+/// * `out_channels` -
+/// * `size` -
+/// * `alpha` -
+/// * `input` -
+/// * `beta` -
+/// * `output` -
+///
+fn conv2d(
+    batch_size: usize,
+    weights: &[f32],
+    offset: &[f32],
+    in_channels: usize,
+    out_channels: usize,
+    size: usize,
+    alpha: f32,
+    input: &[f32],
+    beta: f32,
+    output: &mut [f32]
+)
+{
+    let (c_in, c_out) = (in_channels, out_channels);
+    let pad = (size / 2) as isize;
+    let mut sum = vec! [0.0f32; c_out];
+
+    debug_assert_eq!(weights.len(), c_out * size * size * c_in);
+    debug_assert_eq!(offset.len(), c_out);
+    debug_assert!(input.len() >= batch_size * 361 * c_in);
+    debug_assert!(output.len() >= batch_size * 361 * c_out);
+
+    for n in 0..batch_size {
+        let input_n = &input[(n * 361 * c_in)..((n + 1) * 361 * c_in)];
+
+        for y in 0..19isize {
+            for x in 0..19isize {
+                for s in sum.iter_mut() {
+                    *s = 0.0;
+                }
+
+                for r in 0..size {
+                    let other_y = y + r as isize - pad;
+                    if other_y < 0 || other_y >= 19 {
+                        continue
+                    }
+
+                    for s in 0..size {
+                        let other_x = x + s as isize - pad;
+                        if other_x < 0 || other_x >= 19 {
+                            continue
+                        }
+
+                        let i = (19 * other_y + other_x) as usize * c_in;
+                        let in_pixel = &input_n[i..(i + c_in)];
+
+                        for k in 0..c_out {
+                            let w = ((k * size + r) * size + s) * c_in;
+
+                            sum[k] += dot(&weights[w..(w + c_in)], in_pixel);
+                        }
+                    }
+                }
+
+                let o = (n * 361 + (19 * y + x) as usize) * c_out;
+                let out = &mut output[o..(o + c_out)];
+
+                for k in 0..c_out {
+                    let other = if beta == 0.0 { 0.0 } else { beta * out[k] };
+
+                    out[k] = alpha * sum[k] + other + offset[k];
+                }
+            }
+        }
+    }
+}
+
+/// Computes `output = input * weights` where the `weights` are stored in
+/// `[input][output]` order.
+///
+/// # Arguments
+///
+/// * `batch_size` -
+/// * `weights` -
+/// * `num_inputs` -
+/// * `num_outputs` -
+/// * `input` -
+/// * `output` -
+///
+fn gemm(
+    batch_size: usize,
+    weights: &[f32],
+    num_inputs: usize,
+    num_outputs: usize,
+    input: &[f32],
+    output: &mut [f32]
+)
+{
+    debug_assert_eq!(weights.len(), num_inputs * num_outputs);
+
+    for n in 0..batch_size {
+        let out = &mut output[(n * num_outputs)..((n + 1) * num_outputs)];
+
+        for o in out.iter_mut() {
+            *o = 0.0;
+        }
+
+        for i in 0..num_inputs {
+            let x = input[n * num_inputs + i];
+            if x == 0.0 {
+                continue
+            }
+
+            let w = &weights[(i * num_outputs)..((i + 1) * num_outputs)];
+
+            for (o, &w_o) in out.iter_mut().zip(w.iter()) {
+                *o += x * w_o;
+            }
+        }
+    }
+}
+
+/// A backend that evaluates the neural network on the CPU using single
+/// precision floating point numbers.
+#[derive(Default)]
+pub struct Cpu {
+    /// The uploaded tensors, converted to `f32` and scaled. They are keyed by
+    /// the address of the original tensor, which is stable since the tensors
+    /// are owned by the same `Builder` as this backend.
+    uploaded: RwLock<HashMap<usize, Arc<Vec<f32>>>>
+}
+
+impl Cpu {
+    /// Returns the uploaded copy of the given tensor, uploading it (without
+    /// any scaling) if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `tensor` -
+    ///
+    fn get(&self, tensor: &Tensor) -> Arc<Vec<f32>> {
+        let key = tensor as *const Tensor as usize;
+
+        if let Some(values) = self.uploaded.read().unwrap().get(&key) {
+            return values.clone();
+        }
+
+        self.uploaded.write().unwrap()
+            .entry(key)
+            .or_insert_with(|| Arc::new(tensor.to_f32_vec()))
+            .clone()
+    }
+}
+
+pub struct CpuWorkspace {
+    batch_size: usize,
+    slots: Vec<Vec<f32>>
+}
+
+impl CpuWorkspace {
+    /// Take the given slot out of this workspace, resized to contain exactly
+    /// `num_elements` elements. It must be returned using `put` once done.
+    ///
+    /// # Arguments
+    ///
+    /// * `slot` -
+    /// * `num_elements` -
+    ///
+    fn take(&mut self, slot: Slot, num_elements: usize) -> Vec<f32> {
+        let mut out = mem::replace(&mut self.slots[slot as usize], vec! []);
+
+        out.resize(num_elements, 0.0);
+        out
+    }
+
+    fn put(&mut self, slot: Slot, values: Vec<f32>) {
+        self.slots[slot as usize] = values;
+    }
+}
+
+impl Backend for Cpu {
+    type Workspace = CpuWorkspace;
+
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn is_half(&self) -> bool {
+        false
+    }
+
+    fn get_workspace(&self, batch_size: usize) -> Result<CpuWorkspace, Error> {
+        Ok(CpuWorkspace {
+            batch_size: batch_size,
+            slots: vec! [vec! []; SLOT_SIZE]
+        })
+    }
+
+    fn upload(
+        &self,
+        _workspace: &mut CpuWorkspace,
+        _stream: Stream,
+        tensor: &Tensor,
+        scale: f32
+    ) -> Result<(), Error>
+    {
+        let key = tensor as *const Tensor as usize;
+        let mut uploaded = self.uploaded.write().unwrap();
+
+        uploaded.entry(key).or_insert_with(|| {
+            Arc::new(tensor.to_f32_vec().into_iter().map(|x| scale * x).collect())
+        });
+
+        Ok(())
+    }
+
+    fn set_input<T: InferenceType>(
+        &self,
+        workspace: &mut CpuWorkspace,
+        features: &[T],
+        output: Slot
+    ) -> Result<(), Error>
+    {
+        workspace.put(output, features.iter().map(|&x| x.as_f32()).collect());
+
+        Ok(())
+    }
+
+    fn get_output<T: InferenceType>(
+        &self,
+        workspace: &mut CpuWorkspace,
+        _stream: Stream,
+        input: Slot,
+        num_elements: usize
+    ) -> Result<Vec<f32>, Error>
+    {
+        Ok(workspace.slots[input as usize][..num_elements].to_vec())
+    }
+
+    fn convolution(
+        &self,
+        workspace: &mut CpuWorkspace,
+        _stream: Stream,
+        conv: &Convolution,
+        alpha: f32,
+        input: Slot,
+        beta: f32,
+        output: Slot,
+        act: Activation
+    ) -> Result<(), Error>
+    {
+        debug_assert!(input != output);
+
+        let weights = self.get(conv.weights);
+        let offset = self.get(conv.offset);
+        let batch_size = workspace.batch_size;
+        let mut out = workspace.take(output, batch_size * 361 * conv.out_channels);
+
+        conv2d(
+            batch_size,
+            &weights,
+            &offset,
+            conv.in_channels,
+            conv.out_channels,
+            conv.size,
+            alpha,
+            &workspace.slots[input as usize],
+            beta,
+            &mut out
+        );
+
+        activate(act, conv.out_channels, &mut out);
+        workspace.put(output, out);
+
+        Ok(())
+    }
+
+    fn gemm(
+        &self,
+        workspace: &mut CpuWorkspace,
+        _stream: Stream,
+        weights: &Tensor,
+        num_inputs: usize,
+        num_outputs: usize,
+        input: Slot,
+        output: Slot
+    ) -> Result<(), Error>
+    {
+        debug_assert!(input != output);
+
+        let weights = self.get(weights);
+        let batch_size = workspace.batch_size;
+        let mut out = workspace.take(output, batch_size * num_outputs);
+
+        gemm(
+            batch_size,
+            &weights,
+            num_inputs,
+            num_outputs,
+            &workspace.slots[input as usize],
+            &mut out
+        );
+
+        workspace.put(output, out);
+
+        Ok(())
+    }
+
+    fn offset(
+        &self,
+        workspace: &mut CpuWorkspace,
+        _stream: Stream,
+        offset: &Tensor,
+        alpha: f32,
+        beta: f32,
+        output: Slot
+    ) -> Result<(), Error>
+    {
+        let offset = self.get(offset);
+        let out = &mut workspace.slots[output as usize];
+
+        for chunk in out.chunks_mut(offset.len()) {
+            for (o, &b) in chunk.iter_mut().zip(offset.iter()) {
+                *o = alpha * b + beta * *o;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn activation(
+        &self,
+        workspace: &mut CpuWorkspace,
+        _stream: Stream,
+        act: Activation,
+        num_channels: usize,
+        input: Slot,
+        output: Slot
+    ) -> Result<(), Error>
+    {
+        if input != output {
+            let values = workspace.slots[input as usize].clone();
+
+            workspace.put(output, values);
+        }
+
+        activate(act, num_channels, &mut workspace.slots[output as usize]);
+
+        Ok(())
+    }
+
+    fn fork(&self, _workspace: &mut CpuWorkspace) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn softmax_sums_to_one() {
+        let mut x = [1.0, 2.0, 3.0, -4.0];
+
+        activate(Activation::Softmax, 4, &mut x);
+
+        assert!((x.iter().sum::<f32>() - 1.0).abs() < 1e-6, "{:?}", x);
+        assert!(x[2] > x[1] && x[1] > x[0] && x[0] > x[3], "{:?}", x);
+    }
+
+    #[test]
+    fn conv_identity() {
+        // a 3x3 filter that only has a one in the center for each channel
+        // should return the input plus the offset
+        let mut weights = vec! [0.0; 2 * 9 * 2];
+        weights[(0 * 9 + 4) * 2 + 0] = 1.0;
+        weights[(1 * 9 + 4) * 2 + 1] = 1.0;
+
+        let input = (0..722).map(|i| i as f32).collect::<Vec<_>>();
+        let mut output = vec! [0.0; 722];
+
+        conv2d(1, &weights, &[0.5, -0.5], 2, 2, 3, 1.0, &input, 0.0, &mut output);
+
+        for i in 0..722 {
+            let offset = if i % 2 == 0 { 0.5 } else { -0.5 };
+
+            assert_eq!(output[i], input[i] + offset);
+        }
+    }
+
+    #[test]
+    fn conv_padding() {
+        // a 3x3 filter of all ones should count the number of neighbours
+        // (including itself) of every vertex
+        let mut output = vec! [0.0; 361];
+
+        conv2d(1, &[1.0; 9], &[0.0], 1, 1, 3, 1.0, &[1.0; 361], 0.0, &mut output);
+
+        assert_eq!(output[0], 4.0);  // corner
+        assert_eq!(output[1], 6.0);  // edge
+        assert_eq!(output[20], 9.0);  // center
+        assert_eq!(output[360], 4.0);  // corner
+    }
+
+    #[test]
+    fn conv_blend() {
+        // the output should be blended with its previous value
+        let mut output = vec! [2.0; 361];
+
+        conv2d(1, &[1.0], &[0.0], 1, 1, 1, 0.5, &[1.0; 361], 0.25, &mut output);
+
+        assert!(output.iter().all(|&x| x == 1.0), "{:?}", output);
+    }
+
+    #[test]
+    fn linear() {
+        // [1 2] x [[1 2 3] [4 5 6]] = [9 12 15]
+        let mut output = vec! [0.0; 3];
+
+        gemm(1, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 2, 3, &[1.0, 2.0], &mut output);
+
+        assert_eq!(output, vec! [9.0, 12.0, 15.0]);
+    }
+}
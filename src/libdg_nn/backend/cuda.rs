@@ -0,0 +1,678 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::ptr;
+
+use libc::c_void;
+
+use super::{Activation, Backend, Convolution, Slot, Stream, SLOT_SIZE};
+use devices::get_current_device;
+use ffi::{cublas, cuda, cudnn};
+use graph::InferenceType;
+use tensor::Tensor;
+use Error;
+
+/// A __global__ constant that contains `0.0`.
+const ZERO: f32 = 0.0;
+
+/// A __global__ constant that contains `1.0`.
+const ONE: f32 = 1.0;
+
+/// The number of streams that the operations can be scheduled on.
+const NUM_STREAMS: usize = 3;
+
+/// Returns true if the current device supports `f16` (in a
+/// sensible way).
+fn has_true_half() -> bool {
+    let mut version_major: i32 = 0;
+    let mut version_minor: i32 = 0;
+
+    unsafe {
+        assert!(cuda::cudaDeviceGetAttribute(&mut version_major, cuda::DeviceAttr::ComputeCapabilityMajor, 0).is_ok());
+        assert!(cuda::cudaDeviceGetAttribute(&mut version_minor, cuda::DeviceAttr::ComputeCapabilityMinor, 0).is_ok());
+    }
+
+    (version_major == 6 && version_minor == 0) ||
+        (version_major == 6 && version_minor == 2) ||
+        (version_major >= 7)
+}
+
+/// Copy the value of the given tensor from the device to the host.
+///
+/// # Arguments
+///
+/// * `ptr` - the memory address on the device
+/// * `num_elements` - the number of elements to copy
+/// * `stream` - the stream to execute the copy on
+///
+unsafe fn load_to_host<T: InferenceType>(
+    ptr: *const c_void,
+    num_elements: usize,
+    stream: cuda::Stream
+) -> Result<Vec<f32>, Error>
+{
+    let mut host = vec! [T::default(); num_elements];
+
+    check!(cuda::cudaMemcpyAsync(
+        host.as_mut_ptr() as *mut c_void,
+        ptr,
+        size_of::<T>() * num_elements,
+        cuda::MemcpyKind::DeviceToHost,
+        stream
+    ))?;
+    check!(cuda::cudaStreamSynchronize(stream))?;
+
+    Ok(host.into_iter().map(|x| x.as_f32()).collect())
+}
+
+/// An area of device memory that grows as necessary.
+struct Buffer {
+    ptr: *mut c_void,
+    size_in_bytes: usize
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe { cuda::cudaFree(self.ptr) };
+        }
+    }
+}
+
+impl Buffer {
+    fn new() -> Buffer {
+        Buffer {
+            ptr: ptr::null_mut(),
+            size_in_bytes: 0
+        }
+    }
+
+    /// Returns a pointer to an area of at least `size_in_bytes` bytes,
+    /// re-allocating the buffer if it is too small.
+    ///
+    /// # Arguments
+    ///
+    /// * `size_in_bytes` - the minimum required size of the allocated area
+    /// * `stream` - the stream that will use the memory
+    ///
+    unsafe fn reserve(&mut self, size_in_bytes: usize, stream: cuda::Stream) -> Result<*mut c_void, Error> {
+        if self.size_in_bytes < size_in_bytes || self.ptr.is_null() {
+            if !self.ptr.is_null() {
+                check!(cuda::cudaFree(self.ptr))?;
+            }
+
+            self.ptr = ptr::null_mut();
+            self.size_in_bytes = 0;
+
+            check!(cuda::cudaMalloc(&mut self.ptr, ::std::cmp::max(1, size_in_bytes)))?;
+            check!(cuda::cudaMemsetAsync(self.ptr, 0, size_in_bytes, stream))?;
+
+            self.size_in_bytes = size_in_bytes;
+        }
+
+        Ok(self.ptr)
+    }
+}
+
+/// The filter and convolution descriptors, and the algorithm, for a single
+/// convolution shape.
+struct ConvolutionDescriptor {
+    filter: cudnn::FilterDescriptor,
+    descr: cudnn::ConvolutionDescriptor,
+    fwd_algo: cudnn::ConvolutionFwdAlgoPerf
+}
+
+impl Drop for ConvolutionDescriptor {
+    fn drop(&mut self) {
+        unsafe {
+            cudnn::cudnnDestroyFilterDescriptor(self.filter);
+            cudnn::cudnnDestroyConvolutionDescriptor(self.descr);
+        }
+    }
+}
+
+/// A backend that evaluates the neural network on a CUDA device using
+/// cuDNN and cuBLAS.
+#[derive(Default)]
+pub struct Cuda;
+
+pub struct CudaWorkspace {
+    batch_size: usize,
+    device_id: i32,
+
+    handle_dnn: cudnn::Handle,
+    handle_blas: cublas::Handle,
+
+    tower_finished: cuda::Event,
+    streams: [cuda::Stream; NUM_STREAMS],
+
+    relu: cudnn::ActivationDescriptor,
+    tanh: cudnn::ActivationDescriptor,
+
+    slots: Vec<Buffer>,
+    scratch: Vec<Buffer>,
+    tensors: HashMap<(i32, i32, i32), cudnn::TensorDescriptor>,
+    convolutions: HashMap<(usize, usize, usize), ConvolutionDescriptor>
+}
+
+unsafe impl Send for CudaWorkspace { }  // this is safe because a workspace is only used by one thread at a time
+
+impl Drop for CudaWorkspace {
+    fn drop(&mut self) {
+        unsafe {
+            for (_, descr) in self.tensors.drain() {
+                cudnn::cudnnDestroyTensorDescriptor(descr);
+            }
+
+            self.convolutions.clear();
+
+            cudnn::cudnnDestroyActivationDescriptor(self.relu);
+            cudnn::cudnnDestroyActivationDescriptor(self.tanh);
+
+            cuda::cudaEventDestroy(self.tower_finished);
+
+            for &stream in self.streams.iter() {
+                cuda::cudaStreamDestroy(stream);
+            }
+
+            cublas::cublasDestroy_v2(self.handle_blas);
+            cudnn::cudnnDestroy(self.handle_dnn);
+        }
+    }
+}
+
+impl CudaWorkspace {
+    /// Set the stream of the cuDNN and cuBLAS handles to the given stream,
+    /// and returns it.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` -
+    ///
+    unsafe fn set_stream(&self, stream: Stream) -> Result<cuda::Stream, Error> {
+        let stream = self.streams[stream as usize];
+
+        check!(cudnn::cudnnSetStream(self.handle_dnn, stream))?;
+        check!(cublas::cublasSetStream_v2(self.handle_blas, stream))?;
+
+        Ok(stream)
+    }
+
+    /// Returns a pointer to the memory of the given slot, that is large
+    /// enough to contain `num_elements` elements of any `InferenceType`.
+    ///
+    /// # Arguments
+    ///
+    /// * `slot` -
+    /// * `num_elements` -
+    /// * `stream` -
+    ///
+    unsafe fn get_slot(&mut self, slot: Slot, num_elements: usize, stream: cuda::Stream) -> Result<*mut c_void, Error> {
+        self.slots[slot as usize].reserve(size_of::<f32>() * num_elements, stream)
+    }
+
+    /// Returns a (cached) `NHWC` tensor descriptor with the given shape.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - the number of images
+    /// * `c` - the number of channels
+    /// * `hw` - the height and width of each image
+    ///
+    unsafe fn get_tensor(&mut self, n: i32, c: i32, hw: i32) -> Result<cudnn::TensorDescriptor, Error> {
+        if let Some(&descr) = self.tensors.get(&(n, c, hw)) {
+            return Ok(descr);
+        }
+
+        let mut descr = ptr::null();
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut descr))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            descr,
+            cudnn::TensorFormat::NHWC,
+            cudnn::DataType::Half,
+            n, c, hw, hw
+        ))?;
+
+        self.tensors.insert((n, c, hw), descr);
+        Ok(descr)
+    }
+
+    /// Returns the (cached) filter descriptor, convolution descriptor, and
+    /// forward algorithm for the given convolution.
+    ///
+    /// # Arguments
+    ///
+    /// * `conv` -
+    ///
+    unsafe fn get_convolution(&mut self, conv: &Convolution) -> Result<(cudnn::FilterDescriptor, cudnn::ConvolutionDescriptor, cudnn::ConvolutionFwdAlgo, usize), Error> {
+        let key = (conv.in_channels, conv.out_channels, conv.size);
+
+        if !self.convolutions.contains_key(&key) {
+            let n = self.batch_size as i32;
+            let input = self.get_tensor(n, conv.in_channels as i32, 19)?;
+            let output = self.get_tensor(n, conv.out_channels as i32, 19)?;
+            let pad = (conv.size / 2) as i32;
+            let mut out = ConvolutionDescriptor {
+                filter: ptr::null(),
+                descr: ptr::null(),
+                fwd_algo: cudnn::ConvolutionFwdAlgoPerf::new()
+            };
+
+            check!(cudnn::cudnnCreateFilterDescriptor(&mut out.filter))?;
+            check!(cudnn::cudnnSetFilter4dDescriptor(
+                out.filter,
+                cudnn::DataType::Half,
+                cudnn::TensorFormat::NHWC,
+                conv.out_channels as i32, conv.in_channels as i32, conv.size as i32, conv.size as i32
+            ))?;
+
+            check!(cudnn::cudnnCreateConvolutionDescriptor(&mut out.descr))?;
+            check!(cudnn::cudnnSetConvolution2dDescriptor(
+                out.descr,
+                pad, pad, 1, 1, 1, 1,
+                cudnn::ConvolutionMode::CrossCorrelation,
+                if has_true_half() { cudnn::DataType::Half } else { cudnn::DataType::Float }
+            ))?;
+
+            #[cfg(feature = "tensor-core")] {
+                check!(cudnn::cudnnSetConvolutionMathType(out.descr, cudnn::MathType::TensorOpMath))?;
+            }
+
+            // determine the best algorithm to use for this convolution
+            let mut num_fwd_algo = 0;
+
+            check!(cudnn::cudnnGetConvolutionForwardAlgorithm_v7(
+                self.handle_dnn,
+                input,
+                out.filter,
+                out.descr,
+                output,
+                1, &mut num_fwd_algo, &mut out.fwd_algo
+            ))?;
+
+            assert!(num_fwd_algo > 0);
+
+            self.convolutions.insert(key, out);
+        }
+
+        let out = &self.convolutions[&key];
+
+        Ok((out.filter, out.descr, out.fwd_algo.algo, out.fwd_algo.memory))
+    }
+
+    /// Computes `y = act(x)` in-place or out-of-place.
+    ///
+    /// # Arguments
+    ///
+    /// * `act` -
+    /// * `descr` - the descriptor of both `x` and `y`
+    /// * `x` -
+    /// * `y` -
+    ///
+    unsafe fn activate(
+        &self,
+        act: Activation,
+        descr: cudnn::TensorDescriptor,
+        x: *const c_void,
+        y: *mut c_void
+    ) -> Result<(), Error>
+    {
+        match act {
+            Activation::Identity => {
+                if x != y as *const c_void {
+                    check!(cudnn::cudnnAddTensor(
+                        self.handle_dnn,
+                        &ONE, descr, x,
+                        &ZERO, descr, y
+                    ))?;
+                }
+            },
+            Activation::Relu | Activation::Tanh => {
+                check!(cudnn::cudnnActivationForward(
+                    self.handle_dnn,
+                    if act == Activation::Relu { self.relu } else { self.tanh },
+                    &ONE, descr, x,  // input
+                    &ZERO, descr, y,  // output
+                ))?;
+            },
+            Activation::Softmax => {
+                check!(cudnn::cudnnSoftmaxForward(
+                    self.handle_dnn,
+                    cudnn::SoftmaxAlgorithm::Accurate,
+                    cudnn::SoftmaxMode::Channel,
+                    &ONE, descr, x,  // input
+                    &ZERO, descr, y,  // output
+                ))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Create an activation descriptor for the given mode.
+///
+/// # Arguments
+///
+/// * `mode` -
+///
+unsafe fn create_activation(mode: cudnn::ActivationMode) -> Result<cudnn::ActivationDescriptor, Error> {
+    let mut descr = ptr::null();
+
+    check!(cudnn::cudnnCreateActivationDescriptor(&mut descr))?;
+    check!(cudnn::cudnnSetActivationDescriptor(
+        descr,
+        mode,
+        cudnn::NanPropagation::NotPropagateNan,
+        0.0
+    ))?;
+
+    Ok(descr)
+}
+
+impl Backend for Cuda {
+    type Workspace = CudaWorkspace;
+
+    fn name(&self) -> &'static str {
+        "cuda"
+    }
+
+    fn is_half(&self) -> bool {
+        has_true_half()
+    }
+
+    fn get_workspace(&self, batch_size: usize) -> Result<CudaWorkspace, Error> {
+        let mut w = CudaWorkspace {
+            batch_size: batch_size,
+            device_id: get_current_device()?,
+
+            handle_dnn: ptr::null(),
+            handle_blas: ptr::null(),
+
+            tower_finished: ptr::null(),
+            streams: [ptr::null(); NUM_STREAMS],
+
+            relu: ptr::null(),
+            tanh: ptr::null(),
+
+            slots: (0..SLOT_SIZE).map(|_| Buffer::new()).collect(),
+            scratch: (0..NUM_STREAMS).map(|_| Buffer::new()).collect(),
+            tensors: HashMap::new(),
+            convolutions: HashMap::new()
+        };
+
+        unsafe {
+            check!(cudnn::cudnnCreate(&mut w.handle_dnn))?;
+            check!(cublas::cublasCreate_v2(&mut w.handle_blas))?;
+
+            for stream in w.streams.iter_mut() {
+                check!(cuda::cudaStreamCreateWithFlags(stream, 1))?;
+            }
+
+            check!(cuda::cudaEventCreateWithFlags(&mut w.tower_finished, 2))?;
+
+            w.relu = create_activation(cudnn::ActivationMode::Relu)?;
+            w.tanh = create_activation(cudnn::ActivationMode::Tanh)?;
+
+            #[cfg(feature = "tensor-core")] {
+                check!(cublas::cublasSetMathMode(w.handle_blas, cublas::Math::TensorOp))?;
+            }
+        }
+
+        Ok(w)
+    }
+
+    fn upload(
+        &self,
+        workspace: &mut CudaWorkspace,
+        stream: Stream,
+        tensor: &Tensor,
+        scale: f32
+    ) -> Result<(), Error>
+    {
+        unsafe {
+            let stream = workspace.set_stream(stream)?;
+
+            if tensor.copy_to_device(workspace.device_id, stream)? && scale != 1.0 {
+                let descr = workspace.get_tensor(1, tensor.size_in_elements as i32, 1)?;
+
+                check!(cudnn::cudnnScaleTensor(
+                    workspace.handle_dnn,
+                    descr, tensor.get(workspace.device_id),
+                    &scale
+                ))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_input<T: InferenceType>(
+        &self,
+        workspace: &mut CudaWorkspace,
+        features: &[T],
+        output: Slot
+    ) -> Result<(), Error>
+    {
+        unsafe {
+            let stream = workspace.set_stream(Stream::Tower)?;
+            let input = workspace.get_slot(output, features.len(), stream)?;
+
+            check!(cuda::cudaMemcpyAsync(
+                input,
+                features.as_ptr() as *const c_void,
+                size_of::<T>() * features.len(),
+                cuda::MemcpyKind::HostToDevice,
+                stream
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    fn get_output<T: InferenceType>(
+        &self,
+        workspace: &mut CudaWorkspace,
+        stream: Stream,
+        input: Slot,
+        num_elements: usize
+    ) -> Result<Vec<f32>, Error>
+    {
+        unsafe {
+            let stream = workspace.streams[stream as usize];
+            let input = workspace.get_slot(input, num_elements, stream)?;
+
+            load_to_host::<T>(input, num_elements, stream)
+        }
+    }
+
+    fn convolution(
+        &self,
+        workspace: &mut CudaWorkspace,
+        stream: Stream,
+        conv: &Convolution,
+        alpha: f32,
+        input: Slot,
+        beta: f32,
+        output: Slot,
+        act: Activation
+    ) -> Result<(), Error>
+    {
+        let n = workspace.batch_size as i32;
+        let device_id = workspace.device_id;
+
+        unsafe {
+            let cuda_stream = workspace.set_stream(stream)?;
+
+            conv.weights.copy_to_device(device_id, cuda_stream)?;
+            conv.offset.copy_to_device(device_id, cuda_stream)?;
+
+            let x_descr = workspace.get_tensor(n, conv.in_channels as i32, 19)?;
+            let y_descr = workspace.get_tensor(n, conv.out_channels as i32, 19)?;
+            let b_descr = workspace.get_tensor(1, conv.out_channels as i32, 1)?;
+            let (filter, descr, algo, memory) = workspace.get_convolution(conv)?;
+            let scratch = workspace.scratch[stream as usize].reserve(memory, cuda_stream)?;
+            let x = workspace.get_slot(input, workspace.batch_size * 361 * conv.in_channels, cuda_stream)?;
+            let y = workspace.get_slot(output, workspace.batch_size * 361 * conv.out_channels, cuda_stream)?;
+
+            if act == Activation::Relu {
+                check!(cudnn::cudnnConvolutionBiasActivationForward(
+                    workspace.handle_dnn,
+                    &alpha,
+                    x_descr, x,
+                    filter, conv.weights.get(device_id),
+                    descr, algo,
+                    scratch, memory,
+                    &beta,
+                    y_descr, y,
+                    b_descr, conv.offset.get(device_id),
+                    workspace.relu,
+                    y_descr, y
+                ))?;
+            } else {
+                // the fused convolution only supports the `relu` activation,
+                // so perform the operations separately for everything else
+                check!(cudnn::cudnnConvolutionForward(
+                    workspace.handle_dnn,
+                    &alpha,
+                    x_descr, x,
+                    filter, conv.weights.get(device_id),
+                    descr, algo,
+                    scratch, memory,
+                    &beta,
+                    y_descr, y
+                ))?;
+
+                check!(cudnn::cudnnAddTensor(
+                    workspace.handle_dnn,
+                    &ONE, b_descr, conv.offset.get(device_id),
+                    &ONE, y_descr, y
+                ))?;
+
+                workspace.activate(act, y_descr, y, y)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn gemm(
+        &self,
+        workspace: &mut CudaWorkspace,
+        stream: Stream,
+        weights: &Tensor,
+        num_inputs: usize,
+        num_outputs: usize,
+        input: Slot,
+        output: Slot
+    ) -> Result<(), Error>
+    {
+        let batch_size = workspace.batch_size;
+        let device_id = workspace.device_id;
+
+        unsafe {
+            let cuda_stream = workspace.set_stream(stream)?;
+
+            weights.copy_to_device(device_id, cuda_stream)?;
+
+            let x = workspace.get_slot(input, batch_size * num_inputs, cuda_stream)?;
+            let y = workspace.get_slot(output, batch_size * num_outputs, cuda_stream)?;
+
+            check!(cublas::cublasGemmEx(
+                workspace.handle_blas,
+                cublas::Operation::N,
+                cublas::Operation::N,
+                num_outputs as i32, batch_size as i32, num_inputs as i32,  // output, batch_size, input
+                &ONE as *const f32 as *const c_void,
+                weights.get(device_id), cuda::DataType::R16F, num_outputs as i32,  // input_2
+                x, cuda::DataType::R16F, num_inputs as i32,  // input_1
+                &ZERO as *const f32 as *const c_void,
+                y, cuda::DataType::R16F, num_outputs as i32,  // output
+                cuda::DataType::R32F, cublas::GemmAlgo::DfaltTensorOp
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    fn offset(
+        &self,
+        workspace: &mut CudaWorkspace,
+        stream: Stream,
+        offset: &Tensor,
+        alpha: f32,
+        beta: f32,
+        output: Slot
+    ) -> Result<(), Error>
+    {
+        let n = workspace.batch_size as i32;
+        let c = offset.size_in_elements as i32;
+        let device_id = workspace.device_id;
+
+        unsafe {
+            let cuda_stream = workspace.set_stream(stream)?;
+
+            offset.copy_to_device(device_id, cuda_stream)?;
+
+            let b_descr = workspace.get_tensor(1, c, 1)?;
+            let y_descr = workspace.get_tensor(n, c, 1)?;
+            let y = workspace.get_slot(output, (n * c) as usize, cuda_stream)?;
+
+            check!(cudnn::cudnnAddTensor(
+                workspace.handle_dnn,
+                &alpha, b_descr, offset.get(device_id),
+                &beta, y_descr, y
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    fn activation(
+        &self,
+        workspace: &mut CudaWorkspace,
+        stream: Stream,
+        act: Activation,
+        num_channels: usize,
+        input: Slot,
+        output: Slot
+    ) -> Result<(), Error>
+    {
+        let n = workspace.batch_size;
+
+        unsafe {
+            let cuda_stream = workspace.set_stream(stream)?;
+            let descr = workspace.get_tensor(n as i32, num_channels as i32, 1)?;
+            let x = workspace.get_slot(input, n * num_channels, cuda_stream)?;
+            let y = workspace.get_slot(output, n * num_channels, cuda_stream)?;
+
+            workspace.activate(act, descr, x, y)
+        }
+    }
+
+    fn fork(&self, workspace: &mut CudaWorkspace) -> Result<(), Error> {
+        let tower_stream = workspace.streams[Stream::Tower as usize];
+
+        unsafe {
+            check!(cuda::cudaEventRecord(workspace.tower_finished, tower_stream))?;
+            check!(cuda::cudaStreamWaitEvent(workspace.streams[Stream::Value as usize], workspace.tower_finished, 0))?;
+            check!(cuda::cudaStreamWaitEvent(workspace.streams[Stream::Policy as usize], workspace.tower_finished, 0))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod cpu;
+#[cfg(feature = "cuda")] mod cuda;
+
+pub use self::cpu::Cpu;
+#[cfg(feature = "cuda")] pub use self::cuda::Cuda;
+
+use super::graph::InferenceType;
+use super::tensor::Tensor;
+use super::Error;
+
+/// The named intermediate buffers of a forward pass. Each backend keep one
+/// buffer per slot and workspace, so two operations that refer to the same
+/// slot refer to the same memory.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum Slot {
+    Input = 0,
+    Policy_1 = 1,
+    Policy_2 = 2,
+    Policy_3 = 3,
+    Value_1 = 4,
+    Value_2 = 5,
+    Value_3 = 6,
+    Residual_1 = 7,
+    Residual_2 = 8,
+    Value_4 = 9,
+}
+
+/// The total number of elements in the `Slot` enum.
+pub const SLOT_SIZE: usize = 10;
+
+/// The streams that operations can be scheduled on. Operations on
+/// different streams may be executed concurrently, until they are
+/// synchronized using `Backend::fork` or `Backend::get_output`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stream {
+    Tower = 0,
+    Value = 1,
+    Policy = 2
+}
+
+/// The activation functions that a backend has to support.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Activation {
+    Identity,
+    Relu,
+    Tanh,
+    Softmax
+}
+
+/// Description of a convolution over a `19x19` image in `NHWC` format, with
+/// _same_ padding and a filter stored in `KRSC` format.
+pub struct Convolution<'a> {
+    pub weights: &'a Tensor,
+    pub offset: &'a Tensor,
+    pub in_channels: usize,
+    pub out_channels: usize,
+    pub size: usize
+}
+
+/// The primitive operations that a device needs to implement to evaluate
+/// the neural network. All operations work on the memory of one of the
+/// named `Slot`s of the given workspace, which the backend is responsible
+/// for allocating.
+pub trait Backend: Send + Sync {
+    type Workspace: Send;
+
+    /// Returns the name of this backend.
+    fn name(&self) -> &'static str;
+
+    /// Returns true if this backend evaluates the network using half
+    /// precision floats.
+    fn is_half(&self) -> bool;
+
+    /// Returns a new workspace for the current thread, that can evaluate
+    /// batches of the given size.
+    ///
+    /// # Arguments
+    ///
+    /// * `batch_size` -
+    ///
+    fn get_workspace(&self, batch_size: usize) -> Result<Self::Workspace, Error>;
+
+    /// Upload the given tensor, multiplied by `scale`, to the memory of this
+    /// backend. If the tensor has already been uploaded then this does
+    /// nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` -
+    /// * `stream` -
+    /// * `tensor` -
+    /// * `scale` -
+    ///
+    fn upload(
+        &self,
+        workspace: &mut Self::Workspace,
+        stream: Stream,
+        tensor: &Tensor,
+        scale: f32
+    ) -> Result<(), Error>;
+
+    /// Copy the given host features to the given slot.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` -
+    /// * `features` -
+    /// * `output` -
+    ///
+    fn set_input<T: InferenceType>(
+        &self,
+        workspace: &mut Self::Workspace,
+        features: &[T],
+        output: Slot
+    ) -> Result<(), Error>;
+
+    /// Returns the first `num_elements` elements of the given slot, after all
+    /// operations on `stream` has finished.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` -
+    /// * `stream` -
+    /// * `input` -
+    /// * `num_elements` -
+    ///
+    fn get_output<T: InferenceType>(
+        &self,
+        workspace: &mut Self::Workspace,
+        stream: Stream,
+        input: Slot,
+        num_elements: usize
+    ) -> Result<Vec<f32>, Error>;
+
+    /// Computes `output = act(alpha * conv(input) + beta * output + offset)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` -
+    /// * `stream` -
+    /// * `conv` -
+    /// * `alpha` -
+    /// * `input` -
+    /// * `beta` -
+    /// * `output` -
+    /// * `act` -
+    ///
+    fn convolution(
+        &self,
+        workspace: &mut Self::Workspace,
+        stream: Stream,
+        conv: &Convolution,
+        alpha: f32,
+        input: Slot,
+        beta: f32,
+        output: Slot,
+        act: Activation
+    ) -> Result<(), Error>;
+
+    /// Computes `output = input * weights`, where `input` is a
+    /// `batch_size x num_inputs` matrix and `weights` is a
+    /// `num_inputs x num_outputs` matrix.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` -
+    /// * `stream` -
+    /// * `weights` -
+    /// * `num_inputs` -
+    /// * `num_outputs` -
+    /// * `input` -
+    /// * `output` -
+    ///
+    fn gemm(
+        &self,
+        workspace: &mut Self::Workspace,
+        stream: Stream,
+        weights: &Tensor,
+        num_inputs: usize,
+        num_outputs: usize,
+        input: Slot,
+        output: Slot
+    ) -> Result<(), Error>;
+
+    /// Computes `output = alpha * offset + beta * output`, where `offset` is
+    /// broadcast over every element of the batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` -
+    /// * `stream` -
+    /// * `offset` -
+    /// * `alpha` -
+    /// * `beta` -
+    /// * `output` -
+    ///
+    fn offset(
+        &self,
+        workspace: &mut Self::Workspace,
+        stream: Stream,
+        offset: &Tensor,
+        alpha: f32,
+        beta: f32,
+        output: Slot
+    ) -> Result<(), Error>;
+
+    /// Computes `output = act(input)`, where each element in the batch
+    /// consists of `num_channels` elements.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` -
+    /// * `stream` -
+    /// * `act` -
+    /// * `num_channels` -
+    /// * `input` -
+    /// * `output` -
+    ///
+    fn activation(
+        &self,
+        workspace: &mut Self::Workspace,
+        stream: Stream,
+        act: Activation,
+        num_channels: usize,
+        input: Slot,
+        output: Slot
+    ) -> Result<(), Error>;
+
+    /// Make all operations scheduled on the `Value` and `Policy` streams
+    /// after this call wait for the operations already scheduled on the
+    /// `Tower` stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` -
+    ///
+    fn fork(&self, workspace: &mut Self::Workspace) -> Result<(), Error>;
+}
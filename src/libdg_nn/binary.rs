@@ -0,0 +1,487 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::slice;
+
+use dg_go::utils::features::NUM_FEATURES;
+use super::graph::DEFAULT_NUM_CHANNELS;
+use super::tensor::Tensor;
+use super::{Error, loader};
+
+/// The bytes that every binary weights file starts with.
+pub const MAGIC: [u8; 4] = *b"DGW\0";
+
+/// The version of the binary format that is written by this module, which
+/// is also the only version that can be read.
+pub const VERSION: u32 = 1;
+
+/// The type of the elements of a tensor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DataType {
+    Half = 0,
+    Single = 1,
+    Int = 2
+}
+
+impl DataType {
+    /// Returns the data type with the given tag, if there is one.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` -
+    ///
+    fn from_tag(tag: u8) -> Option<DataType> {
+        match tag {
+            0 => Some(DataType::Half),
+            1 => Some(DataType::Single),
+            2 => Some(DataType::Int),
+            _ => None
+        }
+    }
+
+    /// Returns the data type of the tensor with the given name. The JSON
+    /// weights does not store this, so it has to be inferred from how the
+    /// trainer dumps each tensor.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the tensor
+    ///
+    pub fn of(name: &str) -> DataType {
+        if name == "num_channels:0" || name == "num_blocks:0" {
+            DataType::Int
+        } else if name.ends_with("/alpha:0") {
+            DataType::Single
+        } else {
+            DataType::Half
+        }
+    }
+
+    /// Returns the size of a single element of this data type in bytes.
+    pub fn size_in_bytes(self) -> usize {
+        match self {
+            DataType::Half => 2,
+            DataType::Single => 4,
+            DataType::Int => 4
+        }
+    }
+}
+
+/// The architecture of a network.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Architecture {
+    /// The number of channels in each convolution.
+    pub num_channels: usize,
+
+    /// The number of residual blocks.
+    pub num_residual: usize,
+
+    /// The number of input features.
+    pub num_features: usize
+}
+
+impl Architecture {
+    /// Returns the architecture of the network with the given weights, as
+    /// understood by `graph::Builder`, or `Error::MissingWeights` if the
+    /// input layer is missing.
+    ///
+    /// # Arguments
+    ///
+    /// * `tensors` -
+    ///
+    pub fn of(tensors: &HashMap<String, Tensor>) -> Result<Architecture, Error> {
+        let num_channels = tensors.get("num_channels:0")
+            .map(|x| unsafe { x.as_i32() })
+            .unwrap_or(DEFAULT_NUM_CHANNELS) as usize;
+        let num_residual = (2..)
+            .take_while(|i| {
+                tensors.contains_key(&format!("{:02}_residual/conv_1:0", i)) &&
+                    tensors.contains_key(&format!("{:02}_residual/conv_2:0", i))
+            })
+            .count();
+        let upsample = tensors.get("01_upsample/conv_1:0").ok_or(Error::MissingWeights)?;
+        let num_elements = upsample.size_in_bytes / DataType::Half.size_in_bytes();
+
+        Ok(Architecture {
+            num_channels: num_channels,
+            num_residual: num_residual,
+            num_features: num_elements / (9 * num_channels.max(1))
+        })
+    }
+}
+
+/// The meta-data stored in the header of a binary weights file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Header {
+    /// The version of the format the file was written with.
+    pub version: u32,
+
+    /// The architecture of the network.
+    pub architecture: Architecture,
+
+    /// A free-form description of where the weights came from.
+    pub provenance: String,
+
+    /// The checksum of the file content.
+    pub checksum: u64
+}
+
+/// Returns the 64-bit _FNV-1a_ hash of the given bytes.
+///
+/// # Arguments
+///
+/// * `bytes` -
+///
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Returns true if the given bytes looks like the start of a binary weights
+/// file.
+///
+/// # Arguments
+///
+/// * `bytes` -
+///
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// A cursor over a byte slice that returns `Error::MalformedWeights` if
+/// reading past the end of it.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl<'a> Reader<'a> {
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        let end = self.position.checked_add(count).ok_or(Error::MalformedWeights)?;
+
+        if end > self.bytes.len() {
+            Err(Error::MalformedWeights)
+        } else {
+            let out = &self.bytes[self.position..end];
+            self.position = end;
+
+            Ok(out)
+        }
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.read_bytes(4)?);
+
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, Error> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.read_bytes(8)?);
+
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn read_f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_bits(self.read_u32()?))
+    }
+
+    fn read_string(&mut self) -> Result<String, Error> {
+        let length = self.read_u32()? as usize;
+
+        String::from_utf8(self.read_bytes(length)?.to_vec()).map_err(|_| Error::MalformedWeights)
+    }
+}
+
+/// Returns the header and all tensors in the given binary weights file. The
+/// checksum and the architecture in the header are verified against the
+/// content of the file, and the architecture against what this program
+/// supports.
+///
+/// # Arguments
+///
+/// * `bytes` - the content of the file
+///
+pub fn read(bytes: &[u8]) -> Result<(Header, HashMap<String, Tensor>), Error> {
+    let mut reader = Reader { bytes: bytes, position: 0 };
+
+    if reader.read_bytes(MAGIC.len())? != MAGIC {
+        return Err(Error::MalformedWeights);
+    }
+
+    let version = reader.read_u32()?;
+    if version != VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+
+    let checksum = reader.read_u64()?;
+    if fnv1a(&bytes[reader.position..]) != checksum {
+        return Err(Error::ChecksumMismatch);
+    }
+
+    let architecture = Architecture {
+        num_channels: reader.read_u32()? as usize,
+        num_residual: reader.read_u32()? as usize,
+        num_features: reader.read_u32()? as usize
+    };
+    let provenance = reader.read_string()?;
+    let num_tensors = reader.read_u32()?;
+    let mut tensors = HashMap::with_capacity(num_tensors as usize);
+
+    for _ in 0..num_tensors {
+        let name = reader.read_string()?;
+        let data_type = DataType::from_tag(reader.read_u8()?).ok_or(Error::MalformedWeights)?;
+        let scale = reader.read_f32()?;
+        let num_elements = reader.read_u64()? as usize;
+        let size_in_bytes = num_elements.checked_mul(data_type.size_in_bytes()).ok_or(Error::MalformedWeights)?;
+        let mut tensor = Tensor::default();
+
+        tensor.set_host(reader.read_bytes(size_in_bytes)?.to_vec())?;
+        tensor.size_in_elements = num_elements;
+        tensor.scale = scale;
+        tensors.insert(name, tensor);
+    }
+
+    if reader.position != bytes.len() {
+        return Err(Error::MalformedWeights);
+    }
+
+    // the header has to describe the tensors in the file, and the network
+    // has to accept the features that this program produces
+    let actual = Architecture::of(&tensors)?;
+
+    if actual != architecture {
+        return Err(Error::ArchitectureMismatch(format!("the header describes {:?}, but the tensors describe {:?}", architecture, actual)));
+    } else if architecture.num_features != NUM_FEATURES {
+        return Err(Error::ArchitectureMismatch(format!("the network expects {} features, but {} are supported", architecture.num_features, NUM_FEATURES)));
+    }
+
+    let header = Header {
+        version: version,
+        architecture: architecture,
+        provenance: provenance,
+        checksum: checksum
+    };
+
+    Ok((header, tensors))
+}
+
+fn write_u32(out: &mut Vec<u8>, value: usize) {
+    assert!(value <= ::std::u32::MAX as usize);
+
+    out.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    write_u32(out, value.len());
+    out.extend_from_slice(value.as_bytes());
+}
+
+/// Returns the given tensors encoded as a binary weights file, together
+/// with its header. All integers are stored in little-endian byte order,
+/// and the file has the following layout:
+///
+/// * `magic` - the bytes in `MAGIC`
+/// * `version` - `u32`, the bytes in `VERSION`
+/// * `checksum` - `u64`, the FNV-1a hash of every byte after this field
+/// * `num_channels` - `u32`
+/// * `num_residual` - `u32`
+/// * `num_features` - `u32`
+/// * `provenance` - `u32` length, followed by that many UTF-8 bytes
+/// * `num_tensors` - `u32`
+///
+/// Followed by each tensor, sorted by name:
+///
+/// * `name` - `u32` length, followed by that many UTF-8 bytes
+/// * `data_type` - `u8`, see `DataType`
+/// * `scale` - `f32`
+/// * `num_elements` - `u64`
+/// * `data` - `num_elements` elements of the given data type
+///
+/// # Arguments
+///
+/// * `tensors` - the tensors to encode
+/// * `provenance` - a description of where the tensors came from
+///
+pub fn encode(tensors: &HashMap<String, Tensor>, provenance: &str) -> Result<(Header, Vec<u8>), Error> {
+    let architecture = Architecture::of(tensors)?;
+    let mut body = vec! [];
+
+    write_u32(&mut body, architecture.num_channels);
+    write_u32(&mut body, architecture.num_residual);
+    write_u32(&mut body, architecture.num_features);
+    write_string(&mut body, provenance);
+    write_u32(&mut body, tensors.len());
+
+    // sort the tensors by name so that the same weights always produce
+    // the same file, and therefore the same checksum
+    let mut names = tensors.keys().collect::<Vec<_>>();
+    names.sort();
+
+    for name in names {
+        let tensor = &tensors[name];
+        let data_type = DataType::of(name);
+
+        if tensor.size_in_bytes % data_type.size_in_bytes() != 0 {
+            return Err(Error::MalformedWeights);
+        }
+
+        let data: &[u8] = if tensor.host.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(tensor.host as *const u8, tensor.size_in_bytes) }
+        };
+
+        write_string(&mut body, name);
+        body.push(data_type as u8);
+        body.extend_from_slice(&tensor.scale.to_bits().to_le_bytes());
+        body.extend_from_slice(&((tensor.size_in_bytes / data_type.size_in_bytes()) as u64).to_le_bytes());
+        body.extend_from_slice(data);
+    }
+
+    let checksum = fnv1a(&body);
+    let mut out = Vec::with_capacity(16 + body.len());
+
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&checksum.to_le_bytes());
+    out.extend_from_slice(&body);
+
+    let header = Header {
+        version: VERSION,
+        architecture: architecture,
+        provenance: provenance.to_string(),
+        checksum: checksum
+    };
+
+    Ok((header, out))
+}
+
+/// Converts the weights at the given path, in either the JSON or the binary
+/// format, into a binary weights file at `output`. Returns the header of the
+/// written file.
+///
+/// # Arguments
+///
+/// * `input` - the path to the weights to convert
+/// * `output` - the path to write the binary weights file to
+/// * `provenance` - a description of where the weights came from
+///
+pub fn convert(input: &Path, output: &Path, provenance: &str) -> Result<Header, Error> {
+    let (tensors, _source) = loader::load(input)?;
+    let (header, bytes) = encode(&tensors, provenance)?;
+    let mut file = File::create(output).map(BufWriter::new)?;
+
+    file.write_all(&bytes)?;
+    file.flush()?;
+
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dg_utils::types::f16;
+
+    fn tensor<T: Sized>(data: Vec<T>, scale: f32) -> Tensor {
+        let mut out = Tensor::default();
+        out.set_host(data).unwrap();
+        out.scale = scale;
+        out
+    }
+
+    /// Returns the weights of a network with the smallest architecture that
+    /// `Architecture::of` accepts.
+    fn weights() -> HashMap<String, Tensor> {
+        let mut out = HashMap::new();
+
+        out.insert("num_channels:0".to_string(), tensor(vec! [2i32], 1.0));
+        out.insert("01_upsample/conv_1:0".to_string(), tensor(vec! [f16::from(0.5); 9 * 2 * NUM_FEATURES], 0.25));
+        out.insert("02_residual/conv_1:0".to_string(), tensor(vec! [f16::from(1.0); 36], 1.0));
+        out.insert("02_residual/conv_2:0".to_string(), tensor(vec! [f16::from(-1.0); 36], 1.0));
+        out.insert("02_residual/alpha:0".to_string(), tensor(vec! [0.75f32], 1.0));
+        out
+    }
+
+    #[test]
+    fn round_trip() {
+        let (header, bytes) = encode(&weights(), "test").unwrap();
+        let (other, tensors) = read(&bytes).unwrap();
+
+        assert!(is_binary(&bytes));
+        assert_eq!(header, other);
+        assert_eq!(header.provenance, "test");
+        assert_eq!(header.architecture, Architecture { num_channels: 2, num_residual: 1, num_features: NUM_FEATURES });
+        assert_eq!(tensors.len(), 5);
+        assert_eq!(tensors["01_upsample/conv_1:0"].scale, 0.25);
+        assert_eq!(tensors["01_upsample/conv_1:0"].to_f32_vec(), vec! [0.5; 9 * 2 * NUM_FEATURES]);
+        assert_eq!(tensors["02_residual/alpha:0"].to_f32_vec(), vec! [0.75]);
+        assert_eq!(unsafe { tensors["num_channels:0"].as_i32() }, 2);
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let (_, mut bytes) = encode(&weights(), "test").unwrap();
+        bytes[4] = 2;
+
+        match read(&bytes) {
+            Err(Error::UnsupportedVersion(2)) => {},
+            other => panic!("{:?}", other.map(|(header, _)| header))
+        }
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let (_, mut bytes) = encode(&weights(), "test").unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        match read(&bytes) {
+            Err(Error::ChecksumMismatch) => {},
+            other => panic!("{:?}", other.map(|(header, _)| header))
+        }
+    }
+
+    #[test]
+    fn architecture_mismatch() {
+        let mut tensors = weights();
+        tensors.insert("01_upsample/conv_1:0".to_string(), tensor(vec! [f16::from(0.5); 9 * 2 * (NUM_FEATURES + 1)], 1.0));
+        let (_, bytes) = encode(&tensors, "test").unwrap();
+
+        match read(&bytes) {
+            Err(Error::ArchitectureMismatch(_)) => {},
+            other => panic!("{:?}", other.map(|(header, _)| header))
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let (_, bytes) = encode(&weights(), "test").unwrap();
+
+        match read(&bytes[..bytes.len() / 2]) {
+            Err(Error::ChecksumMismatch) => {},
+            other => panic!("{:?}", other.map(|(header, _)| header))
+        }
+    }
+}
@@ -12,11 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(feature = "cuda")]
 #[link(name = "cudnn")]
 extern {
     fn cudnnGetProperty(property: i32, value: *mut i32) -> i32;
 }
 
+#[cfg(feature = "cuda")]
 fn main() {
     // determine the cuDNN version
     let mut major_version = 0;
@@ -34,3 +36,8 @@ fn main() {
         println!("cargo:rustc-cfg=feature=\"tensor-core\"");
     }
 }
+
+#[cfg(not(feature = "cuda"))]
+fn main() {
+    // nothing to detect without cuDNN
+}
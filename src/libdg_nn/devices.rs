@@ -12,38 +12,53 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::ffi::cuda;
+#[cfg(feature = "cuda")] use super::ffi::cuda;
 use super::Error;
 
 /// The maximum number of devices that we support. 
 pub const MAX_DEVICES: usize = 8;
 
 lazy_static! {
-    pub static ref DEVICES: Vec<i32> = {
-        let devices: Vec<i32> = unsafe {
-            let mut count: i32 = 0;
-
-            check!(cuda::cudaGetDeviceCount(&mut count)).expect("Failed to get the number of devices");
-
-            (0..count).filter(|&device_id| {
-                match is_supported(device_id) {
-                    Ok(supported) => supported,
-                    Err(reason) => {
-                        eprintln!("Failed to determine the compute capabilities of device {} -- {:?}", device_id, reason);
-                        false
-                    }
-                }
-            }).collect()
-        };
-
-        if devices.is_empty() {
-            panic!("No device available with the required compute capacity (6.1)");
+    /// All CUDA devices with the required compute capability (6.1), this
+    /// is empty if no such device is available in which case the neural
+    /// network is evaluated on the CPU instead.
+    pub static ref DEVICES: Vec<i32> = find_devices();
+}
+
+/// Returns all CUDA devices with the required compute capability.
+#[cfg(feature = "cuda")]
+fn find_devices() -> Vec<i32> {
+    let mut count: i32 = 0;
+
+    if !unsafe { cuda::cudaGetDeviceCount(&mut count) }.is_ok() {
+        count = 0;
+    }
+
+    let devices: Vec<i32> = (0..count).filter(|&device_id| {
+        match is_supported(device_id) {
+            Ok(supported) => supported,
+            Err(reason) => {
+                eprintln!("Failed to determine the compute capabilities of device {} -- {:?}", device_id, reason);
+                false
+            }
         }
+    }).collect();
+
+    if devices.is_empty() {
+        eprintln!("No device available with the required compute capacity (6.1), falling back to the CPU");
+    }
 
-        devices
-    };
+    devices
 }
 
+/// Returns all CUDA devices with the required compute capability, which is
+/// always none if this crate was built without support for CUDA.
+#[cfg(not(feature = "cuda"))]
+fn find_devices() -> Vec<i32> {
+    vec! []
+}
+
+#[cfg(feature = "cuda")]
 pub fn get_current_device() -> Result<i32, Error> {
     unsafe {
         let mut device_id: i32 = 0;
@@ -53,6 +68,12 @@ pub fn get_current_device() -> Result<i32, Error> {
     }
 }
 
+#[cfg(not(feature = "cuda"))]
+pub fn get_current_device() -> Result<i32, Error> {
+    Ok(0)
+}
+
+#[cfg(feature = "cuda")]
 pub fn set_current_device(device_id: i32) -> Result<(), Error> {
     unsafe {
         check!(cuda::cudaSetDevice(device_id))?;
@@ -60,7 +81,13 @@ pub fn set_current_device(device_id: i32) -> Result<(), Error> {
     }
 }
 
+#[cfg(not(feature = "cuda"))]
+pub fn set_current_device(_device_id: i32) -> Result<(), Error> {
+    Ok(())
+}
+
 /// Returns the version of the CUDA Runtime library.
+#[cfg(feature = "cuda")]
 fn runtime_version() -> Result<i32, Error> {
     let mut runtime_version: i32 = 0;
 
@@ -73,6 +100,7 @@ fn runtime_version() -> Result<i32, Error> {
 
 /// Returns the major and minor version (in that order) of the CUDA
 /// Compute Capability for the currently selected device.
+#[cfg(feature = "cuda")]
 fn compute_capability(device_id: i32) -> Result<(i32, i32), Error> {
     let mut version_major: i32 = 0;
     let mut version_minor: i32 = 0;
@@ -90,6 +118,7 @@ fn compute_capability(device_id: i32) -> Result<(i32, i32), Error> {
 /// There is no flag that NVIDIA expose to determine this, so we
 /// determine this by the CUDA version (>= 8) and the compute
 /// capabilities (6.1+).
+#[cfg(feature = "cuda")]
 fn is_supported(device_id: i32) -> Result<bool, Error> {
     let (major, minor) = compute_capability(device_id)?;
     let version = runtime_version()?;
@@ -12,16 +12,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::ffi::{cudnn, cuda, cublas};
+use std::io;
+
+#[cfg(feature = "cuda")] use super::ffi::{cudnn, cuda, cublas};
 
 #[derive(Debug)]
 pub enum Error {
-    CuDNN(cudnn::Status),
-    Cuda(cuda::Error),
-    CuBLAS(cublas::Status),
-    MissingWeights
+    #[cfg(feature = "cuda")] CuDNN(cudnn::Status),
+    #[cfg(feature = "cuda")] Cuda(cuda::Error),
+    #[cfg(feature = "cuda")] CuBLAS(cublas::Status),
+    Io(io::Error),
+    MissingWeights,
+    MalformedWeights,
+    UnsupportedVersion(u32),
+    ArchitectureMismatch(String),
+    ChecksumMismatch,
+    UnavailableBackend(&'static str)
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
 }
 
+#[cfg(feature = "cuda")]
 impl From<cublas::Status> for Error {
     fn from(s: cublas::Status) -> Error {
         match s {
@@ -31,6 +46,7 @@ impl From<cublas::Status> for Error {
     }
 }
 
+#[cfg(feature = "cuda")]
 impl From<cuda::Error> for Error {
     fn from(s: cuda::Error) -> Error {
         match s {
@@ -40,6 +56,7 @@ impl From<cuda::Error> for Error {
     }
 }
 
+#[cfg(feature = "cuda")]
 impl From<cudnn::Status> for Error {
     fn from(s: cudnn::Status) -> Error {
         match s {
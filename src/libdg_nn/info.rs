@@ -0,0 +1,306 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::slice;
+
+use dg_utils::types::f16;
+use super::backend::Backend;
+use super::binary::{Architecture, DataType};
+use super::loader::Source;
+use super::tensor::Tensor;
+use super::Error;
+
+/// Statistics about the values of a single tensor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TensorInfo {
+    /// The name of the tensor.
+    pub name: String,
+
+    /// The type of the elements of the tensor.
+    pub data_type: DataType,
+
+    /// The number of elements in the tensor.
+    pub num_elements: usize,
+
+    /// The scale that every (non-integer) element is multiplied with before
+    /// it is used.
+    pub scale: f32,
+
+    /// The smallest finite element.
+    pub min: f32,
+
+    /// The largest finite element.
+    pub max: f32,
+
+    /// The mean of the finite elements.
+    pub mean: f32,
+
+    /// The number of elements that are either infinite or `NaN`.
+    pub num_non_finite: usize
+}
+
+impl TensorInfo {
+    /// Returns statistics about the given tensor, after its elements have
+    /// been scaled.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the tensor
+    /// * `tensor` -
+    ///
+    fn new(name: &str, tensor: &Tensor) -> TensorInfo {
+        let data_type = DataType::of(name);
+        let scale = if data_type == DataType::Int { 1.0 } else { tensor.scale };
+        let values = get_values(data_type, tensor).into_iter()
+            .map(|x| scale * x)
+            .collect::<Vec<_>>();
+        let finite = values.iter().cloned().filter(|x| x.is_finite()).collect::<Vec<_>>();
+        let (min, max, mean) = if finite.is_empty() {
+            (0.0, 0.0, 0.0)
+        } else {
+            (
+                finite.iter().cloned().fold(::std::f32::INFINITY, f32::min),
+                finite.iter().cloned().fold(::std::f32::NEG_INFINITY, f32::max),
+                (finite.iter().map(|&x| f64::from(x)).sum::<f64>() / finite.len() as f64) as f32
+            )
+        };
+
+        TensorInfo {
+            name: name.to_string(),
+            data_type: data_type,
+            num_elements: values.len(),
+            scale: scale,
+            min: min,
+            max: max,
+            mean: mean,
+            num_non_finite: values.len() - finite.len()
+        }
+    }
+}
+
+/// Returns the elements of the given tensor as single precision floats,
+/// interpreting the host memory as the given data type.
+///
+/// # Arguments
+///
+/// * `data_type` - the type of the elements
+/// * `tensor` -
+///
+fn get_values(data_type: DataType, tensor: &Tensor) -> Vec<f32> {
+    let num_elements = tensor.size_in_bytes / data_type.size_in_bytes();
+
+    if tensor.host.is_null() || num_elements == 0 {
+        return vec! [];
+    }
+
+    unsafe {
+        match data_type {
+            DataType::Half => {
+                slice::from_raw_parts(tensor.host as *const f16, num_elements).iter()
+                    .map(|&x| f32::from(x))
+                    .collect()
+            },
+            DataType::Single => {
+                slice::from_raw_parts(tensor.host as *const f32, num_elements).to_vec()
+            },
+            DataType::Int => {
+                slice::from_raw_parts(tensor.host as *const i32, num_elements).iter()
+                    .map(|&x| x as f32)
+                    .collect()
+            }
+        }
+    }
+}
+
+/// A description of a loaded network, and of its weights, to make it
+/// possible to tell which network is in use and whether its weights look
+/// sane.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkInfo {
+    /// The path to the file the weights were loaded from.
+    pub path: String,
+
+    /// The version of the binary format, or `None` if the weights were
+    /// loaded from JSON.
+    pub version: Option<u32>,
+
+    /// The checksum of the file the weights were loaded from.
+    pub checksum: u64,
+
+    /// Where the weights came from, if recorded in the file.
+    pub provenance: Option<String>,
+
+    /// The architecture of the network.
+    pub architecture: Architecture,
+
+    /// Whether the network has an ownership head.
+    pub has_ownership: bool,
+
+    /// The total number of trainable parameters.
+    pub num_parameters: usize,
+
+    /// The name of the backend the network is evaluated on.
+    pub backend: &'static str,
+
+    /// Whether the backend evaluates the network using half precision.
+    pub is_half: bool,
+
+    /// Statistics about each tensor, sorted by name.
+    pub tensors: Vec<TensorInfo>
+}
+
+impl NetworkInfo {
+    /// Returns a description of the network with the given weights.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - where the weights were loaded from
+    /// * `tensors` - the weights
+    /// * `backend` - the backend the network is evaluated on
+    /// * `has_ownership` - whether the network has an ownership head
+    ///
+    pub fn new<B: Backend>(
+        source: &Source,
+        tensors: &HashMap<String, Tensor>,
+        backend: &B,
+        has_ownership: bool
+    ) -> Result<NetworkInfo, Error>
+    {
+        let mut names = tensors.keys().collect::<Vec<_>>();
+        names.sort();
+
+        let tensors_info = names.into_iter()
+            .map(|name| TensorInfo::new(name, &tensors[name]))
+            .collect::<Vec<_>>();
+        let num_parameters = tensors_info.iter()
+            .filter(|info| info.data_type != DataType::Int)
+            .map(|info| info.num_elements)
+            .sum();
+
+        Ok(NetworkInfo {
+            path: source.path.clone(),
+            version: source.header.as_ref().map(|header| header.version),
+            checksum: source.checksum,
+            provenance: source.header.as_ref().map(|header| header.provenance.clone()),
+            architecture: Architecture::of(tensors)?,
+            has_ownership: has_ownership,
+            num_parameters: num_parameters,
+            backend: backend.name(),
+            is_half: backend.is_half(),
+            tensors: tensors_info
+        })
+    }
+}
+
+impl fmt::Display for NetworkInfo {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(fmt, "path: {}", self.path)?;
+        match self.version {
+            Some(version) => writeln!(fmt, "format: binary (version {})", version)?,
+            None => writeln!(fmt, "format: json")?
+        }
+        writeln!(fmt, "checksum: {:016x}", self.checksum)?;
+        if let Some(ref provenance) = self.provenance {
+            writeln!(fmt, "provenance: {}", provenance)?;
+        }
+        writeln!(fmt, "architecture: {} channels, {} residual blocks, {} features{}",
+            self.architecture.num_channels,
+            self.architecture.num_residual,
+            self.architecture.num_features,
+            if self.has_ownership { ", ownership head" } else { "" }
+        )?;
+        writeln!(fmt, "parameters: {}", self.num_parameters)?;
+        writeln!(fmt, "backend: {} ({})", self.backend, if self.is_half { "f16" } else { "f32" })?;
+        write!(fmt, "tensors:")?;
+
+        for info in &self.tensors {
+            let data_type = match info.data_type {
+                DataType::Half => "f16",
+                DataType::Single => "f32",
+                DataType::Int => "i32"
+            };
+
+            write!(fmt, "\n  {:<36} {} {:>8} min {:>9.5} max {:>9.5} mean {:>9.5}",
+                info.name,
+                data_type,
+                info.num_elements,
+                info.min,
+                info.max,
+                info.mean
+            )?;
+
+            if !info.scale.is_finite() || info.scale == 0.0 {
+                write!(fmt, " invalid scale {}", info.scale)?;
+            } else if info.scale != 1.0 {
+                write!(fmt, " scale {}", info.scale)?;
+            }
+
+            if info.num_non_finite > 0 {
+                write!(fmt, " non-finite {}", info.num_non_finite)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tensor<T: Sized>(data: Vec<T>) -> Tensor {
+        let mut out = Tensor::default();
+        out.set_host(data).unwrap();
+        out
+    }
+
+    #[test]
+    fn half_statistics() {
+        let values = vec! [f16::from(-1.0), f16::from(0.5), f16::from(2.5), f16::from_bits(0x7e00)];  // NaN
+        let info = TensorInfo::new("01_upsample/conv_1:0", &tensor(values));
+
+        assert_eq!(info.data_type, DataType::Half);
+        assert_eq!(info.num_elements, 4);
+        assert_eq!(info.min, -1.0);
+        assert_eq!(info.max, 2.5);
+        assert_eq!(info.mean, 2.0 / 3.0);
+        assert_eq!(info.num_non_finite, 1);
+    }
+
+    #[test]
+    fn single_and_int_statistics() {
+        let alpha = TensorInfo::new("02_residual/alpha:0", &tensor(vec! [0.75f32]));
+        let num_channels = TensorInfo::new("num_channels:0", &tensor(vec! [128i32]));
+
+        assert_eq!((alpha.data_type, alpha.num_elements, alpha.mean), (DataType::Single, 1, 0.75));
+        assert_eq!((num_channels.data_type, num_channels.num_elements, num_channels.mean), (DataType::Int, 1, 128.0));
+    }
+
+    #[test]
+    fn scaled_statistics() {
+        let mut weights = tensor(vec! [-1.0f32, 2.0]);
+        weights.scale = 0.5;
+        let info = TensorInfo::new("02_residual/alpha:0", &weights);
+
+        assert_eq!((info.scale, info.min, info.max, info.mean), (0.5, -0.5, 1.0, 0.25));
+
+        weights.scale = ::std::f32::NAN;
+        let info = TensorInfo::new("02_residual/alpha:0", &weights);
+
+        assert_eq!(info.num_non_finite, 2);
+        assert!(info.scale.is_nan());
+    }
+}
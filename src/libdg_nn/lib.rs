@@ -20,19 +20,23 @@ extern crate libc;
 extern crate memchr;
 #[cfg(test)] extern crate test;
 
-#[macro_use] pub mod ffi;
+#[cfg(feature = "cuda")] #[macro_use] pub mod ffi;
+mod backend;
+pub mod binary;
 pub mod devices;
 mod error;
 mod graph;
+mod info;
 mod loader;
 mod network;
 mod output_map;
 mod profiler;
-mod slots;
 mod tensor;
+mod workspace;
 
 pub use self::error::Error;
-pub use self::graph::{Workspace, forward};
+pub use self::info::{NetworkInfo, TensorInfo};
 pub use self::network::{Network, WorkspaceGuard};
 pub use self::output_map::*;
 pub use self::profiler::Profiler;
+pub use self::workspace::{Workspace, forward};
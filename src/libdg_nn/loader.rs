@@ -13,13 +13,13 @@
 // limitations under the License.
 
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufReader, BufRead, ErrorKind};
+use std::fs;
+use std::io::{BufRead, Cursor, ErrorKind};
 use std::path::Path;
 use memchr::memchr;
 
 use super::tensor::Tensor;
-use super::Error;
+use super::{Error, binary};
 use dg_utils::types::f16;
 use dg_utils::b85;
 
@@ -80,7 +80,10 @@ impl<R: BufRead> Iterator for JsonEntryIter<R> {
         // skip until the quote before the name
         skip_until(&mut self.buf_read, b'"');
 
-        let name = String::from_utf8(skip_until(&mut self.buf_read, b'"')).unwrap();
+        let name = match String::from_utf8(skip_until(&mut self.buf_read, b'"')) {
+            Ok(name) => name,
+            Err(_) => { return Some((String::new(), Err(Error::MalformedWeights))) }
+        };
         if name.is_empty() {
             return None;
         }
@@ -93,17 +96,23 @@ impl<R: BufRead> Iterator for JsonEntryIter<R> {
 
         loop {
             skip_until(&mut self.buf_read, b'"');
-            let key = String::from_utf8(skip_until(&mut self.buf_read, b'"')).unwrap();
+            let key = skip_until(&mut self.buf_read, b'"');
 
             skip_until(&mut self.buf_read, b'"');
             let value = skip_until(&mut self.buf_read, b'"');
 
-            if key == "s" {
-                let array = b85::decode::<f32, f32>(&value).unwrap();
+            if key == b"s" {
+                match b85::decode::<f32, f32>(&value) {
+                    Some(ref array) if !array.is_empty() => { tensor.scale = array[0] },
+                    _ => { return Some((name, Err(Error::MalformedWeights))) }
+                }
+            } else if key == b"v" {
+                let array = match b85::decode::<f16, f16>(&value) {
+                    Some(array) => array,
+                    None => { return Some((name, Err(Error::MalformedWeights))) }
+                };
 
-                tensor.scale = array[0];
-            } else if key == "v" {
-                match tensor.set_host(b85::decode::<f16, f16>(&value).unwrap()) {
+                match tensor.set_host(array) {
                     Ok(()) => (),
                     Err(reason) => { return Some((name, Err(reason))) }
                 }
@@ -124,7 +133,8 @@ impl<R: BufRead> Iterator for JsonEntryIter<R> {
 
 /// Load all tensors in the given buffer and returns a map from
 /// their name to description. If we failed to load any tensors
-/// from the given file then `None` is returned.
+/// from the given file then `Error::MissingWeights` is returned, and if
+/// any of them are malformed then `Error::MalformedWeights`.
 /// 
 /// # Arguments
 /// 
@@ -146,26 +156,50 @@ fn load_aux<R: BufRead>(reader: R) -> Result<HashMap<String, Tensor>, Error> {
     }
 }
 
+/// Where a set of weights were loaded from.
+#[derive(Clone, Debug)]
+pub struct Source {
+    /// The path to the file that the weights were loaded from.
+    pub path: String,
+
+    /// The header of the file, if it is a binary weights file.
+    pub header: Option<binary::Header>,
+
+    /// The checksum of the file, which for binary weights files is the one
+    /// stored in its header and otherwise the FNV-1a hash of the entire
+    /// file.
+    pub checksum: u64
+}
+
 /// Load all tensors in the given file and returns a map from
-/// their name to description. If we failed to load any tensors
-/// from the given file then `None` is returned.
+/// their name to description, together with where they came from. The
+/// file can be either a binary weights file, or the JSON format dumped by
+/// the trainer. If the file does not exist then `Error::MissingWeights` is
+/// returned.
 /// 
 /// # Arguments
 /// 
 /// * `path` -
 /// 
-pub fn load(path: &Path) -> Result<HashMap<String, Tensor>, Error> {
-    if let Ok(file) = File::open(path) {
-        load_aux(BufReader::new(file))
+pub fn load(path: &Path) -> Result<(HashMap<String, Tensor>, Source), Error> {
+    let bytes = fs::read(path).map_err(|_| Error::MissingWeights)?;
+    let path = path.to_string_lossy().into_owned();
+
+    if binary::is_binary(&bytes) {
+        let (header, tensors) = binary::read(&bytes)?;
+        let checksum = header.checksum;
+
+        Ok((tensors, Source { path: path, header: Some(header), checksum: checksum }))
     } else {
-        Err(Error::MissingWeights)
+        let tensors = load_aux(Cursor::new(&bytes))?;
+
+        Ok((tensors, Source { path: path, header: None, checksum: binary::fnv1a(&bytes) }))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Cursor;
 
     #[test]
     fn empty_json() {
@@ -186,4 +220,14 @@ mod tests {
         assert_eq!(out["11v_value/linear_2/offset:0"].scale, 0.13704996);
         assert_eq!(out["11v_value/linear_2/offset:0"].size_in_bytes, 4);
     }
+
+    #[test]
+    fn malformed_json() {
+        let out = load_aux(Cursor::new("{\"11v_value/linear_2/offset:0\": {\"s\": \".....\", \"v\": \"(^d>V\"}}"));
+
+        match out {
+            Err(Error::MalformedWeights) => {},
+            other => panic!("{:?}", other.map(|out| out.len()))
+        }
+    }
 }
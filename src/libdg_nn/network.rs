@@ -18,23 +18,30 @@ use std::ops::{Deref, DerefMut};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 
-use super::devices::{get_current_device, set_current_device};
-use super::ffi::cuda;
+use dg_utils::config::{self, Config};
+use super::backend::{self, Backend};
+use super::devices::DEVICES;
+#[cfg(feature = "cuda")] use super::devices::{get_current_device, set_current_device};
+#[cfg(feature = "cuda")] use super::ffi::cuda;
+use super::info::NetworkInfo;
+use super::loader::Source;
+use super::tensor::Tensor;
+use super::workspace::Workspace;
 use super::{Error, graph, loader};
 
-type WorkspaceQueue = Mutex<Vec<graph::Workspace>>;
+type WorkspaceQueue = Mutex<Vec<Workspace>>;
 
 /// Wrapper around a `Workspace` that when dropped returns it to the
 /// pool it was acquired from.
 pub struct WorkspaceGuard<'a> {
-    workspace: Option<graph::Workspace>,
+    workspace: Option<Workspace>,
     pool: *mut WorkspaceQueue,
 
     lifetime: ::std::marker::PhantomData<&'a ()>
 }
 
 impl<'a> Deref for WorkspaceGuard<'a> {
-    type Target = graph::Workspace;
+    type Target = Workspace;
 
     fn deref(&self) -> &Self::Target { self.workspace.as_ref().unwrap() }
 }
@@ -54,10 +61,69 @@ impl<'a> Drop for WorkspaceGuard<'a> {
     }
 }
 
+/// The pre-processed weights for the backend the network is evaluated on.
+#[derive(Clone)]
+enum Builder {
+    #[cfg(feature = "cuda")]
+    Cuda(Arc<graph::Builder<backend::Cuda>>),
+    Cpu(Arc<graph::Builder<backend::Cpu>>)
+}
+
+impl Builder {
+    /// Returns a builder for the given weights on the backend selected by
+    /// the given configuration, or `Error::UnavailableBackend` if that
+    /// backend cannot be used on this machine.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights` -
+    /// * `config` - the configuration to get the backend, and the softmax
+    ///   temperature from
+    ///
+    fn new(weights: HashMap<String, Tensor>, config: &Config) -> Result<Builder, Error> {
+        let softmax_temperature = config.softmax_temperature;
+
+        match config.backend {
+            config::Backend::Cpu => Builder::new_cpu(weights, softmax_temperature),
+            config::Backend::Cuda => {
+                if !cfg!(feature = "cuda") {
+                    Err(Error::UnavailableBackend("this binary was built without support for the cuda backend"))
+                } else if DEVICES.is_empty() {
+                    Err(Error::UnavailableBackend("no CUDA device available with the required compute capacity (6.1)"))
+                } else {
+                    Builder::new_cuda(weights, softmax_temperature)
+                }
+            },
+            config::Backend::Auto => {
+                if cfg!(feature = "cuda") && !DEVICES.is_empty() {
+                    Builder::new_cuda(weights, softmax_temperature)
+                } else {
+                    Builder::new_cpu(weights, softmax_temperature)
+                }
+            }
+        }
+    }
+
+    fn new_cpu(weights: HashMap<String, Tensor>, softmax_temperature: f32) -> Result<Builder, Error> {
+        Ok(Builder::Cpu(Arc::new(graph::Builder::new(backend::Cpu::default(), weights, softmax_temperature)?)))
+    }
+
+    #[cfg(feature = "cuda")]
+    fn new_cuda(weights: HashMap<String, Tensor>, softmax_temperature: f32) -> Result<Builder, Error> {
+        Ok(Builder::Cuda(Arc::new(graph::Builder::new(backend::Cuda::default(), weights, softmax_temperature)?)))
+    }
+
+    #[cfg(not(feature = "cuda"))]
+    fn new_cuda(_weights: HashMap<String, Tensor>, _softmax_temperature: f32) -> Result<Builder, Error> {
+        unreachable!()
+    }
+}
+
 /// Pool of workspaces that can be used for network evaluations.
 #[derive(Clone)]
 pub struct Network {
-    builder: Arc<graph::Builder>,
+    builder: Builder,
+    source: Arc<Source>,
     workspaces: Arc<Mutex<HashMap<(usize, i32), Box<WorkspaceQueue>>>>
 }
 
@@ -65,7 +131,15 @@ unsafe impl Send for Network { }  // this is safe because the Rc<...> is guarded
 unsafe impl Sync for Network { }  // this is safe because the Rc<...> is guarded by a Mutex and/or Arc
 
 impl Network {
-    pub fn new() -> Option<Network> {
+    /// Returns the network given by the `weights` of the given configuration,
+    /// or if no such option was given the first network that is found in one
+    /// of the default locations.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - the configuration to load the network with
+    ///
+    pub fn new(config: &Config) -> Result<Network, Error> {
         lazy_static! {
             static ref PATHS: Vec<String> = vec! [
                 // check for a file named the same as the current executable, but
@@ -84,21 +158,92 @@ impl Network {
             ];
         }
 
-        PATHS.iter()
-            .filter_map(|path| {
-                match loader::load(Path::new(path)) {
-                    Ok(weights) => Some(weights),
-                    Err(Error::MissingWeights) => None,
-                    Err(reason) => {
-                        panic!("Failed to load network weights -- {:?}", reason)
-                    }
-                }
-            })
-            .next()
-            .map(|weights| Network {
-                builder: Arc::new(graph::Builder::new(weights)),
-                workspaces: Arc::new(Mutex::new(HashMap::new()))
-            })
+        if let Some(ref path) = config.weights {
+            return Network::from_path(Path::new(path), config);
+        }
+
+        for path in PATHS.iter() {
+            match Network::from_path(Path::new(path), config) {
+                Err(Error::MissingWeights) => continue,
+                other => return other
+            }
+        }
+
+        Err(Error::MissingWeights)
+    }
+
+    /// Returns a network for the weights stored in the given file, or an
+    /// error if the file does not exist or is malformed. Any number of
+    /// networks may be loaded at the same time.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path to the file that contains the weights
+    /// * `config` - the configuration to load the network with
+    ///
+    pub fn from_path(path: &Path, config: &Config) -> Result<Network, Error> {
+        loader::load(path).and_then(|(weights, source)| Network::with_weights(weights, source, config))
+    }
+
+    /// Returns a network for the given weights, that is evaluated on the
+    /// backend given by the `backend` option. By default this is the GPU
+    /// if there is a supported CUDA device available, and otherwise the
+    /// CPU.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights` -
+    /// * `source` - where the weights were loaded from
+    /// * `config` - the configuration to load the network with
+    ///
+    fn with_weights(weights: HashMap<String, Tensor>, source: Source, config: &Config) -> Result<Network, Error> {
+        Ok(Network {
+            builder: Builder::new(weights, config)?,
+            source: Arc::new(source),
+            workspaces: Arc::new(Mutex::new(HashMap::new()))
+        })
+    }
+
+    /// Returns true if this network is evaluated on the CPU.
+    pub fn is_cpu(&self) -> bool {
+        match self.builder {
+            Builder::Cpu(_) => true,
+            #[cfg(feature = "cuda")]
+            Builder::Cuda(_) => false
+        }
+    }
+
+    /// Returns the name of the backend this network is evaluated on.
+    pub fn backend_name(&self) -> &'static str {
+        match self.builder {
+            Builder::Cpu(ref builder) => builder.backend().name(),
+            #[cfg(feature = "cuda")]
+            Builder::Cuda(ref builder) => builder.backend().name()
+        }
+    }
+
+    /// Returns a description of this network, and statistics about each of
+    /// its weights.
+    pub fn info(&self) -> Result<NetworkInfo, Error> {
+        match self.builder {
+            Builder::Cpu(ref builder) => {
+                NetworkInfo::new(&self.source, builder.tensors(), builder.backend(), builder.has_ownership())
+            },
+            #[cfg(feature = "cuda")]
+            Builder::Cuda(ref builder) => {
+                NetworkInfo::new(&self.source, builder.tensors(), builder.backend(), builder.has_ownership())
+            }
+        }
+    }
+
+    /// Returns true if this network can predict the ownership of each
+    /// vertex, using `Output::Ownership`.
+    pub fn has_ownership(&self) -> bool {
+        match self.builder {
+            Builder::Cpu(ref builder) => builder.has_ownership(),
+            #[cfg(feature = "cuda")]
+            Builder::Cuda(ref builder) => builder.has_ownership()
+        }
     }
 
     /// Returns a `Workspace` with the given batch size.
@@ -108,7 +253,11 @@ impl Network {
     /// * `batch_size` -
     /// 
     pub fn get_workspace(&self, batch_size: usize) -> Result<WorkspaceGuard, Error> {
-        let device_id = get_current_device()?;
+        let device_id = match self.builder {
+            Builder::Cpu(_) => -1,
+            #[cfg(feature = "cuda")]
+            Builder::Cuda(_) => get_current_device()?
+        };
         let key = (batch_size, device_id);
         let mut workspaces = self.workspaces.lock().unwrap();
         let candidates = workspaces.entry(key).or_insert_with(|| Box::new(Mutex::new(vec! [])));
@@ -120,7 +269,11 @@ impl Network {
                 lifetime: ::std::marker::PhantomData::default()
             },
             None => WorkspaceGuard {
-                workspace: Some(self.builder.get_workspace(batch_size)?),
+                workspace: Some(match self.builder {
+                    #[cfg(feature = "cuda")]
+                    Builder::Cuda(ref builder) => Workspace::Cuda(builder.get_workspace(batch_size)?),
+                    Builder::Cpu(ref builder) => Workspace::Cpu(builder.get_workspace(batch_size)?)
+                }),
                 pool: candidates_ptr,
                 lifetime: ::std::marker::PhantomData::default()
             }
@@ -133,19 +286,25 @@ impl Network {
     pub fn synchronize(&self) {
         let mut workspaces = self.workspaces.lock().unwrap();
 
-        unsafe {
-            cuda::cudaDeviceSynchronize();  // this should be allowed to fail
+        match self.builder {
+            Builder::Cpu(_) => {
+                workspaces.clear();
+            },
+            #[cfg(feature = "cuda")]
+            Builder::Cuda(_) => unsafe {
+                cuda::cudaDeviceSynchronize();  // this should be allowed to fail
 
-            let original_device_id = get_current_device().expect("Failed to get the current device");
+                let original_device_id = get_current_device().expect("Failed to get the current device");
 
-            for ((_batch_size, device_id), value) in workspaces.drain() {
-                set_current_device(device_id).expect("Failed to set the device for the current thread");
-                cuda::cudaDeviceSynchronize();  // this should be allowed to fail
+                for ((_batch_size, device_id), value) in workspaces.drain() {
+                    set_current_device(device_id).expect("Failed to set the device for the current thread");
+                    cuda::cudaDeviceSynchronize();  // this should be allowed to fail
 
-                drop(value);
-            }
+                    drop(value);
+                }
 
-            set_current_device(original_device_id).expect("Failed to set the device for the current thread");
+                set_current_device(original_device_id).expect("Failed to set the device for the current thread");
+            }
         }
     }
 }
@@ -64,10 +64,12 @@ pub enum Output {
     Residual_37 = 43,
     Residual_38 = 44,
     Residual_39 = 45,
+
+    Ownership = 46,  // the final ownership output
 }
 
 /// The total number of elements in the `Output` enum.
-const OUTPUT_SIZE: usize = 46;
+const OUTPUT_SIZE: usize = 47;
 
 pub struct OutputMap<T> {
     array: [Option<T>; OUTPUT_SIZE]
@@ -86,7 +88,7 @@ impl<T> Default for OutputMap<T> {
                 None, None, None, None, None,
                 None, None, None, None, None,
                 None, None, None, None, None,
-                None,
+                None, None,
             ]
         }
     }
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::ffi::cuda;
+#[cfg(feature = "cuda")] use super::ffi::cuda;
 
 pub struct Profiler;
 
@@ -20,12 +20,14 @@ impl Profiler {
     pub fn with<T, F>(f: F) -> T
         where F: FnOnce() -> T
     {
+        #[cfg(feature = "cuda")]
         unsafe {
             cuda::cudaProfilerStart();
         }
 
         let out = f();
 
+        #[cfg(feature = "cuda")]
         unsafe {
             cuda::cudaProfilerStop();
         }
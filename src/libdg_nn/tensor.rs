@@ -12,21 +12,63 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::atomic::AtomicPtr;
+#[cfg(feature = "cuda")] use std::sync::atomic::Ordering;
 use std::mem::size_of;
 use std::ptr;
-use libc::c_void;
+use std::slice;
+use libc::{self, c_void};
 
+use dg_utils::types::f16;
 use super::devices::MAX_DEVICES;
-use super::ffi::cuda;
+#[cfg(feature = "cuda")] use super::ffi::cuda;
 use super::Error;
 
+/// Allocate `size_in_bytes` bytes of page-locked host memory and returns
+/// true, or returns false if no such memory could be allocated.
+///
+/// # Arguments
+///
+/// * `ptr` - output pointer for the allocated memory
+/// * `size_in_bytes` -
+///
+#[cfg(feature = "cuda")]
+unsafe fn malloc_host(ptr: &mut *mut c_void, size_in_bytes: usize) -> bool {
+    cuda::cudaMallocHost(ptr, size_in_bytes).is_ok()
+}
+
+#[cfg(not(feature = "cuda"))]
+unsafe fn malloc_host(_ptr: &mut *mut c_void, _size_in_bytes: usize) -> bool {
+    false
+}
+
+/// Free the given host memory, that was allocated using `malloc_host` if
+/// `is_pinned` and otherwise by the system allocator.
+///
+/// # Arguments
+///
+/// * `ptr` -
+/// * `is_pinned` -
+///
+unsafe fn free_host(ptr: *mut c_void, is_pinned: bool) {
+    if is_pinned {
+        #[cfg(feature = "cuda")]
+        cuda::cudaFreeHost(ptr);
+    } else {
+        libc::free(ptr);
+    }
+}
+
 /// A data structure with interior mutability that store the host,
 /// device, and meta information about a tensor.
 pub struct Tensor {
     /// The unscaled tensor in host-memory as raw (untyped) bytes.
     pub host: *mut c_void,
 
+    /// Whether `host` was allocated as page-locked memory using CUDA, or
+    /// using the system allocator (if no CUDA device is available).
+    pub is_pinned: bool,
+
     /// The scaled tensor in device memory as the type given in
     /// `dtype`, or null if not applicable.
     pub ptr: [AtomicPtr<c_void>; MAX_DEVICES],
@@ -45,9 +87,10 @@ impl Drop for Tensor {
     fn drop(&mut self) {
         unsafe {
             if !self.host.is_null() {
-                cuda::cudaFreeHost(self.host);
+                free_host(self.host, self.is_pinned);
             }
 
+            #[cfg(feature = "cuda")]
             for i in 0..MAX_DEVICES {
                 let ptr = self.ptr[i].load(Ordering::Relaxed);
 
@@ -63,6 +106,7 @@ impl Default for Tensor {
     fn default() -> Tensor {
         Tensor {
             host: ptr::null_mut(),
+            is_pinned: false,
             ptr: [
                 AtomicPtr::new(ptr::null_mut()), AtomicPtr::new(ptr::null_mut()),
                 AtomicPtr::new(ptr::null_mut()), AtomicPtr::new(ptr::null_mut()),
@@ -77,6 +121,7 @@ impl Default for Tensor {
 }
 
 impl Tensor {
+    #[cfg(feature = "cuda")]
     pub fn get(&self, device_id: i32) -> *mut c_void {
         self.ptr[device_id as usize].load(Ordering::Relaxed)
     }
@@ -84,13 +129,20 @@ impl Tensor {
     pub fn set_host<T: Sized>(&mut self, data: Vec<T>) -> Result<(), Error> {
         unsafe {
             if !self.host.is_null() {
-                check!(cuda::cudaFreeHost(self.host))?;
+                free_host(self.host, self.is_pinned);
             }
 
             self.size_in_bytes = size_of::<T>() * data.len();
             self.size_in_elements = data.len();
 
-            check!(cuda::cudaMallocHost(&mut self.host, self.size_in_bytes))?;
+            // fallback to the system allocator if we cannot allocate
+            // page-locked memory, which happens if there are no CUDA
+            // devices available.
+            self.is_pinned = malloc_host(&mut self.host, self.size_in_bytes);
+
+            if !self.is_pinned {
+                self.host = libc::malloc(::std::cmp::max(1, self.size_in_bytes));
+            }
 
             ptr::copy_nonoverlapping(
                 data.as_ptr() as *const c_void,
@@ -110,6 +162,24 @@ impl Tensor {
         *(self.host as *const i32)
     }
 
+    /// Returns a copy of the host memory of this tensor as single precision
+    /// floats, assuming the elements are stored as either `f16` or `f32`
+    /// depending on their size.
+    pub fn to_f32_vec(&self) -> Vec<f32> {
+        if self.host.is_null() || self.size_in_elements == 0 {
+            vec! []
+        } else if self.size_in_bytes == size_of::<f16>() * self.size_in_elements {
+            let host = unsafe { slice::from_raw_parts(self.host as *const f16, self.size_in_elements) };
+
+            host.iter().map(|&x| f32::from(x)).collect()
+        } else {
+            let host = unsafe { slice::from_raw_parts(self.host as *const f32, self.size_in_elements) };
+
+            host.to_vec()
+        }
+    }
+
+    #[cfg(feature = "cuda")]
     pub unsafe fn copy_to_device(&self, device_id: i32, stream: cuda::Stream) -> Result<bool, Error> {
         let device_id = device_id as usize;
 
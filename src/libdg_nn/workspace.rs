@@ -0,0 +1,48 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::backend;
+use super::graph::{self, InferenceType};
+use super::output_map::*;
+use super::Error;
+
+/// A workspace that contains everything needed to perform a forward pass
+/// through the network, on one of the supported backends.
+pub enum Workspace {
+    #[cfg(feature = "cuda")]
+    Cuda(graph::Workspace<backend::Cuda>),
+    Cpu(graph::Workspace<backend::Cpu>)
+}
+
+/// Returns the value and policy tensors obtained from a forward pass
+/// through the neural network.
+///
+/// # Arguments
+///
+/// * `workspace` - the workspace for the current thread
+/// * `features` - the input features
+/// * `outputs` - the outputs to copy to host memory
+///
+pub fn forward<T: InferenceType>(
+    workspace: &mut Workspace,
+    features: &[T],
+    outputs: OutputSet
+) -> Result<OutputMap<Vec<f32>>, Error>
+{
+    match *workspace {
+        #[cfg(feature = "cuda")]
+        Workspace::Cuda(ref mut workspace) => graph::forward(workspace, features, outputs),
+        Workspace::Cpu(ref mut workspace) => graph::forward(workspace, features, outputs)
+    }
+}
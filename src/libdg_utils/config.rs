@@ -13,19 +13,25 @@
 // limitations under the License.
 
 use std::env;
+use std::error;
+use std::fmt;
+use std::fs;
 use std::str::FromStr;
 
-use regex::Regex;
+use toml;
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Procedure {
     SelfPlay(usize),
     PolicyPlay(usize, bool),
+    Match(usize),
+    ConvertWeights,
+    NetworkInfo,
     Gtp,
     Help
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum RolloutLimit {
     Default(usize),
     UserDefined(usize),
@@ -64,6 +70,7 @@ impl FromStr for RolloutLimit {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum SamplingStrategy {
     Percent(f32),
     Fixed(usize)
@@ -89,251 +96,1068 @@ impl FromStr for SamplingStrategy {
     }
 }
 
-lazy_static! {
-    /// The main producedure to run during this execution.
-    pub static ref PROCEDURE: Procedure = if has_opt("--help") {
-        Procedure::Help
-    } else if has_opt("--policy-play") {
-        Procedure::PolicyPlay(get_opt("--policy-play").unwrap_or(::std::usize::MAX), has_opt("--ex-it"))
-    } else if has_opt("--self-play") {
-        Procedure::SelfPlay(get_opt("--self-play").unwrap_or(1))
-    } else {
-        Procedure::Gtp
-    };
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Backend {
+    Auto,
+    Cuda,
+    Cpu
+}
+
+impl FromStr for Backend {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.trim().to_lowercase().as_ref() {
+            "auto" => Ok(Backend::Auto),
+            "cuda" => Ok(Backend::Cuda),
+            "cpu" => Ok(Backend::Cpu),
+            _ => Err(())
+        }
+    }
+}
+
+/// The named rule sets that the `rules` option accepts.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RuleSet {
+    Chinese,
+    Japanese,
+    Aga,
+    NewZealand,
+    TrompTaylor
+}
+
+impl FromStr for RuleSet {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.trim().to_lowercase().as_ref() {
+            "chinese" | "cn" => Ok(RuleSet::Chinese),
+            "japanese" | "jp" | "korean" => Ok(RuleSet::Japanese),
+            "aga" | "bga" => Ok(RuleSet::Aga),
+            "new_zealand" | "new-zealand" | "nz" => Ok(RuleSet::NewZealand),
+            "tromp-taylor" | "tromp_taylor" | "tt" => Ok(RuleSet::TrompTaylor),
+            _ => Err(())
+        }
+    }
+}
+
+impl fmt::Display for RuleSet {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RuleSet::Chinese => write!(fmt, "chinese"),
+            RuleSet::Japanese => write!(fmt, "japanese"),
+            RuleSet::Aga => write!(fmt, "aga"),
+            RuleSet::NewZealand => write!(fmt, "new_zealand"),
+            RuleSet::TrompTaylor => write!(fmt, "tromp-taylor")
+        }
+    }
+}
+
+/// The super-ko rules that the `superko` option accepts.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SuperkoRule {
+    Positional,
+    Situational
+}
+
+impl FromStr for SuperkoRule {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.trim().to_lowercase().as_ref() {
+            "positional" | "psk" => Ok(SuperkoRule::Positional),
+            "situational" | "ssk" => Ok(SuperkoRule::Situational),
+            _ => Err(())
+        }
+    }
+}
+
+/// An error in the configuration given on the command-line, in the
+/// environment variables, or in the configuration file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigError {
+    /// An option (or configuration file key) that does not exist.
+    UnknownOption(String),
+
+    /// An option that requires a value was given without one.
+    MissingValue(String),
+
+    /// An option was given a value that is not valid for it.
+    InvalidValue(String, String),
+
+    /// The configuration file could not be read, or is not valid TOML.
+    InvalidFile(String, String),
+
+    /// The options are valid on their own, but not together.
+    Conflict(String)
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::UnknownOption(ref name) => write!(fmt, "unknown option `{}`", name),
+            ConfigError::MissingValue(ref name) => write!(fmt, "missing value for `{}`", name),
+            ConfigError::InvalidValue(ref name, ref value) => write!(fmt, "invalid value `{}` for `{}`", value, name),
+            ConfigError::InvalidFile(ref path, ref reason) => write!(fmt, "could not load configuration file `{}` -- {}", path, reason),
+            ConfigError::Conflict(ref reason) => write!(fmt, "{}", reason)
+        }
+    }
+}
+
+impl error::Error for ConfigError {
+    fn description(&self) -> &str {
+        "invalid configuration"
+    }
+}
+
+/// Command-line options that does not take a value.
+const FLAGS: [&str; 8] = ["--help", "--gtp", "--ex-it", "--network-info", "--tt", "--no-ponder", "--no-resign", "--verbose"];
+
+/// Command-line options that takes an optional value.
+const OPTIONAL_VALUE: [&str; 3] = ["--self-play", "--policy-play", "--match"];
+
+/// Mapping from the environment variables that are recognized to the
+/// name of the option they set.
+const ENV_VARS: [(&str, &str); 8] = [
+    ("POLICY_ROLLOUT", "num_policy_rollout"),
+    ("DIRICHLET_NOISE", "dirichlet_noise"),
+    ("TEMPERATURE", "temperature"),
+    ("SOFTMAX_TEMPERATURE", "softmax_temperature"),
+    ("FPU_REDUCE", "fpu_reduce"),
+    ("VLOSS_CNT", "vloss_cnt"),
+    ("UCT_EXP", "uct_exp"),
+    ("CRITICAL_VALUE", "critical_value")
+];
+
+/// The configuration of the engine. This is usually loaded once from the
+/// command-line arguments, environment variables, and configuration file of
+/// the process (see `Config::load`), with each source overriding the previous
+/// one:
+///
+/// 1. The defaults for the selected procedure.
+/// 2. The TOML file given by `--config <path>`, if any.
+/// 3. The environment variables in `ENV_VARS`.
+/// 4. The command-line arguments.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    /// The main procedure to run.
+    pub procedure: Procedure,
+
+    /// The path to the weights of the network, if it should not be looked
+    /// for in the default locations (next to the executable, `dream_go.json`,
+    /// `models/dream_go.json`, and `/usr/share/dream_go/dream_go.json`).
+    pub weights: Option<String>,
+
+    /// The path to the weights of the network to play against during a
+    /// `Match`.
+    pub opponent: Option<String>,
+
+    /// The path to write the binary weights to during `ConvertWeights`.
+    pub convert_weights: Option<String>,
+
+    /// The description of where the weights came from, to store in the
+    /// binary weights written by `ConvertWeights`.
+    pub provenance: Option<String>,
+
+    /// The backend to evaluate the neural network on. If `Auto` then a CUDA
+    /// device is used if one is available, and otherwise the CPU.
+    pub backend: Backend,
 
     /// Whether to think in the background during idle time.
-    pub static ref NO_PONDER: bool = has_opt("--no-ponder");
+    pub no_ponder: bool,
 
-    /// Whether to play using Tromp-Taylor rules.
-    pub static ref TROMP_TAYLOR: bool = has_opt("--tt");
+    /// The rules to play with.
+    pub rules: RuleSet,
+
+    /// The super-ko rule to play with, if it should be different from the
+    /// one given by the `rules`.
+    pub superko: Option<SuperkoRule>,
 
     /// Whether to allow the GTP interface to resign.
-    pub static ref NO_RESIGN: bool = has_opt("--no-resign");
+    pub no_resign: bool,
 
     /// The number of milliseconds to never let the total game game fall below.
-    ///
-    /// Safe time is intended to compensate for lag or other uncontrollable factors
-    /// that might arise during real games, especially when playing over network.
-    pub static ref SAFE_TIME_MS: usize = get_opt("--safe-time")
-        .unwrap_or(100);
+    pub safe_time_ms: usize,
 
     /// The target number of rollouts for each search tree.
-    pub static ref NUM_ROLLOUT: RolloutLimit = get_opt("--num-rollout").unwrap_or(RolloutLimit::Default(1600));
+    pub num_rollout: RolloutLimit,
+
+    /// The divergence between two snapshots of the root visit distribution
+    /// to stop searching at, or zero if the search should not stop early.
+    pub kl_threshold: f32,
+
+    /// The probability that a self-play move gets a full search, instead of
+    /// a cheap one.
+    pub full_search_prob: f32,
+
+    /// The number of rollouts to perform for each cheap search.
+    pub num_cheap_rollout: usize,
 
-    /// The maximum batch size to forward to the neural network. A larger batch
-    /// size typically result in a faster program but requires more GPU memory.
-    pub static ref BATCH_SIZE: usize = get_opt("--batch-size").unwrap_or(16);
+    /// The maximum batch size to forward to the neural network.
+    pub batch_size: usize,
 
-    /// The maximum number of games to play in parallel during `SelfPlay`,
-    /// `PolicyPlay`, and `Extract` (with expert iteration).
-    pub static ref NUM_GAMES: usize = get_opt("--num-games")
-        .unwrap_or_else(|| if *PROCEDURE == Procedure::Gtp { 1 } else { 16 });
+    /// The maximum number of games to play in parallel.
+    pub num_games: usize,
 
     /// The total number of parallel probes to perform for every monte carlo
     /// search tree.
-    /// 
-    /// When trying to improve the GPU utilization you should prefer to
-    /// increase the `NUM_GAMES` variable instead as that scaled much better.
-    pub static ref NUM_THREADS: usize = {
-        let num_threads = get_opt("--num-threads").unwrap_or(64);
-
-        assert!(
-            num_threads >= *NUM_GAMES,
-            "The number of threads must be at least the same as the number of games"
-        );
-
-        num_threads
-    };
+    pub num_threads: usize,
 
     /// The number of samples to extract from each game record.
-    pub static ref NUM_SAMPLES: SamplingStrategy = get_opt("--num-samples")
-        .unwrap_or(SamplingStrategy::Percent(0.01));
+    pub num_samples: SamplingStrategy,
+
+    /// The maximum number of MiB each search tree may use, or zero if they
+    /// are unlimited.
+    pub max_tree_memory: usize,
+
+    /// The seed of the random number generators, if the search should be
+    /// reproducible. If this is given then every search is single-threaded,
+    /// so that the same position (and network) always produces the same
+    /// search tree.
+    pub seed: Option<u64>,
 
     /// Whether to output extra information for all actions.
-    pub static ref VERBOSE: bool = has_opt("--verbose");
+    pub verbose: bool,
 
     /// The number of rollout to perform for each board position when playing
     /// _according to the policy_.
-    pub static ref NUM_POLICY_ROLLOUT: usize = get_env("POLICY_ROLLOUT").unwrap_or(1);
-
-    /// The amount of dirtchlet noise to add to the root node of each search
-    /// tree. A larger value will result in a more random search, which is
-    /// typically desirable during training but not during tournament play.
-    pub static ref DIRICHLET_NOISE: f32 = get_env("DIRICHLET_NOISE")
-        .unwrap_or_else(|| if *PROCEDURE == Procedure::Gtp { 0.05 } else { 0.25 });
-
-    /// The temperature of the move selection during the eight first moves. A
-    /// larger values make the engine more likely to pick a sub-optimal
-    /// move (according to the search).
-    pub static ref TEMPERATURE: f32 = get_env("TEMPERATURE")
-        .unwrap_or_else(|| if *PROCEDURE == Procedure::Gtp { 0.3 } else { 0.8 });
-
-    /// The softmax temperature to use at the end of the _policy head_. This
-    /// temperature is applied for the entire game.
-    pub static ref SOFTMAX_TEMPERATURE: f32 = get_env("SOFTMAX_TEMPERATURE")
-        .unwrap_or(1.0);
-
-    /// The _First Play Urgency_ reduction. Setting this is `1.0`, or `0.0`
-    /// effectively disables FPU.
-    pub static ref FPU_REDUCE: Vec<(i32, f32)> = get_intp_list("FPU_REDUCE")
-        .unwrap_or_else(|| vec! [(0, 0.35), (800, 0.22), (1600, 0.10)]);
-
-    /// The number of virtual losses to add during async probes into the monte
-    /// carlo search tree. A higher value avoids multiple probes exploring the
-    /// same search tree.
-    pub static ref VLOSS_CNT: i32 = get_env("VLOSS_CNT").unwrap_or(2);
-
-    /// The UCT exploration rate.
-    pub static ref UCT_EXP: Vec<(i32, f32)> = get_intp_list("UCT_EXP")
-        .unwrap_or_else(|| vec! [(0, 0.88), (3200, 1.44)]);
-
-    /// The LCB critical value.
-    pub static ref CRITICAL_VALUE: Vec<(i32, f32)> = get_intp_list("CRITICAL_VALUE")
-        .unwrap_or_else(|| vec! [(0, 1.645), (3200, 1.96), (16000, 2.576)]);
-}
+    pub num_policy_rollout: usize,
 
-/// Returns a description of the configurations for this engine.
-pub fn get_description() -> String {
-    vec! [
-        format!("TROMP_TAYLOR {}", *TROMP_TAYLOR),
-        format!("NO_RESIGN {}", *NO_RESIGN),
-        format!("NUM_ROLLOUT {:?}", *NUM_ROLLOUT),
-        format!("DIRICHLET_NOISE {}", *DIRICHLET_NOISE),
-        format!("TEMPERATURE {}", *TEMPERATURE),
-        format!("SOFTMAX_TEMPERATURE {}", *SOFTMAX_TEMPERATURE),
-        format!("VLOSS_CNT {}", *VLOSS_CNT),
-        format!("FPU_REDUCE {:?}", *FPU_REDUCE),
-        format!("UCT_EXP {:?}", *UCT_EXP),
-        format!("CRITICAL_VALUE {:?}", *CRITICAL_VALUE)
-    ].join("\n")
-}
+    /// The softmax temperature to use at the end of the _policy head_.
+    pub softmax_temperature: f32,
 
-/// Returns true if any command-line argument with the given name is present.
-/// 
-/// # Arguments
-/// 
-/// * `name` - the command-line arguments to check for
-/// 
-pub fn has_opt(name: &str) -> bool {
-    env::args().skip(1).any(|arg| arg == name)
+    /// The initial search parameters.
+    pub search: SearchParams
 }
 
-pub fn get_opt<T: FromStr>(name: &str) -> Option<T> {
-    env::args().skip(1).zip(env::args().skip(2))
-        .filter_map(|(arg, value)| {
-            if arg == name {
-                T::from_str(&value).ok()
-            } else {
-                None
-            }
-        })
-        .next()
-}
+impl Config {
+    /// Returns the default configuration for the given procedure.
+    ///
+    /// # Arguments
+    ///
+    /// * `procedure` - the main procedure to run
+    ///
+    pub fn new(procedure: Procedure) -> Config {
+        let is_gtp = procedure == Procedure::Gtp;
+        let is_match = if let Procedure::Match(_) = procedure { true } else { false };
+        let mut search = SearchParams::default();
 
-pub fn get_env<T: FromStr>(name: &str) -> Option<T> {
-    match env::var(name) {
-        Ok(value) => T::from_str(&value).ok(),
-        _ => None
-    }
-}
+        // a match should be played like a real game, so only use the more
+        // exploratory search parameters when generating training data
+        if !is_gtp && !is_match {
+            search.dirichlet_noise = 0.25;
+            search.temperature = 0.8;
+        }
 
-pub fn get_intp_list(name: &str) -> Option<Vec<(i32, f32)>> {
-    lazy_static! {
-        static ref POINT: Regex = Regex::new(r"([0-9]+),(0-9\.):?").unwrap();
+        Config {
+            procedure: procedure,
+            weights: None,
+            opponent: None,
+            convert_weights: None,
+            provenance: None,
+            backend: Backend::Auto,
+            no_ponder: false,
+            rules: RuleSet::Chinese,
+            superko: None,
+            no_resign: false,
+            safe_time_ms: 100,
+            num_rollout: RolloutLimit::Default(1600),
+            kl_threshold: 0.0,
+            full_search_prob: 1.0,
+            num_cheap_rollout: 100,
+            batch_size: 16,
+            num_games: if is_gtp { 1 } else { 16 },
+            num_threads: 64,
+            num_samples: SamplingStrategy::Percent(0.01),
+            max_tree_memory: 0,
+            seed: None,
+            verbose: false,
+            num_policy_rollout: 1,
+            softmax_temperature: 1.0,
+            search: search
+        }
     }
 
-    get_env::<String>(name).and_then(|s| {
-        let mut out: Vec<(i32, f32)> = POINT.captures_iter(&s).map(|point| {
-            let x = point[1].parse::<i32>();
-            let y = point[2].parse::<f32>();
+    /// Returns the configuration of this process.
+    pub fn load() -> Result<Config, ConfigError> {
+        let args = env::args().skip(1).collect::<Vec<_>>();
+        let vars = env::vars().collect::<Vec<_>>();
 
-            (x.unwrap(), y.unwrap())
-        }).collect();
+        Config::parse(&args, &vars)
+    }
 
-        out.sort_by_key(|p| { p.0 });
+    /// Returns the configuration given by the given command-line arguments
+    /// and environment variables, and by the configuration file given in the
+    /// arguments (if any).
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - the command-line arguments, excluding the program name
+    /// * `vars` - the environment variables
+    ///
+    pub fn parse(args: &[String], vars: &[(String, String)]) -> Result<Config, ConfigError> {
+        let options = parse_args(args)?;
+        let has = |name: &str| options.iter().any(|&(ref other, _)| other == name);
+        let value_of = |name: &str| options.iter()
+            .filter(|&&(ref other, _)| other == name)
+            .filter_map(|&(_, ref value)| value.clone())
+            .last();
+        let parse_count = |name: &str, default_value: usize| {
+            match value_of(name) {
+                Some(value) => value.parse::<usize>().map_err(|_| ConfigError::InvalidValue(name.to_string(), value)),
+                None => Ok(default_value)
+            }
+        };
 
-        if out.is_empty() {
-            s.parse::<f32>().ok().map(|v| vec! [(0, v)])
+        let procedure = if has("--help") {
+            Procedure::Help
+        } else if has("--policy-play") {
+            Procedure::PolicyPlay(parse_count("--policy-play", ::std::usize::MAX)?, has("--ex-it"))
+        } else if has("--self-play") {
+            Procedure::SelfPlay(parse_count("--self-play", 1)?)
+        } else if has("--match") {
+            Procedure::Match(parse_count("--match", 100)?)
+        } else if has("--convert-weights") {
+            Procedure::ConvertWeights
+        } else if has("--network-info") {
+            Procedure::NetworkInfo
         } else {
-            Some(out)
+            Procedure::Gtp
+        };
+        let mut config = Config::new(procedure);
+
+        if let Some(path) = value_of("--config") {
+            let content = fs::read_to_string(&path)
+                .map_err(|err| ConfigError::InvalidFile(path.clone(), err.to_string()))?;
+
+            config.merge_toml(&content).map_err(|err| {
+                match err {
+                    ConfigError::InvalidFile(_, reason) => ConfigError::InvalidFile(path.clone(), reason),
+                    other => other
+                }
+            })?;
+        }
+
+        for &(env_name, name) in ENV_VARS.iter() {
+            if let Some(&(_, ref value)) = vars.iter().find(|&&(ref other, _)| other == env_name) {
+                config.set(name, value).map_err(|_| ConfigError::InvalidValue(env_name.to_string(), value.clone()))?;
+            }
         }
-    })
-}
 
-/// Returns all unnamed arguments given to this program.
-pub fn get_args() -> Vec<String> {
-    let mut rest = vec! [];
+        for &(ref arg, ref value) in &options {
+            let result = match arg.as_ref() {
+                "--help" | "--gtp" | "--ex-it" | "--self-play" | "--policy-play" | "--match" | "--network-info" | "--config" => Ok(()),
+                "--tt" => config.set("rules", "tromp-taylor"),
+                "--no-ponder" | "--no-resign" | "--verbose" => config.set(&arg[2..].replace('-', "_"), "true"),
+                "--safe-time" => config.set("safe_time_ms", value.as_ref().unwrap()),
+                _ => config.set(&arg[2..].replace('-', "_"), value.as_ref().unwrap())
+            };
 
-    for (i, arg) in env::args().enumerate().skip(1) {
-        if !arg.starts_with("--") && usize::from_str(&arg).is_err() {
-            rest.push(arg);
-        } else if arg == "--" {
-            for arg in env::args().skip(i + 1) {
-                rest.push(arg);
+            match result {
+                Ok(()) => {},
+                Err(SetError::UnknownOption) => return Err(ConfigError::UnknownOption(arg.clone())),
+                Err(SetError::InvalidValue) => return Err(ConfigError::InvalidValue(arg.clone(), value.clone().unwrap_or_default()))
             }
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Overrides the options in this configuration with the ones in the
+    /// given TOML document. Any search parameters has to be inside of a
+    /// `[search]` table. If the document is not valid then this
+    /// configuration is left unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - the TOML document
+    ///
+    pub fn merge_toml(&mut self, content: &str) -> Result<(), ConfigError> {
+        let mut other = self.clone();
+        let root = content.parse::<toml::Value>()
+            .map_err(|err| ConfigError::InvalidFile(String::new(), err.to_string()))?;
+        let root = match root {
+            toml::Value::Table(table) => table,
+            _ => return Err(ConfigError::InvalidFile(String::new(), "expected a table".to_string()))
+        };
+
+        for (key, value) in root.iter() {
+            if key == "search" {
+                let search = value.as_table()
+                    .ok_or_else(|| ConfigError::InvalidValue(key.clone(), value.to_string()))?;
 
-            break
+                for (name, value) in search.iter() {
+                    let full_name = format!("search.{}", name);
+
+                    if !SearchParams::NAMES.contains(&name.as_str()) {
+                        return Err(ConfigError::UnknownOption(full_name));
+                    }
+
+                    other.set_toml(name, &full_name, value)?;
+                }
+            } else if SearchParams::NAMES.contains(&key.as_str()) {
+                return Err(ConfigError::UnknownOption(key.clone()));
+            } else {
+                other.set_toml(key, key, value)?;
+            }
         }
+
+        // only apply the document if all of it is valid, so that an error
+        // does not leave this configuration half-way updated
+        other.validate()?;
+        *self = other;
+        Ok(())
     }
 
-    rest
-}
+    /// Sets the option with the given name to the given TOML value.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the option
+    /// * `full_name` - the name of the option to use in error messages
+    /// * `value` - the new value of the option
+    ///
+    fn set_toml(&mut self, name: &str, full_name: &str, value: &toml::Value) -> Result<(), ConfigError> {
+        let invalid = || ConfigError::InvalidValue(full_name.to_string(), value.to_string());
+        let value_str = match *value {
+            toml::Value::String(ref s) => s.clone(),
+            toml::Value::Integer(i) => i.to_string(),
+            toml::Value::Float(f) => f.to_string(),
+            toml::Value::Boolean(b) => b.to_string(),
+            toml::Value::Array(ref points) => {
+                // an array of `[visits, value]` pairs, for the interpolated
+                // search parameters
+                let points = points.iter()
+                    .map(|point| {
+                        let point = point.as_array().filter(|p| p.len() == 2)?;
+                        let x = point[0].as_integer()?;
+                        let y = point[1].as_float().or_else(|| point[1].as_integer().map(|y| y as f64))?;
 
-fn get_intp_value(points: &[(i32, f32)], x: i32) -> f32 {
-    if let Some(i) = points.iter().position(|e| e.0 >= x) {
-        let x0 = points.get(if i == 0 { 0 } else { i-1 }).unwrap_or(&points[0]);
-        let x1 = &points[i];
-        let a = if x0.0 >= x1.0 {
-            0.5
-        } else {
-            assert!(x1.0 >= x0.0);
+                        Some(format!("{},{}", x, y))
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(invalid)?;
 
-            (x - x0.0) as f32 / (x1.0 - x0.0) as f32
+                points.join(":")
+            },
+            _ => return Err(invalid())
         };
 
-        (1.0 - a) * x0.1 + a * x1.1
-    } else {
-        points.last().unwrap().1
+        match self.set(name, &value_str) {
+            Ok(()) => Ok(()),
+            Err(SetError::UnknownOption) => Err(ConfigError::UnknownOption(full_name.to_string())),
+            Err(SetError::InvalidValue) => Err(invalid())
+        }
+    }
+
+    /// Sets the option with the given name to the given value.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the option
+    /// * `value` - the new value of the option
+    ///
+    fn set(&mut self, name: &str, value: &str) -> Result<(), SetError> {
+        fn parse<T: FromStr>(value: &str) -> Result<T, SetError> {
+            value.trim().parse::<T>().map_err(|_| SetError::InvalidValue)
+        }
+
+        fn parse_positive(value: &str) -> Result<usize, SetError> {
+            parse::<usize>(value).and_then(|v| if v > 0 { Ok(v) } else { Err(SetError::InvalidValue) })
+        }
+
+        match name {
+            "weights" => self.weights = Some(value.to_string()),
+            "opponent" => self.opponent = Some(value.to_string()),
+            "convert_weights" => self.convert_weights = Some(value.to_string()),
+            "provenance" => self.provenance = Some(value.to_string()),
+            "backend" => self.backend = parse(value)?,
+            "no_ponder" => self.no_ponder = parse(value)?,
+            "rules" => self.rules = parse(value)?,
+            "superko" => self.superko = Some(parse(value)?),
+            "no_resign" => self.no_resign = parse(value)?,
+            "safe_time_ms" => self.safe_time_ms = parse(value)?,
+            "num_rollout" => self.num_rollout = RolloutLimit::UserDefined(parse_positive(value)?),
+            "kl_threshold" => {
+                self.kl_threshold = parse::<f32>(value)
+                    .and_then(|v| if v >= 0.0 && v.is_finite() { Ok(v) } else { Err(SetError::InvalidValue) })?;
+            },
+            "full_search_prob" => {
+                self.full_search_prob = parse::<f32>(value)
+                    .and_then(|v| if v >= 0.0 && v <= 1.0 { Ok(v) } else { Err(SetError::InvalidValue) })?;
+            },
+            "num_cheap_rollout" => self.num_cheap_rollout = parse_positive(value)?,
+            "batch_size" => self.batch_size = parse_positive(value)?,
+            "num_games" => self.num_games = parse_positive(value)?,
+            "num_threads" => self.num_threads = parse_positive(value)?,
+            "num_samples" => self.num_samples = parse(value)?,
+            "max_tree_memory" => self.max_tree_memory = parse(value)?,
+            "seed" => self.seed = Some(parse(value)?),
+            "verbose" => self.verbose = parse(value)?,
+            "num_policy_rollout" => self.num_policy_rollout = parse_positive(value)?,
+            "softmax_temperature" => {
+                self.softmax_temperature = parse::<f32>(value)
+                    .and_then(|v| if v > 0.0 && v.is_finite() { Ok(v) } else { Err(SetError::InvalidValue) })?;
+            },
+            _ if SearchParams::NAMES.contains(&name) => {
+                self.search.set(name, value).map_err(|_| SetError::InvalidValue)?
+            },
+            _ => return Err(SetError::UnknownOption)
+        }
+
+        Ok(())
+    }
+
+    /// Returns the maximum number of bytes that the search trees may use, or
+    /// zero if they are unlimited.
+    pub fn max_tree_memory_bytes(&self) -> usize {
+        self.max_tree_memory.saturating_mul(1048576)
+    }
+
+    /// Returns whether to think in the background during idle time. This is
+    /// always disabled if a `seed` is given, since how far it gets depends on
+    /// the timing between commands.
+    pub fn is_pondering(&self) -> bool {
+        !self.no_ponder && self.seed.is_none()
+    }
+
+    /// Returns a description of this configuration.
+    pub fn description(&self) -> String {
+        vec! [
+            format!("RULES {}", self.rules),
+            format!("NO_RESIGN {}", self.no_resign),
+            format!("NUM_ROLLOUT {:?}", self.num_rollout),
+            format!("KL_THRESHOLD {}", self.kl_threshold),
+            format!("FULL_SEARCH_PROB {}", self.full_search_prob),
+            format!("NUM_CHEAP_ROLLOUT {}", self.num_cheap_rollout),
+            format!("MAX_TREE_MEMORY {}", self.max_tree_memory),
+            format!("SEED {:?}", self.seed),
+            format!("DIRICHLET_NOISE {}", self.search.dirichlet_noise),
+            format!("TEMPERATURE {}", self.search.temperature),
+            format!("SOFTMAX_TEMPERATURE {}", self.softmax_temperature),
+            format!("VLOSS_CNT {}", self.search.vloss_cnt),
+            format!("FPU_REDUCE {:?}", self.search.fpu_reduce),
+            format!("UCT_EXP {:?}", self.search.uct_exp),
+            format!("CRITICAL_VALUE {:?}", self.search.critical_value)
+        ].join("\n")
+    }
+
+    /// Returns an error if any options conflict with each other.
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.num_threads < self.num_games {
+            Err(ConfigError::Conflict("The number of threads must be at least the same as the number of games".to_string()))
+        } else if self.opponent.is_none() && if let Procedure::Match(_) = self.procedure { true } else { false } {
+            Err(ConfigError::Conflict("A match requires the weights of an opponent".to_string()))
+        } else if self.weights.is_none() && self.procedure == Procedure::ConvertWeights {
+            Err(ConfigError::Conflict("Converting weights requires the --weights to convert".to_string()))
+        } else if self.full_search_prob < 1.0 && self.num_cheap_rollout > usize::from(self.num_rollout) {
+            Err(ConfigError::Conflict("The number of cheap rollouts must be at most the number of rollouts".to_string()))
+        } else {
+            Ok(())
+        }
     }
 }
 
-/// Returns the UCT exploration constant as a function of the number of
-/// visits to the **current** node.
+/// The reason why an option could not be set, without the name of the
+/// option (since that depends on where it came from).
+#[derive(Debug)]
+enum SetError {
+    UnknownOption,
+    InvalidValue
+}
+
+/// Returns the given command-line arguments as a list of options and their
+/// values (if any).
 ///
 /// # Arguments
 ///
-/// * `visits` - 
+/// * `args` - the command-line arguments, excluding the program name
 ///
-pub fn get_uct_exp(visits: i32) -> f32 {
-    get_intp_value(&UCT_EXP, visits)
+fn parse_args(args: &[String]) -> Result<Vec<(String, Option<String>)>, ConfigError> {
+    let mut out = vec! [];
+    let mut i = 0;
+
+    while i < args.len() {
+        let arg = &args[i];
+        let next = args.get(i + 1).filter(|next| !next.starts_with("--"));
+
+        if !arg.starts_with("--") {
+            return Err(ConfigError::UnknownOption(arg.clone()));
+        } else if FLAGS.contains(&arg.as_str()) {
+            out.push((arg.clone(), None));
+        } else if OPTIONAL_VALUE.contains(&arg.as_str()) {
+            out.push((arg.clone(), next.cloned()));
+            i += next.is_some() as usize;
+        } else if let Some(next) = next {
+            out.push((arg.clone(), Some(next.clone())));
+            i += 1;
+        } else {
+            return Err(ConfigError::MissingValue(arg.clone()));
+        }
+
+        i += 1;
+    }
+
+    Ok(out)
 }
 
-/// Returns the first-play urgency constant as a function of the number of
-/// visits to the **current** node.
+/// The tunable parameters of the monte carlo tree search. A copy of these is
+/// taken at the start of every search, so changing them does not affect any
+/// search that is already running.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchParams {
+    /// The UCT exploration rate, as a function of the number of visits.
+    pub uct_exp: Vec<(i32, f32)>,
+
+    /// The _First Play Urgency_ reduction, as a function of the number of
+    /// visits.
+    pub fpu_reduce: Vec<(i32, f32)>,
+
+    /// The LCB critical value, as a function of the number of visits.
+    pub critical_value: Vec<(i32, f32)>,
+
+    /// The number of virtual losses to add during async probes.
+    pub vloss_cnt: i32,
+
+    /// The temperature of the move selection during the eight first moves.
+    pub temperature: f32,
+
+    /// The amount of dirichlet noise to add to the root node.
+    pub dirichlet_noise: f32
+}
+
+impl Default for SearchParams {
+    fn default() -> SearchParams {
+        SearchParams {
+            uct_exp: vec! [(0, 0.88), (3200, 1.44)],
+            fpu_reduce: vec! [(0, 0.35), (800, 0.22), (1600, 0.10)],
+            critical_value: vec! [(0, 1.645), (3200, 1.96), (16000, 2.576)],
+            vloss_cnt: 2,
+            temperature: 0.3,
+            dirichlet_noise: 0.05
+        }
+    }
+}
+
+impl SearchParams {
+    /// The names of all parameters, as accepted by `get` and `set`.
+    pub const NAMES: [&'static str; 6] = [
+        "uct_exp", "fpu_reduce", "critical_value", "vloss_cnt", "temperature", "dirichlet_noise"
+    ];
+
+    /// Returns the UCT exploration constant as a function of the number of
+    /// visits to the **current** node.
+    ///
+    /// # Arguments
+    ///
+    /// * `visits` - the number of visits to the current node
+    ///
+    #[inline]
+    pub fn uct_exp(&self, visits: i32) -> f32 {
+        get_intp_value(&self.uct_exp, visits)
+    }
+
+    /// Returns the first-play urgency constant as a function of the number
+    /// of visits to the **current** node.
+    ///
+    /// # Arguments
+    ///
+    /// * `visits` - the number of visits to the current node
+    ///
+    #[inline]
+    pub fn fpu_reduce(&self, visits: i32) -> f32 {
+        get_intp_value(&self.fpu_reduce, visits)
+    }
+
+    /// Returns the critical value to use when calculating the LCB of values
+    /// for the given number of visits.
+    ///
+    /// # Arguments
+    ///
+    /// * `visits` - the number of visits to the current node
+    ///
+    #[inline]
+    pub fn critical_value(&self, visits: i32) -> f32 {
+        get_intp_value(&self.critical_value, visits)
+    }
+
+    /// Returns the value of the parameter with the given name, in the same
+    /// format as accepted by `set`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the parameter
+    ///
+    pub fn get(&self, name: &str) -> Option<String> {
+        match name {
+            "uct_exp" => Some(format_intp_list(&self.uct_exp)),
+            "fpu_reduce" => Some(format_intp_list(&self.fpu_reduce)),
+            "critical_value" => Some(format_intp_list(&self.critical_value)),
+            "vloss_cnt" => Some(format!("{}", self.vloss_cnt)),
+            "temperature" => Some(format!("{}", self.temperature)),
+            "dirichlet_noise" => Some(format!("{}", self.dirichlet_noise)),
+            _ => None
+        }
+    }
+
+    /// Sets the parameter with the given name to the given value, if the
+    /// value is valid for that parameter. Interpolated parameters accept
+    /// either a single constant, or a list of `visits,value` points separated
+    /// by colons (e.g. `0,0.88:3200,1.44`).
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the parameter
+    /// * `value` - the new value of the parameter
+    ///
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let invalid = || format!("invalid value for {} -- {}", name, value);
+
+        match name {
+            "uct_exp" | "fpu_reduce" | "critical_value" => {
+                let points = parse_intp_list(value)
+                    .filter(|points| points.iter().all(|&(_, y)| y.is_finite() && y >= 0.0))
+                    .ok_or_else(invalid)?;
+
+                match name {
+                    "uct_exp" => self.uct_exp = points,
+                    "fpu_reduce" => self.fpu_reduce = points,
+                    _ => self.critical_value = points
+                }
+            },
+            "vloss_cnt" => {
+                self.vloss_cnt = value.parse::<i32>().ok()
+                    .filter(|&v| v >= 0 && v <= 64)
+                    .ok_or_else(invalid)?;
+            },
+            "temperature" => {
+                self.temperature = value.parse::<f32>().ok()
+                    .filter(|&v| v.is_finite() && v >= 0.0)
+                    .ok_or_else(invalid)?;
+            },
+            "dirichlet_noise" => {
+                self.dirichlet_noise = value.parse::<f32>().ok()
+                    .filter(|&v| v >= 0.0 && v <= 1.0)
+                    .ok_or_else(invalid)?;
+            },
+            _ => return Err(format!("unknown parameter -- {}", name))
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the interpolation points in the given string, which is either a
+/// single constant or a list of `x,y` points separated by colons. The points
+/// are sorted by their `x` value.
 ///
 /// # Arguments
 ///
-/// * `visits` - 
+/// * `s` - the string to parse
 ///
-pub fn get_fpu_reduce(visits: i32) -> f32 {
-    get_intp_value(&FPU_REDUCE, visits)
+fn parse_intp_list(s: &str) -> Option<Vec<(i32, f32)>> {
+    if let Ok(value) = s.parse::<f32>() {
+        return Some(vec! [(0, value)]);
+    }
+
+    let mut out = s.split(':')
+        .map(|point| {
+            let mut parts = point.splitn(2, ',');
+            let x = parts.next()?.trim().parse::<i32>().ok()?;
+            let y = parts.next()?.trim().parse::<f32>().ok()?;
+
+            Some((x, y))
+        })
+        .collect::<Option<Vec<(i32, f32)>>>()?;
+
+    out.sort_by_key(|p| { p.0 });
+    Some(out)
 }
 
-/// Returns the critical value to use when calculating the LCB of values for the given
-/// number of visits.
+/// Returns the given interpolation points in the format accepted by
+/// `parse_intp_list`.
 ///
 /// # Arguments
 ///
-/// * `visits` -
+/// * `points` - the points to format
 ///
-pub fn get_lcb_critical_value(visits: i32) -> f32 {
-    get_intp_value(&CRITICAL_VALUE, visits)
+fn format_intp_list(points: &[(i32, f32)]) -> String {
+    points.iter()
+        .map(|&(x, y)| format!("{},{}", x, y))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn get_intp_value(points: &[(i32, f32)], x: i32) -> f32 {
+    if let Some(i) = points.iter().position(|e| e.0 >= x) {
+        let x0 = points.get(if i == 0 { 0 } else { i-1 }).unwrap_or(&points[0]);
+        let x1 = &points[i];
+        let a = if x0.0 >= x1.0 {
+            0.5
+        } else {
+            assert!(x1.0 >= x0.0);
+
+            (x - x0.0) as f32 / (x1.0 - x0.0) as f32
+        };
+
+        (1.0 - a) * x0.1 + a * x1.1
+    } else {
+        points.last().unwrap().1
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn backend_from_str() {
+        assert_eq!("auto".parse::<Backend>(), Ok(Backend::Auto));
+        assert_eq!("CUDA".parse::<Backend>(), Ok(Backend::Cuda));
+        assert_eq!("cpu".parse::<Backend>(), Ok(Backend::Cpu));
+        assert_eq!("opencl".parse::<Backend>(), Err(()));
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_args_default() {
+        let config = Config::parse(&[], &[]).unwrap();
+
+        assert_eq!(config, Config::new(Procedure::Gtp));
+        assert_eq!(config.num_rollout, RolloutLimit::Default(1600));
+        assert_eq!(config.search.temperature, 0.3);
+    }
+
+    #[test]
+    fn parse_args_procedure() {
+        let config = Config::parse(&args(&["--policy-play", "--ex-it", "--num-games", "4"]), &[]).unwrap();
+
+        assert_eq!(config.procedure, Procedure::PolicyPlay(::std::usize::MAX, true));
+        assert_eq!(config.num_games, 4);
+        assert_eq!(config.search.dirichlet_noise, 0.25);
+        assert_eq!(Config::parse(&args(&["--self-play", "20"]), &[]).unwrap().procedure, Procedure::SelfPlay(20));
+    }
+
+    #[test]
+    fn parse_args_weights() {
+        assert_eq!(Config::parse(&[], &[]).unwrap().weights, None);
+        assert_eq!(
+            Config::parse(&args(&["--weights", "models/dream_go.json"]), &[]).unwrap().weights,
+            Some("models/dream_go.json".to_string())
+        );
+        assert_eq!(
+            Config::parse(&args(&["--weights"]), &[]),
+            Err(ConfigError::MissingValue("--weights".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_args_match() {
+        let config = Config::parse(&args(&["--match", "400", "--opponent", "models/other.json"]), &[]).unwrap();
+
+        assert_eq!(config.procedure, Procedure::Match(400));
+        assert_eq!(config.opponent, Some("models/other.json".to_string()));
+        assert_eq!(config.search.dirichlet_noise, 0.05);
+        assert_eq!(
+            Config::parse(&args(&["--match"]), &[]),
+            Err(ConfigError::Conflict("A match requires the weights of an opponent".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_args_convert_weights() {
+        let config = Config::parse(&args(&["--convert-weights", "dream_go.dgw", "--weights", "dream_go.json", "--provenance", "run 42"]), &[]).unwrap();
+
+        assert_eq!(config.procedure, Procedure::ConvertWeights);
+        assert_eq!(config.convert_weights, Some("dream_go.dgw".to_string()));
+        assert_eq!(config.provenance, Some("run 42".to_string()));
+        assert_eq!(
+            Config::parse(&args(&["--convert-weights", "dream_go.dgw"]), &[]),
+            Err(ConfigError::Conflict("Converting weights requires the --weights to convert".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_args_network_info() {
+        let config = Config::parse(&args(&["--network-info", "--weights", "dream_go.json"]), &[]).unwrap();
+
+        assert_eq!(config.procedure, Procedure::NetworkInfo);
+        assert_eq!(config.weights, Some("dream_go.json".to_string()));
+    }
+
+    #[test]
+    fn parse_args_unknown() {
+        assert_eq!(
+            Config::parse(&args(&["--num-rolout", "100"]), &[]),
+            Err(ConfigError::UnknownOption("--num-rolout".to_string()))
+        );
+        assert_eq!(
+            Config::parse(&args(&["--num-rollout", "many"]), &[]),
+            Err(ConfigError::InvalidValue("--num-rollout".to_string(), "many".to_string()))
+        );
+        assert_eq!(
+            Config::parse(&args(&["--num-rollout"]), &[]),
+            Err(ConfigError::MissingValue("--num-rollout".to_string()))
+        );
+        assert_eq!(
+            Config::parse(&args(&["--num-games", "128"]), &[]),
+            Err(ConfigError::Conflict("The number of threads must be at least the same as the number of games".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_args_max_tree_memory() {
+        assert_eq!(Config::parse(&[], &[]).unwrap().max_tree_memory, 0);
+        assert_eq!(Config::parse(&args(&["--max-tree-memory", "512"]), &[]).unwrap().max_tree_memory, 512);
+        assert_eq!(
+            Config::parse(&args(&["--max-tree-memory", "-1"]), &[]),
+            Err(ConfigError::InvalidValue("--max-tree-memory".to_string(), "-1".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_args_rules() {
+        assert_eq!(Config::parse(&[], &[]).unwrap().rules, RuleSet::Chinese);
+        assert_eq!(Config::parse(&args(&["--rules", "jp"]), &[]).unwrap().rules, RuleSet::Japanese);
+        assert_eq!(Config::parse(&args(&["--superko", "ssk"]), &[]).unwrap().superko, Some(SuperkoRule::Situational));
+        assert_eq!(
+            Config::parse(&args(&["--rules", "chinse"]), &[]),
+            Err(ConfigError::InvalidValue("--rules".to_string(), "chinse".to_string()))
+        );
+        assert_eq!(
+            Config::parse(&args(&["--superko", "natural"]), &[]),
+            Err(ConfigError::InvalidValue("--superko".to_string(), "natural".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_args_kl_threshold() {
+        assert_eq!(Config::parse(&[], &[]).unwrap().kl_threshold, 0.0);
+        assert_eq!(Config::parse(&args(&["--kl-threshold", "0.0001"]), &[]).unwrap().kl_threshold, 0.0001);
+        assert_eq!(
+            Config::parse(&args(&["--kl-threshold", "-0.5"]), &[]),
+            Err(ConfigError::InvalidValue("--kl-threshold".to_string(), "-0.5".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_args_full_search_prob() {
+        assert_eq!(Config::parse(&[], &[]).unwrap().full_search_prob, 1.0);
+        assert_eq!(Config::parse(&args(&["--full-search-prob", "0.25"]), &[]).unwrap().full_search_prob, 0.25);
+        assert_eq!(
+            Config::parse(&args(&["--full-search-prob", "1.5"]), &[]),
+            Err(ConfigError::InvalidValue("--full-search-prob".to_string(), "1.5".to_string()))
+        );
+        assert_eq!(
+            Config::parse(&args(&["--full-search-prob", "0.25", "--num-rollout", "50"]), &[]),
+            Err(ConfigError::Conflict("The number of cheap rollouts must be at most the number of rollouts".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_args_seed() {
+        assert_eq!(Config::parse(&[], &[]).unwrap().seed, None);
+        assert_eq!(Config::parse(&args(&["--seed", "42"]), &[]).unwrap().seed, Some(42));
+        assert_eq!(
+            Config::parse(&args(&["--seed", "x"]), &[]),
+            Err(ConfigError::InvalidValue("--seed".to_string(), "x".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_env() {
+        let vars = vec! [
+            ("UCT_EXP".to_string(), "0,0.5:1000,1.25".to_string()),
+            ("VLOSS_CNT".to_string(), "4".to_string())
+        ];
+        let config = Config::parse(&args(&["--vloss-cnt", "3", "--tt"]), &vars).unwrap();
+
+        assert_eq!(config.search.uct_exp, vec! [(0, 0.5), (1000, 1.25)]);
+        assert_eq!(config.search.vloss_cnt, 3);
+        assert_eq!(config.rules, RuleSet::TrompTaylor);
+        assert_eq!(
+            Config::parse(&[], &[("FPU_REDUCE".to_string(), "a lot".to_string())]),
+            Err(ConfigError::InvalidValue("FPU_REDUCE".to_string(), "a lot".to_string()))
+        );
+    }
+
+    #[test]
+    fn merge_toml() {
+        let mut config = Config::new(Procedure::Gtp);
+        let content = "
+            num_rollout = 800
+            backend = \"cpu\"
+            no_resign = true
+
+            [search]
+            uct_exp = [[0, 0.75], [3200, 1.5]]
+            fpu_reduce = 0.2
+            dirichlet_noise = 0
+        ";
+
+        assert_eq!(config.merge_toml(content), Ok(()));
+        assert_eq!(config.num_rollout, RolloutLimit::UserDefined(800));
+        assert_eq!(config.backend, Backend::Cpu);
+        assert!(config.no_resign);
+        assert_eq!(config.search.uct_exp, vec! [(0, 0.75), (3200, 1.5)]);
+        assert_eq!(config.search.fpu_reduce, vec! [(0, 0.2)]);
+        assert_eq!(config.search.dirichlet_noise, 0.0);
+    }
+
+    #[test]
+    fn merge_toml_strict() {
+        let mut config = Config::new(Procedure::Gtp);
+
+        assert_eq!(config.merge_toml("num_rolout = 800"), Err(ConfigError::UnknownOption("num_rolout".to_string())));
+        assert_eq!(config.merge_toml("uct_exp = 1.0"), Err(ConfigError::UnknownOption("uct_exp".to_string())));
+        assert_eq!(config.merge_toml("[search]\nuct = 1.0"), Err(ConfigError::UnknownOption("search.uct".to_string())));
+        assert_eq!(config.merge_toml("batch_size = 0"), Err(ConfigError::InvalidValue("batch_size".to_string(), "0".to_string())));
+        assert!(config.merge_toml("batch_size = ").is_err());
+        assert_eq!(config, Config::new(Procedure::Gtp));
+    }
+
+    #[test]
+    fn merge_toml_all_or_nothing() {
+        let mut config = Config::new(Procedure::Gtp);
+
+        assert!(config.merge_toml("batch_size = 32\nnum_rolout = 800").is_err());
+        assert!(config.merge_toml("batch_size = 32\n[search]\nuct_exp = -1.0").is_err());
+        assert!(config.merge_toml("batch_size = 32\nnum_games = 128").is_err());
+        assert_eq!(config, Config::new(Procedure::Gtp));
+
+        assert_eq!(config.merge_toml("batch_size = 32"), Ok(()));
+        assert_eq!(config.batch_size, 32);
+    }
+
+    #[test]
+    fn parse_intp() {
+        assert_eq!(parse_intp_list("0.5"), Some(vec! [(0, 0.5)]));
+        assert_eq!(parse_intp_list("800,0.22:0,0.35"), Some(vec! [(0, 0.35), (800, 0.22)]));
+        assert_eq!(parse_intp_list("0,0.35:800"), None);
+        assert_eq!(format_intp_list(&[(0, 0.35), (800, 0.22)]), "0,0.35:800,0.22");
+    }
+
+    #[test]
+    fn search_params_set() {
+        let mut params = SearchParams::default();
+
+        assert!(params.set("uct_exp", "0,1.0:100,2.0").is_ok());
+        assert_eq!(params.uct_exp(50), 1.5);
+        assert!(params.set("vloss_cnt", "3").is_ok());
+        assert_eq!(params.get("vloss_cnt"), Some("3".to_string()));
+        assert!(params.set("vloss_cnt", "-1").is_err());
+        assert!(params.set("dirichlet_noise", "1.5").is_err());
+        assert!(params.set("fpu_reduce", "0,-0.1").is_err());
+        assert!(params.set("foo", "1").is_err());
+        assert!(SearchParams::NAMES.iter().all(|name| params.get(name).is_some()));
+    }
+
     #[test]
     fn intp_out_of_bounds_1() {
         assert_eq!(get_intp_value(&vec! [(0, 0.0), (100, 1.0)], -100), 0.0);
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use ::config;
+use ::config::SearchParams;
 
 /// Returns the lower confidence bound of the normal distribution with
 /// mean `p_hat`, and `n` samples. Using the confidence interval for `m`
@@ -24,13 +24,35 @@ use ::config;
 /// * `p_std` -
 /// * `n` -
 /// * `m` -
+/// * `params` - the search parameters to get the critical value from
 ///
-pub fn normal_lcb_m(p_hat: f32, p_std: f32, n: i32, m: i32) -> f32 {
+pub fn normal_lcb_m(p_hat: f32, p_std: f32, n: i32, m: i32, params: &SearchParams) -> f32 {
     if n > 0 {
-        let z = config::get_lcb_critical_value(m);
+        let z = params.critical_value(m);
 
         p_hat - z * p_std / (n as f32).sqrt()
     } else {
         0.0
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn critical_value_from_params() {
+        let mut params = SearchParams::default();
+
+        params.critical_value = vec! [(0, 2.0)];
+        assert_eq!(normal_lcb_m(0.5, 0.1, 4, 0, &params), 0.4);
+
+        params.critical_value = vec! [(0, 1.0)];
+        assert_eq!(normal_lcb_m(0.5, 0.1, 4, 0, &params), 0.45);
+    }
+
+    #[test]
+    fn no_samples() {
+        assert_eq!(normal_lcb_m(0.5, 0.1, 0, 0, &SearchParams::default()), 0.0);
+    }
+}
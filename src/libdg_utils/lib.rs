@@ -16,7 +16,7 @@
 #![feature(test)]
 
 #[macro_use] extern crate lazy_static;
-extern crate regex;
+extern crate toml;
 #[cfg(test)] extern crate test;
 
 pub mod b85;